@@ -0,0 +1,334 @@
+use git2::Repository;
+use serde_json::{Value, json};
+
+/// Conventional Commit types in the order their sections appear in the changelog.
+/// Types not listed here are grouped under "Other Changes".
+static SECTIONS: &[(&str, &str)] = &[
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance Improvements"),
+    ("revert", "Reverts"),
+    ("refactor", "Code Refactoring"),
+    ("docs", "Documentation"),
+    ("style", "Styles"),
+    ("test", "Tests"),
+    ("build", "Build System"),
+    ("ci", "Continuous Integration"),
+    ("chore", "Chores"),
+];
+
+const OTHER_SECTION: &str = "Other Changes";
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum ChangelogFormat {
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConventionalCommit {
+    pub kind: Option<String>,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+}
+
+/// Parse a commit message according to the Conventional Commits spec.
+/// Messages that don't follow the `type(scope)!: description` header are
+/// returned with `kind: None` and the first line as their description.
+pub fn parse_conventional_commit(message: &str) -> ConventionalCommit {
+    let header = message.lines().next().unwrap_or("").trim();
+    let breaking_footer = message
+        .lines()
+        .any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:"));
+
+    let parsed = header.split_once(": ").and_then(|(prefix, description)| {
+        let (prefix, bang) = match prefix.strip_suffix('!') {
+            Some(p) => (p, true),
+            None => (prefix, false),
+        };
+        let (kind, scope) = match prefix.split_once('(') {
+            Some((kind, rest)) => (kind, Some(rest.strip_suffix(')')?)),
+            None => (prefix, None),
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(ConventionalCommit {
+            kind: Some(kind.to_lowercase()),
+            scope: scope.filter(|s| !s.is_empty()).map(|s| s.to_string()),
+            breaking: bang || breaking_footer,
+            description: description.trim().to_string(),
+        })
+    });
+
+    parsed.unwrap_or_else(|| ConventionalCommit {
+        kind: None,
+        scope: None,
+        breaking: breaking_footer,
+        description: header.to_string(),
+    })
+}
+
+/// Turn a git remote URL into the web URL of the repository, e.g.
+/// `git@github.com:owner/repo.git` becomes `https://github.com/owner/repo`. Remotes that
+/// aren't hosted anywhere, such as `file://` URLs and local paths, have none.
+pub fn remote_web_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+
+    let (host, path) = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .or_else(|| url.strip_prefix("ssh://"))
+        .or_else(|| url.strip_prefix("git://"))
+    {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next()?;
+        // Drop an explicit port; web URLs are served on the default one.
+        (host.split(':').next()?, path)
+    } else if url.contains("://") {
+        return None;
+    } else {
+        // scp-like syntax: [user@]host:path. As in git, a path with a slash before the
+        // colon, or a drive letter, is local.
+        let (authority, path) = url.split_once(':')?;
+        if authority.contains(['/', '\\']) || authority.len() == 1 {
+            return None;
+        }
+        (authority.rsplit('@').next()?, path)
+    };
+
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{}/{}", host, path.trim_start_matches('/')))
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangelogEntry {
+    pub hash: String,
+    pub commit: ConventionalCommit,
+}
+
+/// Collect the commits reachable from `to` but not from `from`, newest first.
+pub fn collect_entries(
+    repo: &Repository,
+    from: Option<&str>,
+    to: &str,
+) -> anyhow::Result<Vec<ChangelogEntry>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(repo.revparse_single(to)?.peel_to_commit()?.id())?;
+    if let Some(from) = from {
+        revwalk.hide(repo.revparse_single(from)?.peel_to_commit()?.id())?;
+    }
+
+    let mut entries = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        entries.push(ChangelogEntry {
+            hash: commit.id().to_string(),
+            commit: parse_conventional_commit(commit.message().unwrap_or("")),
+        });
+    }
+    Ok(entries)
+}
+
+fn section_title(kind: Option<&str>) -> &'static str {
+    kind.and_then(|kind| {
+        SECTIONS
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, title)| *title)
+    })
+    .unwrap_or(OTHER_SECTION)
+}
+
+/// Group entries into sections, in changelog order, skipping empty ones.
+fn group_entries(entries: &[ChangelogEntry]) -> Vec<(&'static str, Vec<&ChangelogEntry>)> {
+    let titles = SECTIONS
+        .iter()
+        .map(|(_, title)| *title)
+        .chain(std::iter::once(OTHER_SECTION));
+
+    titles
+        .map(|title| {
+            let members: Vec<_> = entries
+                .iter()
+                .filter(|e| section_title(e.commit.kind.as_deref()) == title)
+                .collect();
+            (title, members)
+        })
+        .filter(|(_, members)| !members.is_empty())
+        .collect()
+}
+
+fn commit_url(base_url: Option<&str>, hash: &str) -> Option<String> {
+    base_url.map(|base| format!("{}/commit/{}", base, hash))
+}
+
+pub fn render_markdown(entries: &[ChangelogEntry], base_url: Option<&str>) -> String {
+    let format_line = |entry: &ChangelogEntry| {
+        let short = &entry.hash[..7.min(entry.hash.len())];
+        let link = match commit_url(base_url, &entry.hash) {
+            Some(url) => format!("[{}]({})", short, url),
+            None => short.to_string(),
+        };
+        match &entry.commit.scope {
+            Some(scope) => format!("* **{}:** {} ({})\n", scope, entry.commit.description, link),
+            None => format!("* {} ({})\n", entry.commit.description, link),
+        }
+    };
+
+    let mut out = String::new();
+    for (title, members) in group_entries(entries) {
+        out.push_str(&format!("### {}\n\n", title));
+        for entry in members {
+            out.push_str(&format_line(entry));
+        }
+        out.push('\n');
+    }
+
+    let breaking: Vec<_> = entries.iter().filter(|e| e.commit.breaking).collect();
+    if !breaking.is_empty() {
+        out.push_str("### BREAKING CHANGES\n\n");
+        for entry in breaking {
+            out.push_str(&format_line(entry));
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+pub fn render_json(entries: &[ChangelogEntry], base_url: Option<&str>) -> Value {
+    let sections: Vec<Value> = group_entries(entries)
+        .into_iter()
+        .map(|(title, members)| {
+            let commits: Vec<Value> = members
+                .into_iter()
+                .map(|entry| {
+                    json!({
+                        "hash": entry.hash,
+                        "type": entry.commit.kind,
+                        "scope": entry.commit.scope,
+                        "description": entry.commit.description,
+                        "breaking": entry.commit.breaking,
+                        "url": commit_url(base_url, &entry.hash),
+                    })
+                })
+                .collect();
+            json!({ "title": title, "commits": commits })
+        })
+        .collect();
+
+    json!({ "repository_url": base_url, "sections": sections })
+}
+
+/// Generate a changelog for `from..to` using the repository's `origin` remote for links.
+pub fn generate(
+    repo: &Repository,
+    from: Option<&str>,
+    to: &str,
+    format: &ChangelogFormat,
+) -> anyhow::Result<String> {
+    let entries = collect_entries(repo, from, to)?;
+    let base_url = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().ok().and_then(remote_web_url));
+
+    Ok(match format {
+        ChangelogFormat::Markdown => render_markdown(&entries, base_url.as_deref()),
+        ChangelogFormat::Json => {
+            serde_json::to_string_pretty(&render_json(&entries, base_url.as_deref()))?
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: &str, message: &str) -> ChangelogEntry {
+        ChangelogEntry {
+            hash: hash.to_string(),
+            commit: parse_conventional_commit(message),
+        }
+    }
+
+    #[test]
+    fn test_parse_conventional_commit_with_scope() {
+        let commit = parse_conventional_commit("feat(cli): add changelog command");
+        assert_eq!(commit.kind.as_deref(), Some("feat"));
+        assert_eq!(commit.scope.as_deref(), Some("cli"));
+        assert_eq!(commit.description, "add changelog command");
+        assert!(!commit.breaking);
+    }
+
+    #[test]
+    fn test_parse_conventional_commit_breaking() {
+        assert!(parse_conventional_commit("fix!: drop old flag").breaking);
+        assert!(parse_conventional_commit("fix: drop old flag\n\nBREAKING CHANGE: gone").breaking);
+    }
+
+    #[test]
+    fn test_parse_non_conventional_commit() {
+        let commit = parse_conventional_commit("Initial commit\n\nbody");
+        assert_eq!(commit.kind, None);
+        assert_eq!(commit.description, "Initial commit");
+    }
+
+    #[test]
+    fn test_remote_web_url() {
+        let expected = Some("https://github.com/starfy84/git-seek".to_string());
+        assert_eq!(
+            remote_web_url("git@github.com:starfy84/git-seek.git"),
+            expected
+        );
+        assert_eq!(
+            remote_web_url("https://github.com/starfy84/git-seek.git"),
+            expected
+        );
+        assert_eq!(
+            remote_web_url("ssh://git@github.com:22/starfy84/git-seek"),
+            expected
+        );
+        assert_eq!(remote_web_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_remote_web_url_local_remotes() {
+        assert_eq!(remote_web_url("file:///srv/git/project.git"), None);
+        assert_eq!(remote_web_url("file://localhost/srv/git/project.git"), None);
+        assert_eq!(remote_web_url("/srv/git/project.git"), None);
+        assert_eq!(remote_web_url("../project"), None);
+        assert_eq!(remote_web_url("./backups:2024/project.git"), None);
+        assert_eq!(remote_web_url("C:\\git\\project.git"), None);
+    }
+
+    #[test]
+    fn test_render_markdown_groups_by_type() {
+        let entries = vec![
+            entry("aaaaaaaaaa", "fix: handle empty repo"),
+            entry("bbbbbbbbbb", "feat(cli): add changelog"),
+            entry("cccccccccc", "update readme"),
+        ];
+        let markdown = render_markdown(&entries, Some("https://example.com/o/r"));
+        let features = markdown.find("### Features").unwrap();
+        let fixes = markdown.find("### Bug Fixes").unwrap();
+        let other = markdown.find("### Other Changes").unwrap();
+        assert!(features < fixes && fixes < other);
+        assert!(markdown.contains(
+            "* **cli:** add changelog ([bbbbbbb](https://example.com/o/r/commit/bbbbbbbbbb))"
+        ));
+    }
+
+    #[test]
+    fn test_render_json_without_remote() {
+        let entries = vec![entry("aaaaaaaaaa", "feat!: new api")];
+        let value = render_json(&entries, None);
+        assert_eq!(value["sections"][0]["title"], "Features");
+        assert_eq!(value["sections"][0]["commits"][0]["breaking"], true);
+        assert_eq!(value["sections"][0]["commits"][0]["url"], Value::Null);
+    }
+}
@@ -0,0 +1,274 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, ListArray, RecordBatch, StringArray,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{DataType, Field, Schema as ArrowSchema, SchemaRef};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use trustfall::{FieldValue, Schema};
+use trustfall_core::ir::Type;
+use trustfall_git_adapter::Row;
+
+/// The Arrow schema for the outputs of `query`, in the same order as
+/// [`columns`](crate::output::columns).
+///
+/// Types come from the query rather than its results, so they're the same however many
+/// rows there are: `Int` becomes `Int64`, `Float` `Float64`, `Boolean` `Boolean`, other
+/// types `Utf8`, and lists, such as `@fold` outputs, `List` columns.
+pub fn schema(schema: &Schema, query: &str) -> anyhow::Result<SchemaRef> {
+    let query = trustfall_core::frontend::parse(schema, query)?;
+    let fields: Vec<Field> = query
+        .outputs
+        .values()
+        .map(|output| field(&output.name, &output.value_type))
+        .collect();
+    Ok(Arc::new(ArrowSchema::new(fields)))
+}
+
+/// `schema` with only `columns`, in that order, each typed like the field `original`
+/// gives for it, for `--columns` and `--rename`.
+pub fn select<'a>(
+    schema: &ArrowSchema,
+    columns: &'a [Arc<str>],
+    original: impl Fn(&'a str) -> &'a str,
+) -> anyhow::Result<SchemaRef> {
+    let fields = columns
+        .iter()
+        .map(|column| {
+            let field = schema.field_with_name(original(column))?;
+            Ok(field.clone().with_name(column.as_ref()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Arc::new(ArrowSchema::new(fields)))
+}
+
+fn field(name: &str, ty: &Type) -> Field {
+    Field::new(name, data_type(ty), ty.nullable())
+}
+
+fn data_type(ty: &Type) -> DataType {
+    if let Some(item) = ty.as_list() {
+        return DataType::List(Arc::new(field("item", &item)));
+    }
+    match ty.base_type() {
+        "Int" => DataType::Int64,
+        "Float" => DataType::Float64,
+        "Boolean" => DataType::Boolean,
+        _ => DataType::Utf8,
+    }
+}
+
+/// `rows` as a single batch with the columns of `schema`.
+pub fn record_batch(schema: SchemaRef, rows: &[Row]) -> anyhow::Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let values: Vec<&FieldValue> = rows
+                .iter()
+                .map(|row| row.get(field.name().as_str()).unwrap_or(&FieldValue::Null))
+                .collect();
+            array(field.name(), field.data_type(), &values)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn array(name: &str, data_type: &DataType, values: &[&FieldValue]) -> anyhow::Result<ArrayRef> {
+    let mismatch = |value: &FieldValue| {
+        anyhow::anyhow!("Output '{name}' has the value {value:?}, which isn't a {data_type}")
+    };
+    Ok(match data_type {
+        DataType::Int64 => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    FieldValue::Null => Ok(None),
+                    FieldValue::Int64(n) => Ok(Some(*n)),
+                    FieldValue::Uint64(n) => {
+                        i64::try_from(*n).map(Some).map_err(|_| mismatch(value))
+                    }
+                    _ => Err(mismatch(value)),
+                })
+                .collect::<anyhow::Result<Int64Array>>()?,
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    FieldValue::Null => Ok(None),
+                    FieldValue::Float64(f) => Ok(Some(*f)),
+                    _ => Err(mismatch(value)),
+                })
+                .collect::<anyhow::Result<Float64Array>>()?,
+        ),
+        DataType::Boolean => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    FieldValue::Null => Ok(None),
+                    FieldValue::Boolean(b) => Ok(Some(*b)),
+                    _ => Err(mismatch(value)),
+                })
+                .collect::<anyhow::Result<BooleanArray>>()?,
+        ),
+        DataType::List(item) => {
+            let mut items = Vec::new();
+            let mut lengths = Vec::with_capacity(values.len());
+            let mut valid = Vec::with_capacity(values.len());
+            for value in values {
+                match value {
+                    FieldValue::Null => {
+                        lengths.push(0);
+                        valid.push(false);
+                    }
+                    FieldValue::List(list) => {
+                        items.extend(list.iter());
+                        lengths.push(list.len());
+                        valid.push(true);
+                    }
+                    _ => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(ListArray::try_new(
+                item.clone(),
+                OffsetBuffer::from_lengths(lengths),
+                array(name, item.data_type(), &items)?,
+                Some(NullBuffer::from(valid)),
+            )?)
+        }
+        _ => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    FieldValue::Null => Ok(None),
+                    FieldValue::String(s) => Ok(Some(s.to_string())),
+                    FieldValue::Enum(s) => Ok(Some(s.to_string())),
+                    _ => Err(mismatch(value)),
+                })
+                .collect::<anyhow::Result<StringArray>>()?,
+        ),
+    })
+}
+
+/// Write `batch` to a Snappy-compressed Parquet file at `path`.
+pub fn write_parquet(path: &Path, batch: &RecordBatch) -> anyhow::Result<()> {
+    let file = create(path)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Write `batch` to an Arrow IPC file at `path`.
+pub fn write_ipc(path: &Path, batch: &RecordBatch) -> anyhow::Result<()> {
+    let file = create(path)?;
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn create(path: &Path) -> anyhow::Result<File> {
+    File::create(path).map_err(|e| anyhow::anyhow!("Could not create '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use trustfall_git_adapter::{GitAdapter, backend::MemoryBackend};
+
+    fn row(fields: &[(&str, FieldValue)]) -> Row {
+        fields
+            .iter()
+            .map(|(name, value)| (Arc::from(*name), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_from_output_types() {
+        let backend = MemoryBackend::builder("fixture")
+            .commit("init", |c| c.message("Initial commit").file("a.txt", "a\n"))
+            .branch("main", "init")
+            .build();
+        let adapter = GitAdapter::with_backend(backend);
+        let query = r#"{repository {commits {
+            hash @output
+            message @output
+            generation @output
+            changes @fold {path @output additions @output}
+        }}}"#;
+        let schema = schema(adapter.schema(), query).unwrap();
+        let list = |data_type| DataType::List(Arc::new(Field::new("item", data_type, false)));
+        let expected = ArrowSchema::new(vec![
+            Field::new("additions", list(DataType::Int64), false),
+            Field::new("generation", DataType::Int64, true),
+            Field::new("hash", DataType::Utf8, false),
+            Field::new("message", DataType::Utf8, true),
+            Field::new("path", list(DataType::Utf8), false),
+        ]);
+        assert_eq!(*schema, expected);
+
+        // Rows from the adapter fit the schema.
+        let rows = adapter
+            .execute(query, std::collections::BTreeMap::<&str, &str>::new())
+            .unwrap();
+        let batch = record_batch(schema, &rows).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+    }
+
+    #[test]
+    fn test_record_batch_lists_and_nulls() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("count", DataType::Int64, false),
+            Field::new(
+                "paths",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, false))),
+                true,
+            ),
+        ]));
+        let paths =
+            |paths: &[&str]| FieldValue::List(paths.iter().map(|path| (*path).into()).collect());
+        let rows = vec![
+            row(&[
+                ("count", FieldValue::Uint64(2)),
+                ("paths", paths(&["a", "b"])),
+            ]),
+            row(&[("count", FieldValue::Int64(0)), ("paths", FieldValue::Null)]),
+            row(&[("count", FieldValue::Int64(1)), ("paths", paths(&["c"]))]),
+        ];
+        let batch = record_batch(schema.clone(), &rows).unwrap();
+
+        let counts = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(counts.values(), &[2, 0, 1]);
+        let lists = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap();
+        assert_eq!(lists.value_offsets(), &[0, 2, 2, 3]);
+        assert!(lists.is_null(1));
+        let items = lists
+            .values()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(items.iter().flatten().collect::<Vec<_>>(), ["a", "b", "c"]);
+
+        let rows = vec![row(&[
+            ("count", "many".into()),
+            ("paths", FieldValue::Null),
+        ])];
+        let error = record_batch(schema, &rows).unwrap_err();
+        assert!(error.to_string().contains("Output 'count'"));
+    }
+}
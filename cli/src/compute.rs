@@ -0,0 +1,356 @@
+use chrono::{Datelike, NaiveDate, Timelike, Utc};
+use trustfall::FieldValue;
+use trustfall_git_adapter::Row;
+
+use crate::template::parse_date;
+
+const FUNCTIONS: &str = "substr(COL,START[,LEN]), date_trunc(COL,UNIT), lower(COL), upper(COL), \
+                         first_line(COL), length(COL)";
+
+/// A `--compute NAME=EXPRESSION` column, e.g. `short=substr(hash,0,8)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Computed {
+    pub name: String,
+    pub expression: Expression,
+}
+
+/// Parse `NAME=EXPRESSION`.
+pub fn parse_compute(value: &str) -> Result<Computed, String> {
+    let (name, expression) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=EXPRESSION, got '{value}'"))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("missing column name in '{value}'"));
+    }
+    Ok(Computed {
+        name: name.to_string(),
+        expression: Expression::parse(expression)?,
+    })
+}
+
+/// A column, or a function applied to an expression. Functions take their input
+/// first, followed by literal arguments such as `8` or `'month'`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Column(String),
+    Call {
+        function: Function,
+        input: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Function {
+    /// Characters from `start`, counting from 0, up to `length` of them.
+    Substr {
+        start: usize,
+        length: Option<usize>,
+    },
+    /// The date at the start of its year, quarter, month, week, day or hour.
+    DateTrunc(DateUnit),
+    Lower,
+    Upper,
+    FirstLine,
+    /// The number of characters.
+    Length,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateUnit {
+    Year,
+    Quarter,
+    Month,
+    /// Weeks start on Monday.
+    Week,
+    Day,
+    Hour,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser { rest: source };
+        let expression = parser.expression()?;
+        parser.skip_space();
+        if !parser.rest.is_empty() {
+            return Err(format!(
+                "unexpected '{}' in expression '{source}'",
+                parser.rest
+            ));
+        }
+        Ok(expression)
+    }
+
+    /// The column the expression reads.
+    pub fn column(&self) -> &str {
+        match self {
+            Self::Column(column) => column,
+            Self::Call { input, .. } => input.column(),
+        }
+    }
+
+    /// The expression's value for `row`. Nulls stay null and functions apply to each
+    /// item of a list.
+    pub fn evaluate(&self, row: &Row) -> anyhow::Result<FieldValue> {
+        match self {
+            Self::Column(column) => Ok(row
+                .get(column.as_str())
+                .cloned()
+                .unwrap_or(FieldValue::Null)),
+            Self::Call { function, input } => function.apply(&input.evaluate(row)?),
+        }
+    }
+}
+
+impl Function {
+    fn apply(&self, value: &FieldValue) -> anyhow::Result<FieldValue> {
+        let text = match value {
+            FieldValue::Null => return Ok(FieldValue::Null),
+            FieldValue::List(items) => {
+                return Ok(FieldValue::List(
+                    items
+                        .iter()
+                        .map(|item| self.apply(item))
+                        .collect::<anyhow::Result<_>>()?,
+                ));
+            }
+            FieldValue::String(s) | FieldValue::Enum(s) => s.to_string(),
+            FieldValue::Int64(n) => n.to_string(),
+            FieldValue::Uint64(n) => n.to_string(),
+            FieldValue::Float64(f) => f.to_string(),
+            FieldValue::Boolean(b) => b.to_string(),
+            _ => anyhow::bail!("can't compute with {value:?}"),
+        };
+        Ok(match self {
+            Self::Substr { start, length } => {
+                let chars = text.chars().skip(*start);
+                match length {
+                    Some(length) => chars.take(*length).collect::<String>(),
+                    None => chars.collect(),
+                }
+                .into()
+            }
+            Self::DateTrunc(unit) => unit
+                .truncate(&text)
+                .ok_or_else(|| anyhow::anyhow!("date_trunc needs a date, got '{text}'"))?
+                .into(),
+            Self::Lower => text.to_lowercase().into(),
+            Self::Upper => text.to_uppercase().into(),
+            Self::FirstLine => text.lines().next().unwrap_or_default().into(),
+            Self::Length => FieldValue::Int64(text.chars().count() as i64),
+        })
+    }
+}
+
+impl DateUnit {
+    fn parse(unit: &str) -> Result<Self, String> {
+        Ok(match unit {
+            "year" => Self::Year,
+            "quarter" => Self::Quarter,
+            "month" => Self::Month,
+            "week" => Self::Week,
+            "day" => Self::Day,
+            "hour" => Self::Hour,
+            _ => {
+                return Err(format!(
+                    "unknown date_trunc unit '{unit}'; use year, quarter, month, week, day or hour"
+                ));
+            }
+        })
+    }
+
+    /// The start of the period holding `date`, in UTC, so that dates recorded with
+    /// different offsets fall into the same periods.
+    fn truncate(self, date: &str) -> Option<String> {
+        let date = parse_date(date)?.with_timezone(&Utc);
+        let day = match self {
+            Self::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1)?,
+            Self::Quarter => {
+                NaiveDate::from_ymd_opt(date.year(), (date.month() - 1) / 3 * 3 + 1, 1)?
+            }
+            Self::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?,
+            Self::Week => {
+                date.date_naive() - chrono::Days::new(date.weekday().num_days_from_monday().into())
+            }
+            Self::Day | Self::Hour => date.date_naive(),
+        };
+        let hour = if self == Self::Hour { date.hour() } else { 0 };
+        let start = day.and_hms_opt(hour, 0, 0)?.and_utc();
+        Some(start.to_rfc3339())
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn word(&mut self) -> Result<&str, String> {
+        self.skip_space();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err(format!("expected a column or function at '{}'", self.rest));
+        }
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(word)
+    }
+
+    /// A quoted string, a number or a bare word.
+    fn literal(&mut self) -> Result<String, String> {
+        self.skip_space();
+        for quote in ['\'', '"'] {
+            if let Some(quoted) = self.rest.strip_prefix(quote) {
+                let end = quoted
+                    .find(quote)
+                    .ok_or_else(|| format!("unclosed {quote} in expression"))?;
+                self.rest = &quoted[end + 1..];
+                return Ok(quoted[..end].to_string());
+            }
+        }
+        Ok(self.word()?.to_string())
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let name = self.word()?.to_string();
+        if !self.eat('(') {
+            return Ok(Expression::Column(name));
+        }
+        let input = self.expression()?;
+        let mut args = Vec::new();
+        while self.eat(',') {
+            args.push(self.literal()?);
+        }
+        if !self.eat(')') {
+            return Err(format!("expected ')' after the arguments of {name}"));
+        }
+
+        let number = |arg: &String| {
+            arg.parse::<usize>()
+                .map_err(|_| format!("{name} needs a number, got '{arg}'"))
+        };
+        let function = match (name.as_str(), args.as_slice()) {
+            ("substr", [start]) => Function::Substr {
+                start: number(start)?,
+                length: None,
+            },
+            ("substr", [start, length]) => Function::Substr {
+                start: number(start)?,
+                length: Some(number(length)?),
+            },
+            ("date_trunc", [unit]) => Function::DateTrunc(DateUnit::parse(unit)?),
+            ("lower", []) => Function::Lower,
+            ("upper", []) => Function::Upper,
+            ("first_line", []) => Function::FirstLine,
+            ("length", []) => Function::Length,
+            ("substr" | "date_trunc" | "lower" | "upper" | "first_line" | "length", _) => {
+                return Err(format!(
+                    "wrong arguments for {name}; functions: {FUNCTIONS}"
+                ));
+            }
+            _ => return Err(format!("unknown function '{name}'; functions: {FUNCTIONS}")),
+        };
+        Ok(Expression::Call {
+            function,
+            input: Box::new(input),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn compute(expression: &str, value: FieldValue) -> FieldValue {
+        let row = Row::from([(Arc::from("x"), value)]);
+        Expression::parse(expression)
+            .unwrap()
+            .evaluate(&row)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_compute() {
+        let computed = parse_compute("short = upper(substr(hash, 0, 8))").unwrap();
+        assert_eq!(computed.name, "short");
+        assert_eq!(computed.expression.column(), "hash");
+        assert_eq!(
+            parse_compute("m=date_trunc(date, 'month')")
+                .unwrap()
+                .expression,
+            Expression::Call {
+                function: Function::DateTrunc(DateUnit::Month),
+                input: Box::new(Expression::Column("date".to_string())),
+            }
+        );
+        assert!(parse_compute("substr(hash,0,8)").is_err());
+        assert!(parse_compute("s=substr(hash)").is_err());
+        assert!(parse_compute("s=substr(hash,zero)").is_err());
+        assert!(parse_compute("s=reverse(hash)").is_err());
+        assert!(parse_compute("s=lower(hash").is_err());
+        assert!(parse_compute("s=date_trunc(date,'fortnight')").is_err());
+    }
+
+    #[test]
+    fn test_text_functions() {
+        assert_eq!(
+            compute("substr(x,0,8)", "0123456789ab".into()),
+            "01234567".into()
+        );
+        assert_eq!(compute("substr(x, 10)", "0123456789ab".into()), "ab".into());
+        assert_eq!(compute("upper(x)", "Ab".into()), "AB".into());
+        assert_eq!(
+            compute("first_line(x)", "Subject\n\nBody".into()),
+            "Subject".into()
+        );
+        assert_eq!(compute("length(x)", "héllo".into()), FieldValue::Int64(5));
+        assert_eq!(compute("length(x)", FieldValue::Null), FieldValue::Null);
+        assert_eq!(
+            compute(
+                "substr(x,0,1)",
+                FieldValue::List(vec!["ab".into(), "cd".into()].into())
+            ),
+            FieldValue::List(vec!["a".into(), "c".into()].into())
+        );
+    }
+
+    #[test]
+    fn test_date_trunc() {
+        let date = || FieldValue::from("2024-05-16T14:30:15+02:00");
+        let trunc = |unit: &str| compute(&format!("date_trunc(x,{unit})"), date());
+        assert_eq!(trunc("year"), "2024-01-01T00:00:00+00:00".into());
+        assert_eq!(trunc("quarter"), "2024-04-01T00:00:00+00:00".into());
+        assert_eq!(trunc("'month'"), "2024-05-01T00:00:00+00:00".into());
+        assert_eq!(trunc("week"), "2024-05-13T00:00:00+00:00".into());
+        assert_eq!(trunc("day"), "2024-05-16T00:00:00+00:00".into());
+        assert_eq!(trunc("hour"), "2024-05-16T12:00:00+00:00".into());
+        // Late on the 31st in UTC-5 is already June in UTC.
+        assert_eq!(
+            compute("date_trunc(x,month)", "2024-05-31T22:00:00-05:00".into()),
+            "2024-06-01T00:00:00+00:00".into()
+        );
+
+        let row = Row::from([(Arc::from("x"), FieldValue::from("soon"))]);
+        let expression = Expression::parse("date_trunc(x, day)").unwrap();
+        assert!(expression.evaluate(&row).is_err());
+    }
+}
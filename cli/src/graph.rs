@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, HashMap};
+
+use trustfall::FieldValue;
+use trustfall_git_adapter::{GitAdapter, Row};
+
+/// Looks up one commit to draw by its hash, wherever it is, without walking history.
+/// Edge arguments can't be variables, so `$hash` is replaced with the hash itself.
+const COMMIT_QUERY: &str = r#"{
+  repository {
+    commit(hash: "$hash") {
+      message @output
+      parents @fold {
+        parents: hash @output
+      }
+    }
+  }
+}"#;
+
+const BRANCHES_QUERY: &str = r#"{
+  repository {
+    branches {
+      name @output
+      commit {
+        hash @output @filter(op: "one_of", value: ["$hashes"])
+      }
+    }
+  }
+}"#;
+
+const TAGS_QUERY: &str = r#"{
+  repository {
+    tags {
+      name @output
+      commit {
+        hash @output @filter(op: "one_of", value: ["$hashes"])
+      }
+    }
+  }
+}"#;
+
+/// The commits a query matched, with the parent edges between them and the branches
+/// and tags pointing at them, for `--format dot` and `--format mermaid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitGraph {
+    commits: Vec<GraphCommit>,
+    /// Child and parent, as indexes into `commits`.
+    edges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct GraphCommit {
+    hash: String,
+    /// The first line of the message, when the commit was found.
+    subject: Option<String>,
+    /// Branch names, then `tag: <name>` for tags, like `git log --decorate`.
+    labels: Vec<String>,
+}
+
+impl CommitGraph {
+    /// The commits in `hashes`, in order of first appearance, from the `hash` outputs of
+    /// `rows`. Lists, such as `@fold` outputs, contribute each of their hashes.
+    pub fn hashes(rows: &[Row]) -> Vec<String> {
+        fn collect(value: &FieldValue, hashes: &mut Vec<String>) {
+            match value {
+                FieldValue::String(hash) => hashes.push(hash.to_string()),
+                FieldValue::List(items) => items.iter().for_each(|item| collect(item, hashes)),
+                _ => {}
+            }
+        }
+        let mut hashes = Vec::new();
+        for value in rows.iter().filter_map(|row| row.get("hash")) {
+            collect(value, &mut hashes);
+        }
+        hashes
+    }
+
+    /// Look up `hashes` in the adapter's repository. Only parent edges between these
+    /// commits are drawn; hashes that aren't commits of the repository are drawn alone.
+    pub fn load(adapter: &GitAdapter, hashes: Vec<String>) -> anyhow::Result<Self> {
+        let mut commits: Vec<GraphCommit> = Vec::new();
+        let mut index = HashMap::new();
+        for hash in hashes {
+            if !index.contains_key(&hash) {
+                index.insert(hash.clone(), commits.len());
+                commits.push(GraphCommit {
+                    hash,
+                    subject: None,
+                    labels: Vec::new(),
+                });
+            }
+        }
+
+        let list = FieldValue::List(
+            commits
+                .iter()
+                .map(|commit| FieldValue::String(commit.hash.as_str().into()))
+                .collect(),
+        );
+        let variables = BTreeMap::from([("hashes", list)]);
+
+        let mut parents = Vec::new();
+        for (i, commit) in commits.iter_mut().enumerate() {
+            // Only hex digits are written into the query; anything else is drawn alone.
+            if !commit.hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let query = COMMIT_QUERY.replace("$hash", &commit.hash);
+            let rows = adapter.execute(&query, BTreeMap::<&str, &str>::new())?;
+            let Some(row) = rows.first() else {
+                continue;
+            };
+            commit.subject = Some(
+                string(row, "message")
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+            if let Some(FieldValue::List(hashes)) = row.get("parents") {
+                parents.extend(
+                    hashes
+                        .iter()
+                        .filter_map(|parent| index.get(parent.as_str()?))
+                        .map(|&parent| (i, parent)),
+                );
+            }
+        }
+
+        for (query, prefix) in [(BRANCHES_QUERY, ""), (TAGS_QUERY, "tag: ")] {
+            for row in adapter.execute(query, variables.clone())? {
+                let Some(&i) = index.get(string(&row, "hash")) else {
+                    continue;
+                };
+                commits[i]
+                    .labels
+                    .push(format!("{prefix}{}", string(&row, "name")));
+            }
+        }
+
+        Ok(Self {
+            commits,
+            edges: parents,
+        })
+    }
+
+    /// Render the graph in Graphviz DOT, with edges from each commit to its parents.
+    pub fn to_dot(&self) -> String {
+        let escape = |text: String| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = String::from("digraph commits {\n");
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for commit in &self.commits {
+            out.push_str(&format!(
+                "  \"{}\" [label=\"{}\"];\n",
+                commit.hash,
+                commit
+                    .label_lines()
+                    .map(escape)
+                    .collect::<Vec<_>>()
+                    .join("\\n")
+            ));
+        }
+        for &(child, parent) in &self.edges {
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\";\n",
+                self.commits[child].hash, self.commits[parent].hash
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the graph as a Mermaid flowchart, with edges from each commit to its parents.
+    pub fn to_mermaid(&self) -> String {
+        let escape = |text: String| {
+            text.replace('&', "#amp;")
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;")
+        };
+        let mut out = String::from("flowchart TD\n");
+        for (i, commit) in self.commits.iter().enumerate() {
+            out.push_str(&format!(
+                "  c{i}[\"{}\"]\n",
+                commit
+                    .label_lines()
+                    .map(escape)
+                    .collect::<Vec<_>>()
+                    .join("<br/>")
+            ));
+        }
+        for (child, parent) in &self.edges {
+            out.push_str(&format!("  c{child} --> c{parent}\n"));
+        }
+        out
+    }
+}
+
+impl GraphCommit {
+    /// The abbreviated hash and subject, then the branches and tags in parentheses.
+    fn label_lines(&self) -> impl Iterator<Item = String> {
+        let short = &self.hash[..self.hash.len().min(7)];
+        let title = match &self.subject {
+            Some(subject) if !subject.is_empty() => format!("{short} {subject}"),
+            _ => short.to_string(),
+        };
+        let labels = (!self.labels.is_empty()).then(|| format!("({})", self.labels.join(", ")));
+        std::iter::once(title).chain(labels)
+    }
+}
+
+fn string<'a>(row: &'a Row, name: &str) -> &'a str {
+    row.get(name)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trustfall_git_adapter::backend::MemoryBackend;
+
+    fn adapter() -> GitAdapter {
+        let backend = MemoryBackend::builder("fixture")
+            .commit("init", |c| c.message("Initial commit"))
+            .commit("feature", |c| c.message("Add \"feature\"\n\nDetails"))
+            .commit("fix", |c| c.message("Fix").parents(["init"]))
+            .commit("merge", |c| c.message("Merge").parents(["fix", "feature"]))
+            .branch("main", "merge")
+            .branch("feature", "feature")
+            .tag("v1.0", "merge")
+            .head("main")
+            .build();
+        GitAdapter::with_backend(backend)
+    }
+
+    /// The full hashes of the fixture's commits, by subject.
+    fn hash_of(adapter: &GitAdapter, subject: &str) -> String {
+        let rows = adapter
+            .execute(
+                "{repository {commits {hash @output message @output}}}",
+                BTreeMap::<&str, &str>::new(),
+            )
+            .unwrap();
+        let row = rows
+            .iter()
+            .find(|row| string(row, "message").starts_with(subject))
+            .unwrap();
+        string(row, "hash").to_string()
+    }
+
+    #[test]
+    fn test_hashes_from_rows() {
+        let row = |value: FieldValue| Row::from([("hash".into(), value)]);
+        let rows = vec![
+            row("a".into()),
+            row(FieldValue::List(vec!["b".into(), "c".into()].into())),
+            row(FieldValue::Null),
+        ];
+        assert_eq!(CommitGraph::hashes(&rows), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_graph_dot_and_mermaid() {
+        let adapter = adapter();
+        let [merge, feature, init] = ["Merge", "Add", "Initial"].map(|s| hash_of(&adapter, s));
+        // `fix` isn't drawn, so `merge` only has an edge to `feature`.
+        let hashes = vec![merge.clone(), feature.clone(), merge.clone(), init.clone()];
+        let graph = CommitGraph::load(&adapter, hashes).unwrap();
+
+        assert_eq!(
+            graph.to_dot(),
+            format!(
+                "digraph commits {{\n  node [shape=box, fontname=\"monospace\"];\n\
+                 \x20 \"{merge}\" [label=\"{} Merge\\n(main, tag: v1.0)\"];\n\
+                 \x20 \"{feature}\" [label=\"{} Add \\\"feature\\\"\\n(feature)\"];\n\
+                 \x20 \"{init}\" [label=\"{} Initial commit\"];\n\
+                 \x20 \"{merge}\" -> \"{feature}\";\n\
+                 \x20 \"{feature}\" -> \"{init}\";\n}}\n",
+                &merge[..7],
+                &feature[..7],
+                &init[..7],
+            )
+        );
+        assert_eq!(
+            graph.to_mermaid(),
+            format!(
+                "flowchart TD\n\
+                 \x20 c0[\"{} Merge<br/>(main, tag: v1.0)\"]\n\
+                 \x20 c1[\"{} Add #quot;feature#quot;<br/>(feature)\"]\n\
+                 \x20 c2[\"{} Initial commit\"]\n\
+                 \x20 c0 --> c1\n\
+                 \x20 c1 --> c2\n",
+                &merge[..7],
+                &feature[..7],
+                &init[..7],
+            )
+        );
+    }
+
+    #[test]
+    fn test_graph_unknown_hash() {
+        let graph = CommitGraph::load(&adapter(), vec!["0123456789abcdef".to_string()]).unwrap();
+        assert_eq!(graph.to_mermaid(), "flowchart TD\n  c0[\"0123456\"]\n");
+    }
+
+    #[test]
+    fn test_graph_finds_commits_no_branch_reaches() {
+        let builder = MemoryBackend::builder("fixture")
+            .commit("init", |c| c.message("Initial commit"))
+            .commit("dropped", |c| c.message("Dropped"));
+        // Ids only depend on the commits, so a branch in one copy gives the id of the
+        // commit that nothing refers to in the other.
+        let hash = hash_of(
+            &GitAdapter::with_backend(builder.clone().branch("tmp", "dropped").build()),
+            "Dropped",
+        );
+        let adapter = GitAdapter::with_backend(builder.branch("main", "init").build());
+        let graph = CommitGraph::load(&adapter, vec![hash.clone()]).unwrap();
+        assert_eq!(
+            graph.to_mermaid(),
+            format!("flowchart TD\n  c0[\"{} Dropped\"]\n", &hash[..7])
+        );
+    }
+}
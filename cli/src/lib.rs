@@ -1,11 +1,26 @@
+pub mod changelog;
+pub mod columnar;
+pub mod compute;
+pub mod graph;
+pub mod output;
+pub mod postprocess;
 pub mod presets;
+pub mod sqlite;
+pub mod template;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use comfy_table::{Table, presets::UTF8_FULL};
+use compute::Computed;
 use git2::Repository;
+use graph::CommitGraph;
+use output::{ListEncoding, ListFormat};
+use postprocess::{Aggregate, PostProcess, Rename, SortKey};
 use serde_json::{Map, Value};
-use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
-use trustfall_git_adapter::GitAdapter;
+use sqlite::SqliteTable;
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, time::Duration};
+use template::Template;
+use tracing_subscriber::{filter::LevelFilter, fmt::format::FmtSpan};
+use trustfall_git_adapter::{GitAdapter, LimitExceeded, Limits, Profile, backend::Git2Backend};
 
 fn convert_trustfall_value_to_json(value: &trustfall::FieldValue) -> Value {
     match value {
@@ -78,6 +93,206 @@ pub struct Cli {
     /// Output format
     #[arg(long, value_enum, default_value = "raw")]
     pub format: OutputFormat,
+
+    /// Repository to query; repeat to query several through `repositories`.
+    /// The first one is also the `repository` root. Defaults to the current repository.
+    #[arg(long = "repo", global = true)]
+    pub repos: Vec<PathBuf>,
+
+    /// Keep commit metadata and diffstats in `.git/git-seek/` so repeated queries
+    /// don't decode and diff the same commits again.
+    #[arg(long, global = true)]
+    pub cache: bool,
+
+    /// Stop the query after this long, e.g. `30s`, `500ms` or `2m` (plain numbers are seconds)
+    #[arg(long, global = true, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Stop the query once it has produced this many rows
+    #[arg(long, global = true)]
+    pub max_rows: Option<usize>,
+
+    /// Stop the query once it has read this many git objects
+    #[arg(long, global = true)]
+    pub max_objects: Option<u64>,
+
+    /// After the results, print to stderr how often each resolver ran and over how many
+    /// vertices, the git objects read and the time taken
+    #[arg(long, global = true)]
+    pub profile: bool,
+
+    /// Log spans to stderr at this level and above: `info` for each query, `debug` for
+    /// each resolver call, and `trace` for each git operation
+    #[arg(long, global = true, value_enum, default_value = "off")]
+    pub log_level: LogLevel,
+
+    /// How csv, tsv, markdown, html and template output write lists, such as `@fold`
+    /// outputs, into one cell. Defaults to json
+    #[arg(long, global = true, value_enum)]
+    pub list_encoding: Option<ListEncoding>,
+
+    /// Separator between list items for `--list-encoding join`. Defaults to `;`
+    #[arg(long, global = true)]
+    pub list_separator: Option<String>,
+
+    /// Template for `--format template`, e.g. `{{hash|truncate:8}} {{message|first_line}}`.
+    /// Filters: truncate:N, first_line, date[:FORMAT], pad:N, lpad:N, default:TEXT
+    #[arg(long, global = true, conflicts_with = "template_file")]
+    pub template: Option<String>,
+
+    /// Read the template for `--format template` from a file
+    #[arg(long, global = true)]
+    pub template_file: Option<PathBuf>,
+
+    /// File to write `--format sqlite`, `parquet` or `arrow` results to. A database is
+    /// created if it doesn't exist; Parquet and Arrow files are overwritten
+    #[arg(long, global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Table for `--format sqlite` to create and fill; it must not exist yet. Defaults
+    /// to `results`
+    #[arg(long, global = true)]
+    pub table: Option<String>,
+
+    /// Sort rows by a column, e.g. `date` or `count:desc`. Numbers sort by value and
+    /// nulls come last. Repeat to break ties with further columns
+    #[arg(long, global = true, value_name = "COLUMN[:desc]", value_parser = postprocess::parse_sort)]
+    pub sort: Vec<SortKey>,
+
+    /// Merge rows with the same values in this column into one row per value, with
+    /// the columns from --agg. Repeat to group by several columns
+    #[arg(long, global = true, value_name = "COLUMN")]
+    pub group_by: Vec<String>,
+
+    /// Column to compute for each group: count, sum(COLUMN), min(COLUMN), max(COLUMN)
+    /// or distinct(COLUMN). Without --group-by, all rows form one group. Defaults to count
+    #[arg(long, global = true, value_parser = postprocess::parse_aggregate)]
+    pub agg: Vec<Aggregate>,
+
+    /// Only write the first N rows, after grouping and sorting
+    #[arg(long, global = true, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// Add a column computed from another, e.g. `short=substr(hash,0,8)` or
+    /// `month=date_trunc(date,'month')`. Functions: substr, date_trunc, lower, upper,
+    /// first_line, length
+    #[arg(long, global = true, value_name = "NAME=EXPRESSION", value_parser = compute::parse_compute)]
+    pub compute: Vec<Computed>,
+
+    /// Only write these columns, in this order, e.g. `hash,author`
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub columns: Vec<String>,
+
+    /// Rename a column in the output, e.g. `hash=sha`
+    #[arg(long, global = true, value_name = "FROM=TO", value_parser = postprocess::parse_rename)]
+    pub rename: Vec<Rename>,
+}
+
+/// Settings from global flags that apply to every query run.
+struct QueryOptions {
+    limits: Limits,
+    profile: bool,
+    lists: ListFormat,
+    /// The source of `--template` or `--template-file`.
+    template: Option<String>,
+    output: Option<PathBuf>,
+    table: String,
+    postprocess: PostProcess,
+}
+
+impl Cli {
+    fn query_options(&self) -> anyhow::Result<QueryOptions> {
+        let template = match (&self.template, &self.template_file) {
+            (Some(template), _) => Some(template.clone()),
+            (None, Some(path)) => {
+                let template = std::fs::read_to_string(path).map_err(|e| {
+                    anyhow::anyhow!("Could not read template '{}': {}", path.display(), e)
+                })?;
+                // Each row is already printed on its own line.
+                Some(template.strip_suffix('\n').unwrap_or(&template).to_string())
+            }
+            (None, None) => None,
+        };
+        Ok(QueryOptions {
+            limits: Limits {
+                timeout: self.timeout,
+                max_rows: self.max_rows,
+                max_objects: self.max_objects,
+            },
+            profile: self.profile,
+            lists: ListFormat {
+                encoding: self.list_encoding.unwrap_or(ListEncoding::Json),
+                separator: self
+                    .list_separator
+                    .clone()
+                    .unwrap_or_else(|| ";".to_string()),
+            },
+            template,
+            output: self.output.clone(),
+            table: self.table.clone().unwrap_or_else(|| "results".to_string()),
+            postprocess: PostProcess {
+                compute: self.compute.clone(),
+                sort: self.sort.clone(),
+                group_by: self.group_by.clone(),
+                aggregates: self.agg.clone(),
+                top: self.top,
+                columns: self.columns.clone(),
+                rename: self.rename.clone(),
+            },
+        })
+    }
+
+    /// The flags given that only apply when running a query, which commands like
+    /// `changelog` would otherwise silently ignore.
+    fn query_flags(&self) -> Vec<&'static str> {
+        let flags = [
+            ("--query", self.query.is_some()),
+            ("--file", self.file.is_some()),
+            ("--var", !self.vars.is_empty()),
+            ("--timeout", self.timeout.is_some()),
+            ("--max-rows", self.max_rows.is_some()),
+            ("--max-objects", self.max_objects.is_some()),
+            ("--profile", self.profile),
+            ("--list-encoding", self.list_encoding.is_some()),
+            ("--list-separator", self.list_separator.is_some()),
+            ("--template", self.template.is_some()),
+            ("--template-file", self.template_file.is_some()),
+            ("--output", self.output.is_some()),
+            ("--table", self.table.is_some()),
+            ("--sort", !self.sort.is_empty()),
+            ("--group-by", !self.group_by.is_empty()),
+            ("--agg", !self.agg.is_empty()),
+            ("--top", self.top.is_some()),
+            ("--compute", !self.compute.is_empty()),
+            ("--columns", !self.columns.is_empty()),
+            ("--rename", !self.rename.is_empty()),
+        ];
+        flags
+            .into_iter()
+            .filter(|(_, given)| *given)
+            .map(|(flag, _)| flag)
+            .collect()
+    }
+}
+
+/// Parse a duration with an optional `ms`, `s`, `m` or `h` suffix; seconds by default.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{value}'"))?;
+    let seconds = match unit.trim() {
+        "" | "s" => amount,
+        "ms" => amount / 1000.0,
+        "m" => amount * 60.0,
+        "h" => amount * 3600.0,
+        other => return Err(format!("unknown duration unit '{other}' in '{value}'")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration '{value}': {e}"))
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -87,6 +302,32 @@ enum Commands {
         #[command(subcommand)]
         action: PresetAction,
     },
+    /// Generate a changelog from Conventional Commit messages
+    Changelog {
+        /// Revision to start from (exclusive); defaults to the root of history
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Revision to end at (inclusive)
+        #[arg(long, default_value = "HEAD")]
+        to: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: changelog::ChangelogFormat,
+    },
+    /// Maintain data that speeds up queries
+    Maintenance {
+        #[command(subcommand)]
+        action: MaintenanceAction,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum MaintenanceAction {
+    /// Write git's commit-graph file, which speeds up history walks and reachability
+    /// checks such as `containing_branches`
+    WriteGraph,
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -108,14 +349,75 @@ enum PresetAction {
     },
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Log spans at `level` to stderr as they close, with the time spent in each.
+fn init_logging(level: LogLevel) {
+    if level == LogLevel::Off {
+        return;
+    }
+    // Fails only if a subscriber is already installed, e.g. by a program embedding the CLI.
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::from(level))
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr)
+        .try_init();
+}
+
 #[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
 pub enum OutputFormat {
     Table,
     Json,
     Raw,
+    Csv,
+    Tsv,
+    /// One compact JSON object per line, written as each row is produced
+    #[value(alias = "jsonl")]
+    Ndjson,
+    /// JSON objects nested like the query, with an array per edge
+    JsonNested,
+    /// One line per row from `--template` or `--template-file`
+    Template,
+    /// A GitHub-flavored Markdown table
+    Markdown,
+    /// A standalone HTML page with a table
+    Html,
+    /// A YAML sequence with a mapping per row
+    Yaml,
+    /// A new table in the SQLite database given by `--output`
+    Sqlite,
+    /// A Parquet file given by `--output`
+    Parquet,
+    /// An Arrow IPC file given by `--output`
+    Arrow,
+    /// A Graphviz graph of the commits in the `hash` output, with parents, branches and tags
+    Dot,
+    /// A Mermaid flowchart of the commits in the `hash` output, like `dot`
+    Mermaid,
 }
 
-use std::io::{self, IsTerminal, Read};
+use std::io::{self, IsTerminal, Read, Write};
 
 fn load_query(query: &Option<String>, file: &Option<PathBuf>) -> anyhow::Result<String> {
     if let Some(q) = query {
@@ -144,18 +446,117 @@ fn coerce_variable(value: &str) -> trustfall::FieldValue {
     }
 }
 
+/// Print `profile` as a table, followed by the totals for the whole query.
+fn print_profile(profile: &Profile) {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_header(vec!["Resolver", "Type", "Name", "Calls", "Vertices"]);
+    for entry in &profile.entries {
+        table.add_row(vec![
+            entry.resolver.to_string(),
+            entry.type_name.clone(),
+            entry.name.clone(),
+            entry.calls.to_string(),
+            entry.vertices.to_string(),
+        ]);
+    }
+    eprintln!("{table}");
+    eprintln!("Objects read: {}", profile.objects_read);
+    eprintln!("Wall time: {:?}", profile.elapsed);
+}
+
+/// Run `query` within the configured limits and print its rows. If a limit stops the
+/// query, the rows produced until then are printed before the error is returned. When
+/// profiling, the query's profile is printed to stderr after the rows.
 fn execute_and_output(
-    adapter: &GitAdapter<'_>,
+    adapter: &GitAdapter,
     query: &str,
     variables: BTreeMap<&str, &str>,
     format: &OutputFormat,
+    options: &QueryOptions,
 ) -> anyhow::Result<()> {
     let typed_variables: BTreeMap<&str, trustfall::FieldValue> = variables
         .into_iter()
         .map(|(k, v)| (k, coerce_variable(v)))
         .collect();
-    let result =
-        trustfall::execute_query(adapter.schema(), Arc::new(adapter), query, typed_variables)?;
+    let limits = &options.limits;
+    let postprocess = &options.postprocess;
+    // Check the columns, template and database before running what may be a long query.
+    let columns = postprocess.columns(output::columns(adapter.schema(), query)?)?;
+    let unsupported = match format {
+        OutputFormat::JsonNested | OutputFormat::Dot | OutputFormat::Mermaid
+            if postprocess.reshapes() =>
+        {
+            Some("--group-by, --agg, --compute, --columns or --rename")
+        }
+        OutputFormat::Parquet | OutputFormat::Arrow
+            if postprocess.groups() || !postprocess.compute.is_empty() =>
+        {
+            Some("--group-by, --agg or --compute")
+        }
+        _ => None,
+    };
+    if let Some(flags) = unsupported {
+        anyhow::bail!(
+            "--format {} follows the query's outputs, so it can't be used with {flags}",
+            format_name(format)
+        );
+    }
+    if matches!(format, OutputFormat::JsonNested) {
+        output::check_nested(adapter.schema(), query)?;
+    }
+    let template = match format {
+        OutputFormat::Template => Some(load_template(&columns, options)?),
+        _ => None,
+    };
+    let writes_file = matches!(
+        format,
+        OutputFormat::Sqlite | OutputFormat::Parquet | OutputFormat::Arrow
+    );
+    match (writes_file, &options.output) {
+        (true, None) => anyhow::bail!("--format {} needs --output", format_name(format)),
+        (false, Some(_)) => {
+            anyhow::bail!("--output is only used with --format sqlite, parquet or arrow")
+        }
+        _ => {}
+    }
+    if matches!(format, OutputFormat::Dot | OutputFormat::Mermaid)
+        && !columns.iter().any(|column| &**column == "hash")
+    {
+        anyhow::bail!(
+            "--format {} draws the commits whose hashes the query outputs as `hash`",
+            format_name(format)
+        );
+    }
+    let sqlite = match (format, &options.output) {
+        (OutputFormat::Sqlite, Some(path)) => Some(SqliteTable::open(path, &options.table)?),
+        _ => None,
+    };
+    let arrow_schema = match format {
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            let schema = columnar::schema(adapter.schema(), query)?;
+            Some(columnar::select(&schema, &columns, |column| {
+                postprocess.original_name(column)
+            })?)
+        }
+        _ => None,
+    };
+    // Sorting and grouping need every row, so they can't be streamed.
+    if *format == OutputFormat::Ndjson && postprocess.is_empty() {
+        return stream_ndjson(adapter, query, typed_variables, options);
+    }
+    let (results, profile) = if options.profile {
+        let (results, profile) = adapter.execute_profiled(query, typed_variables, limits)?;
+        (results, Some(profile))
+    } else {
+        (
+            adapter.execute_with_limits(query, typed_variables, limits)?,
+            None,
+        )
+    };
+    let row_count = results.rows.len();
+    let result = postprocess.apply(results.rows)?.into_iter();
 
     match format {
         OutputFormat::Json => {
@@ -164,31 +565,190 @@ fn execute_and_output(
         }
         OutputFormat::Table => {
             let rows: Vec<_> = result.collect();
-            if rows.is_empty() {
-                return Ok(());
-            }
-            let columns: Vec<String> = rows[0].keys().map(|k| k.to_string()).collect();
-            let mut table = Table::new();
-            table.load_preset(UTF8_FULL).set_header(&columns);
-            for row in &rows {
-                let row_values = columns.iter().map(|col| match row.get(col.as_str()) {
-                    Some(value) => format_trustfall_value_for_table(value),
-                    None => String::new(),
-                });
-                table.add_row(row_values);
+            if !rows.is_empty() {
+                let mut table = Table::new();
+                table
+                    .load_preset(UTF8_FULL)
+                    .set_header(columns.iter().map(|column| column.as_ref()));
+                for row in &rows {
+                    let row_values = columns.iter().map(|col| match row.get(col) {
+                        Some(value) => format_trustfall_value_for_table(value),
+                        None => String::new(),
+                    });
+                    table.add_row(row_values);
+                }
+                println!("{table}");
             }
-            println!("{table}");
         }
         OutputFormat::Raw => {
             for row in result {
                 println!("{:?}", row);
             }
         }
+        OutputFormat::JsonNested => {
+            let rows: Vec<_> = result.collect();
+            let nested = output::render_nested(&rows, adapter.schema(), query)?;
+            println!("{}", serde_json::to_string_pretty(&nested)?);
+        }
+        OutputFormat::Template => {
+            let template = template.expect("template is loaded for the template format");
+            for row in result {
+                println!("{}", template.render(&row, &options.lists));
+            }
+        }
+        OutputFormat::Ndjson => {
+            for row in result {
+                println!("{}", convert_result_row_to_json(&row));
+            }
+        }
+        OutputFormat::Markdown | OutputFormat::Html | OutputFormat::Yaml => {
+            let rows: Vec<_> = result.collect();
+            let rendered = match format {
+                OutputFormat::Markdown => output::render_markdown(&rows, &columns, &options.lists),
+                OutputFormat::Html => output::render_html(&rows, &columns, &options.lists),
+                _ => output::render_yaml(&rows, &columns),
+            };
+            print!("{rendered}");
+        }
+        OutputFormat::Sqlite => {
+            let rows: Vec<_> = result.collect();
+            let table = sqlite.expect("database is opened for the sqlite format");
+            table.write(&rows, &columns)?;
+        }
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            let rows: Vec<_> = result.collect();
+            let schema = arrow_schema.expect("schema is built for the parquet and arrow formats");
+            let batch = columnar::record_batch(schema, &rows)?;
+            let path = options.output.as_deref().expect("--output is checked");
+            if *format == OutputFormat::Parquet {
+                columnar::write_parquet(path, &batch)?;
+            } else {
+                columnar::write_ipc(path, &batch)?;
+            }
+        }
+        OutputFormat::Dot | OutputFormat::Mermaid => {
+            let rows: Vec<_> = result.collect();
+            let graph = CommitGraph::load(adapter, CommitGraph::hashes(&rows))?;
+            if *format == OutputFormat::Dot {
+                print!("{}", graph.to_dot());
+            } else {
+                print!("{}", graph.to_mermaid());
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let rows: Vec<_> = result.collect();
+            if *format == OutputFormat::Csv {
+                print!("{}", output::render_csv(&rows, &columns, &options.lists));
+            } else {
+                print!("{}", output::render_tsv(&rows, &columns, &options.lists));
+            }
+        }
+    }
+
+    finish_query(results.exceeded, row_count, profile.as_ref())
+}
+
+/// Parse the configured template, checking that it only uses `columns`.
+fn load_template(columns: &[Arc<str>], options: &QueryOptions) -> anyhow::Result<Template> {
+    let source = options
+        .template
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("--format template needs --template or --template-file"))?;
+    let template = Template::parse(source)?;
+    if let Some(unknown) = template
+        .fields()
+        .find(|field| !columns.iter().any(|column| column.as_ref() == *field))
+    {
+        let outputs: Vec<&str> = columns.iter().map(|column| column.as_ref()).collect();
+        anyhow::bail!(
+            "Template uses '{unknown}', which the query doesn't output. Outputs: {}",
+            outputs.join(", ")
+        );
+    }
+    Ok(template)
+}
+
+/// The name `--format` takes for `format`.
+fn format_name(format: &OutputFormat) -> String {
+    format
+        .to_possible_value()
+        .expect("no formats are skipped")
+        .get_name()
+        .to_string()
+}
+
+/// Print each row as a line of JSON as soon as the query produces it, flushing every
+/// line so downstream tools see rows immediately. A closed pipe, e.g. from `head`, ends
+/// the query quietly.
+fn stream_ndjson(
+    adapter: &GitAdapter,
+    query: &str,
+    variables: BTreeMap<&str, trustfall::FieldValue>,
+    options: &QueryOptions,
+) -> anyhow::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut write_row = |row: trustfall_git_adapter::Row| -> anyhow::Result<()> {
+        serde_json::to_writer(&mut stdout, &convert_result_row_to_json(&row))?;
+        stdout.write_all(b"\n")?;
+        stdout.flush()?;
+        Ok(())
+    };
+
+    let limits = &options.limits;
+    let result = if options.profile {
+        adapter
+            .execute_streaming_profiled(query, variables, limits, &mut write_row)
+            .map(|(streamed, profile)| (streamed, Some(profile)))
+    } else {
+        adapter
+            .execute_streaming(query, variables, limits, &mut write_row)
+            .map(|streamed| (streamed, None))
+    };
+
+    match result {
+        Ok((streamed, profile)) => finish_query(streamed.exceeded, streamed.rows, profile.as_ref()),
+        Err(error) if is_broken_pipe(&error) => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        let kind = cause
+            .downcast_ref::<io::Error>()
+            .map(io::Error::kind)
+            .or_else(|| {
+                cause
+                    .downcast_ref::<serde_json::Error>()
+                    .and_then(serde_json::Error::io_error_kind)
+            });
+        kind == Some(io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Print the profile, if any, and report a limit that stopped the query after `rows`.
+fn finish_query(
+    exceeded: Option<LimitExceeded>,
+    rows: usize,
+    profile: Option<&Profile>,
+) -> anyhow::Result<()> {
+    if let Some(profile) = profile {
+        print_profile(profile);
+    }
+
+    match exceeded {
+        Some(exceeded) => Err(anyhow::anyhow!(
+            "{exceeded}; stopped early after {rows} rows"
+        )),
+        None => Ok(()),
     }
-    Ok(())
 }
 
-fn run_preset(adapter: &GitAdapter<'_>, action: PresetAction) -> anyhow::Result<()> {
+fn run_preset(
+    adapter: &GitAdapter,
+    action: PresetAction,
+    options: &QueryOptions,
+) -> anyhow::Result<()> {
     match action {
         PresetAction::List => {
             let mut table = Table::new();
@@ -291,18 +851,65 @@ fn run_preset(adapter: &GitAdapter<'_>, action: PresetAction) -> anyhow::Result<
                 q
             };
 
-            execute_and_output(adapter, &query, variables, &format)
+            execute_and_output(adapter, &query, variables, &format, options)
         }
     }
 }
 
-/// Run the CLI with the given parsed arguments and a specific repo path.
-pub fn run_with_repo(cli: Cli, repo_path: &std::path::Path) -> anyhow::Result<()> {
-    let repo = Repository::open(repo_path)?;
-    let adapter = GitAdapter::new(&repo);
+fn run_command(repos: &[Repository], cli: Cli) -> anyhow::Result<()> {
+    let command = match &cli.command {
+        Some(Commands::Changelog { .. }) => Some("changelog"),
+        Some(Commands::Maintenance { .. }) => Some("maintenance"),
+        Some(Commands::Preset {
+            action: PresetAction::List,
+        }) => Some("preset list"),
+        _ => None,
+    };
+    if let Some(command) = command {
+        let flags = cli.query_flags();
+        if !flags.is_empty() {
+            anyhow::bail!(
+                "`{command}` doesn't run a query, so it doesn't take {}",
+                flags.join(", ")
+            );
+        }
+    }
+    let (repo, extra_repos) = repos
+        .split_first()
+        .expect("at least one repository is always opened");
+    let mut adapter = if cli.cache {
+        GitAdapter::with_backend(Git2Backend::new(repo).with_cache())
+    } else {
+        GitAdapter::new(repo)
+    };
+    for extra in extra_repos {
+        if cli.cache {
+            adapter.add_backend(Git2Backend::new(extra).with_cache());
+        } else {
+            adapter.add_repository(extra);
+        }
+    }
 
+    let options = cli.query_options()?;
     match cli.command {
-        Some(Commands::Preset { action }) => run_preset(&adapter, action),
+        Some(Commands::Preset { action }) => run_preset(&adapter, action, &options),
+        Some(Commands::Maintenance {
+            action: MaintenanceAction::WriteGraph,
+        }) => {
+            for repo in repos {
+                let count = trustfall_git_adapter::commit_graph::write(repo)?;
+                println!(
+                    "Wrote commit-graph with {count} commits for {}",
+                    repo.commondir().display()
+                );
+            }
+            Ok(())
+        }
+        Some(Commands::Changelog { from, to, format }) => {
+            let output = changelog::generate(repo, from.as_deref(), &to, &format)?;
+            println!("{}", output.trim_end());
+            Ok(())
+        }
         None => {
             let variables = cli
                 .vars
@@ -311,29 +918,41 @@ pub fn run_with_repo(cli: Cli, repo_path: &std::path::Path) -> anyhow::Result<()
                 .collect::<BTreeMap<_, _>>();
 
             let query = load_query(&cli.query, &cli.file)?;
-            execute_and_output(&adapter, &query, variables, &cli.format)
+            execute_and_output(&adapter, &query, variables, &cli.format, &options)
         }
     }
 }
 
-/// Run the CLI using the repository from the current environment.
-pub fn run(cli: Cli) -> anyhow::Result<()> {
-    let repo = Repository::open_from_env()?;
-    let adapter = GitAdapter::new(&repo);
+/// Open every `--repo` path, or only `default` when no `--repo` was given.
+fn open_repositories(
+    cli: &Cli,
+    default: impl FnOnce() -> Result<Repository, git2::Error>,
+) -> anyhow::Result<Vec<Repository>> {
+    if cli.repos.is_empty() {
+        return Ok(vec![default()?]);
+    }
+    cli.repos
+        .iter()
+        .map(|path| {
+            Repository::open(path).map_err(|e| {
+                anyhow::anyhow!("Could not open repository '{}': {}", path.display(), e)
+            })
+        })
+        .collect()
+}
 
-    match cli.command {
-        Some(Commands::Preset { action }) => run_preset(&adapter, action),
-        None => {
-            let variables = cli
-                .vars
-                .iter()
-                .filter_map(|var_entry| var_entry.split_once("="))
-                .collect::<BTreeMap<_, _>>();
+/// Run the CLI with the given parsed arguments and a specific repo path.
+/// The path plays the role of the current repository, so `--repo` flags still take precedence.
+pub fn run_with_repo(cli: Cli, repo_path: &std::path::Path) -> anyhow::Result<()> {
+    let repos = open_repositories(&cli, || Repository::open(repo_path))?;
+    run_command(&repos, cli)
+}
 
-            let query = load_query(&cli.query, &cli.file)?;
-            execute_and_output(&adapter, &query, variables, &cli.format)
-        }
-    }
+/// Run the CLI using the repository from the current environment.
+pub fn run(cli: Cli) -> anyhow::Result<()> {
+    init_logging(cli.log_level);
+    let repos = open_repositories(&cli, Repository::open_from_env)?;
+    run_command(&repos, cli)
 }
 
 #[cfg(test)]
@@ -364,6 +983,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_convert_trustfall_value_to_json_float64() {
         let value = trustfall::FieldValue::Float64(3.14);
         let result = convert_trustfall_value_to_json(&value);
@@ -410,6 +1030,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_format_trustfall_value_for_table_numbers() {
         let value = trustfall::FieldValue::Int64(-42);
         assert_eq!(format_trustfall_value_for_table(&value), "-42");
@@ -513,14 +1134,47 @@ mod tests {
         assert_eq!(result, "inline query");
     }
 
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("5 days").is_err());
+    }
+
     #[test]
     fn test_output_format_values() {
         use clap::ValueEnum;
         let formats = OutputFormat::value_variants();
-        assert_eq!(formats.len(), 3);
+        assert_eq!(formats.len(), 16);
         assert!(formats.contains(&OutputFormat::Table));
         assert!(formats.contains(&OutputFormat::Json));
         assert!(formats.contains(&OutputFormat::Raw));
+        assert!(formats.contains(&OutputFormat::Csv));
+        assert!(formats.contains(&OutputFormat::Tsv));
+        assert!(formats.contains(&OutputFormat::Ndjson));
+        assert!(formats.contains(&OutputFormat::JsonNested));
+        assert!(formats.contains(&OutputFormat::Template));
+        assert!(formats.contains(&OutputFormat::Markdown));
+        assert!(formats.contains(&OutputFormat::Html));
+        assert!(formats.contains(&OutputFormat::Yaml));
+        assert!(formats.contains(&OutputFormat::Sqlite));
+        assert!(formats.contains(&OutputFormat::Parquet));
+        assert!(formats.contains(&OutputFormat::Arrow));
+        assert!(formats.contains(&OutputFormat::Dot));
+        assert!(formats.contains(&OutputFormat::Mermaid));
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        let broken = anyhow::Error::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(is_broken_pipe(&broken));
+        assert!(is_broken_pipe(&broken.context("writing row")));
+        let other = anyhow::Error::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(!is_broken_pipe(&other));
     }
 
     #[test]
@@ -534,6 +1188,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_coerce_variable_float() {
         assert_eq!(
             coerce_variable("3.14"),
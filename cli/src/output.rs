@@ -0,0 +1,850 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde_json::{Map, Value};
+use trustfall::{FieldValue, Schema};
+use trustfall_core::ir::{IRFold, IRQueryComponent, Vid};
+use trustfall_git_adapter::Row;
+
+use crate::convert_trustfall_value_to_json;
+
+/// How lists, such as the values collected by `@fold`, are written into a single cell.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListEncoding {
+    /// A JSON array, e.g. `["a.txt","b.txt"]`
+    Json,
+    /// The items joined with `--list-separator`, e.g. `a.txt;b.txt`
+    Join,
+}
+
+/// How list values are written in formats whose cells hold plain text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListFormat {
+    pub encoding: ListEncoding,
+    pub separator: String,
+}
+
+/// The output names of `query`, in the order every format writes its columns.
+///
+/// Outputs are sorted by name, which is also the order of each row's keys, so columns
+/// stay the same between runs and are known even when the query returns no rows.
+pub fn columns(schema: &Schema, query: &str) -> anyhow::Result<Vec<Arc<str>>> {
+    let query = trustfall_core::frontend::parse(schema, query)?;
+    Ok(query.outputs.keys().cloned().collect())
+}
+
+/// Render `value` as the text of one cell. Nulls are empty.
+pub fn format_cell(value: &FieldValue, lists: &ListFormat) -> String {
+    match value {
+        FieldValue::Null => String::new(),
+        FieldValue::String(s) => s.to_string(),
+        FieldValue::List(items) => match lists.encoding {
+            ListEncoding::Json => convert_trustfall_value_to_json(value).to_string(),
+            ListEncoding::Join => items
+                .iter()
+                .map(|item| format_cell(item, lists))
+                .collect::<Vec<_>>()
+                .join(&lists.separator),
+        },
+        FieldValue::Int64(n) => n.to_string(),
+        FieldValue::Uint64(n) => n.to_string(),
+        FieldValue::Float64(f) => f.to_string(),
+        FieldValue::Boolean(b) => b.to_string(),
+        _ => convert_trustfall_value_to_json(value).to_string(),
+    }
+}
+
+/// Render `rows` as CSV with a header row.
+///
+/// Fields containing a comma, a quote or a line break are quoted, with quotes doubled, so
+/// multi-line commit messages stay in one cell. Lines end in `\r\n` as RFC 4180 asks.
+pub fn render_csv(rows: &[Row], columns: &[Arc<str>], lists: &ListFormat) -> String {
+    render_records(rows, columns, lists, ',', "\r\n", |field| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    })
+}
+
+/// Render `rows` as tab-separated values with a header row.
+///
+/// Nothing is quoted, so that tools like `cut` and `awk` can split lines on tabs. Instead,
+/// tabs, line breaks and backslashes in fields are written as `\t`, `\n`, `\r` and `\\`,
+/// and lines end in `\n`.
+pub fn render_tsv(rows: &[Row], columns: &[Arc<str>], lists: &ListFormat) -> String {
+    render_records(rows, columns, lists, '\t', "\n", |field| {
+        let mut escaped = String::with_capacity(field.len());
+        for c in field.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    })
+}
+
+/// One line per row after a header, with each field passed through `escape`.
+fn render_records(
+    rows: &[Row],
+    columns: &[Arc<str>],
+    lists: &ListFormat,
+    delimiter: char,
+    line_end: &str,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut write_record = |fields: &mut dyn Iterator<Item = String>| {
+        let record: Vec<String> = fields.map(|field| escape(&field)).collect();
+        out.push_str(&record.join(&delimiter.to_string()));
+        out.push_str(line_end);
+    };
+
+    write_record(&mut columns.iter().map(|column| column.to_string()));
+    for row in rows {
+        write_record(&mut columns.iter().map(|column| match row.get(column) {
+            Some(value) => format_cell(value, lists),
+            None => String::new(),
+        }));
+    }
+    out
+}
+
+/// Render `rows` as a GitHub-flavored Markdown table. Pipes in cells are escaped and
+/// line breaks become `<br>`, so multi-line values stay in their cell.
+pub fn render_markdown(rows: &[Row], columns: &[Arc<str>], lists: &ListFormat) -> String {
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace('\n', "<br>")
+    };
+    let record = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut out = record(columns.iter().map(|column| escape(column)).collect());
+    out.push_str(&record(columns.iter().map(|_| "---".to_string()).collect()));
+    for row in rows {
+        out.push_str(&record(
+            columns
+                .iter()
+                .map(|column| match row.get(column) {
+                    Some(value) => escape(&format_cell(value, lists)),
+                    None => String::new(),
+                })
+                .collect(),
+        ));
+    }
+    out
+}
+
+/// Render `rows` as a standalone HTML page holding a single table.
+pub fn render_html(rows: &[Row], columns: &[Arc<str>], lists: &ListFormat) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>git-seek results</title>\n<style>\n\
+         body { font-family: sans-serif; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; \
+         vertical-align: top; white-space: pre-wrap; }\n\
+         </style>\n</head>\n<body>\n<table>\n<thead>\n<tr>",
+    );
+    for column in columns {
+        out.push_str(&format!("<th>{}</th>", escape_html(column)));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for column in columns {
+            let cell = row
+                .get(column)
+                .map(|value| format_cell(value, lists))
+                .unwrap_or_default();
+            out.push_str(&format!("<td>{}</td>", escape_html(&cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Render `rows` as a YAML sequence of mappings, one per row, with keys in `columns`
+/// order. Lists become nested sequences and multi-line strings literal blocks.
+pub fn render_yaml(rows: &[Row], columns: &[Arc<str>]) -> String {
+    if rows.is_empty() {
+        return "[]\n".to_string();
+    }
+    let mut out = String::new();
+    for row in rows {
+        for (i, column) in columns.iter().enumerate() {
+            out.push_str(if i == 0 { "- " } else { "  " });
+            out.push_str(&yaml_string(column));
+            out.push(':');
+            yaml_value(&mut out, row.get(column).unwrap_or(&FieldValue::Null), 2);
+        }
+    }
+    out
+}
+
+/// Append `value` after a mapping key or sequence dash, with nested lines indented by
+/// `indent` spaces.
+fn yaml_value(out: &mut String, value: &FieldValue, indent: usize) {
+    match value {
+        FieldValue::List(items) if items.is_empty() => out.push_str(" []\n"),
+        FieldValue::List(items) => {
+            out.push('\n');
+            for item in items.iter() {
+                out.push_str(&" ".repeat(indent));
+                out.push('-');
+                yaml_value(out, item, indent + 2);
+            }
+        }
+        FieldValue::String(s) if s.contains('\n') && literal_block_safe(s) => {
+            let body = s.trim_end_matches('\n');
+            let chomp = match s.len() - body.len() {
+                0 => "-",
+                1 => "",
+                _ => "+",
+            };
+            out.push_str(&format!(" |{chomp}\n"));
+            for line in s.lines() {
+                if !line.is_empty() {
+                    out.push_str(&" ".repeat(indent + 2));
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+            // `lines` drops the last line break, which `|+` needs to keep.
+            if s.ends_with("\n\n") {
+                out.push('\n');
+            }
+        }
+        FieldValue::String(s) => {
+            out.push(' ');
+            out.push_str(&yaml_string(s));
+            out.push('\n');
+        }
+        FieldValue::Null => out.push_str(" null\n"),
+        _ => {
+            out.push(' ');
+            out.push_str(&convert_trustfall_value_to_json(value).to_string());
+            out.push('\n');
+        }
+    }
+}
+
+/// Whether `s` can be written as a literal block: its first line must not start with a
+/// space, which would be taken for indentation, and it can't hold other control characters.
+fn literal_block_safe(s: &str) -> bool {
+    !s.starts_with([' ', '\n'])
+        && !s.chars().any(|c| c.is_control() && c != '\n')
+        && !s.lines().any(|line| line.ends_with(' '))
+}
+
+/// `s` as a plain scalar when YAML would read it back as the same string, or else
+/// double-quoted, which YAML parses the same as JSON.
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || " _-./@+()".contains(c))
+        && !s.starts_with([' ', '-', '.', '@'])
+        && !s.ends_with(' ')
+        && s.parse::<f64>().is_err()
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n" | "~"
+        );
+    if plain {
+        s.to_string()
+    } else {
+        Value::String(s.to_string()).to_string()
+    }
+}
+
+/// A row with its values converted to JSON, as [`render_nested`] works on them.
+type JsonRow = Map<String, Value>;
+
+/// Edges that reach at most one vertex, so the vertices they reach need no identifying
+/// output to be told apart.
+const SINGLE_EDGES: &[(&str, &str)] = &[
+    ("RootSchemaQuery", "repository"),
+    ("Repository", "commit"),
+    ("Branch", "commit"),
+    ("Tag", "commit"),
+    ("Stash", "commit"),
+    ("Reference", "commit"),
+    ("Submodule", "open"),
+    ("Conflict", "ancestor"),
+    ("Conflict", "ours"),
+    ("Conflict", "theirs"),
+];
+
+/// The properties that tell apart the vertices of each type reached through one edge.
+const IDENTIFYING: &[(&str, &[&str])] = &[
+    ("Repository", &["path"]),
+    ("Commit", &["hash"]),
+    ("Branch", &["name"]),
+    ("Tag", &["name"]),
+    ("StatusEntry", &["path"]),
+    ("Conflict", &["path"]),
+    ("IndexEntry", &["path"]),
+    ("FileChange", &["path"]),
+    ("Stash", &["index"]),
+    ("Reference", &["name"]),
+    ("ReflogEntry", &["old_oid", "new_oid", "timestamp"]),
+    ("Note", &["ref"]),
+    ("Submodule", &["name"]),
+    ("Worktree", &["name"]),
+];
+
+/// How the rows under a parent vertex are split between the vertices an edge reaches.
+enum Grouping<'a> {
+    /// By these outputs, which identify the vertex.
+    Identity(Vec<&'a str>),
+    /// There's at most one vertex, so all the rows are its own, if it's there at all.
+    Single,
+    /// Each row is a vertex of its own, as for the elements of a `@fold` with no edges
+    /// inside it.
+    Row,
+}
+
+/// Render `rows` as JSON objects nested like the vertices in `query`.
+///
+/// Each vertex becomes an object with its outputs, holding an array per edge of the
+/// vertices reached through it, keyed by the edge name. Rows are told apart by the
+/// vertices' identifying outputs, such as a commit's `hash`, which [`check_nested`]
+/// makes sure the query has. `@fold` outputs are turned back into an array of objects,
+/// and `@optional` edges that found nothing become empty arrays.
+pub fn render_nested(rows: &[Row], schema: &Schema, query: &str) -> anyhow::Result<Value> {
+    let query = trustfall_core::frontend::parse(schema, query)?;
+    let component = &query.ir_query.root_component;
+    let grouping = check_identified(component, &query.ir_query.root_name)?;
+    let rows: Vec<JsonRow> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|(name, value)| (name.to_string(), convert_trustfall_value_to_json(value)))
+                .collect()
+        })
+        .collect();
+    let rows: Vec<&JsonRow> = rows.iter().collect();
+    Ok(Value::Array(nest(
+        component,
+        component.root,
+        &grouping,
+        &rows,
+        false,
+    )))
+}
+
+/// Check that `query` outputs what `--format json-nested` needs to tell vertices apart:
+/// the identifying properties of every vertex it nests that an edge may reach several
+/// of, like the `hash` of each commit.
+pub fn check_nested(schema: &Schema, query: &str) -> anyhow::Result<()> {
+    let query = trustfall_core::frontend::parse(schema, query)?;
+    check_identified(&query.ir_query.root_component, &query.ir_query.root_name).map(|_| ())
+}
+
+/// The grouping of the root vertex of `component`, reached through `edge_name`, after
+/// checking that every vertex it nests can be told apart.
+fn check_identified<'a>(
+    component: &'a IRQueryComponent,
+    edge_name: &str,
+) -> anyhow::Result<Grouping<'a>> {
+    fn check_edges(component: &IRQueryComponent, vid: Vid) -> anyhow::Result<()> {
+        for edge in component.edges.values() {
+            if edge.from_vid == vid && has_outputs(component, edge.to_vid) {
+                edge_grouping(component, edge.from_vid, &edge.edge_name, edge.to_vid)?;
+                check_edges(component, edge.to_vid)?;
+            }
+        }
+        for fold in component.folds.values() {
+            if fold.from_vid == vid {
+                fold_grouping(component, fold)?;
+                check_edges(&fold.component, fold.component.root)?;
+            }
+        }
+        Ok(())
+    }
+
+    let root = component.root;
+    let grouping = match SINGLE_EDGES.contains(&("RootSchemaQuery", edge_name)) {
+        true => Grouping::Single,
+        false => Grouping::Identity(identity(component, root, edge_name)?),
+    };
+    check_edges(component, root)?;
+    Ok(grouping)
+}
+
+/// How the rows reaching `to` through `edge_name` from `from` are grouped.
+fn edge_grouping<'a>(
+    component: &'a IRQueryComponent,
+    from: Vid,
+    edge_name: &str,
+    to: Vid,
+) -> anyhow::Result<Grouping<'a>> {
+    let from_type = &*component.vertices[&from].type_name;
+    if SINGLE_EDGES.contains(&(from_type, edge_name)) {
+        return Ok(Grouping::Single);
+    }
+    identity(component, to, edge_name).map(Grouping::Identity)
+}
+
+/// How the elements of `fold` are grouped. Without edges inside the fold, each of its
+/// elements is a separate vertex.
+fn fold_grouping<'a>(
+    component: &IRQueryComponent,
+    fold: &'a IRFold,
+) -> anyhow::Result<Grouping<'a>> {
+    if fold.component.edges.is_empty() {
+        return Ok(Grouping::Row);
+    }
+    let from_type = &*component.vertices[&fold.from_vid].type_name;
+    if SINGLE_EDGES.contains(&(from_type, &*fold.edge_name)) {
+        return Ok(Grouping::Single);
+    }
+    identity(&fold.component, fold.component.root, &fold.edge_name).map(Grouping::Identity)
+}
+
+/// The names of the outputs identifying the vertex `vid`, reached through `edge_name`.
+fn identity<'a>(
+    component: &'a IRQueryComponent,
+    vid: Vid,
+    edge_name: &str,
+) -> anyhow::Result<Vec<&'a str>> {
+    let type_name = &*component.vertices[&vid].type_name;
+    let properties = IDENTIFYING
+        .iter()
+        .find(|(name, _)| *name == type_name)
+        .map_or(&[][..], |(_, properties)| *properties);
+    let outputs: Option<Vec<&str>> = properties
+        .iter()
+        .map(|property| {
+            component
+                .outputs
+                .iter()
+                .find(|(_, field)| field.vertex_id == vid && &*field.field_name == *property)
+                .map(|(name, _)| name.as_ref())
+        })
+        .collect();
+    match outputs {
+        Some(outputs) if !outputs.is_empty() => Ok(outputs),
+        _ => {
+            let properties: Vec<String> = properties.iter().map(|p| format!("`{p}`")).collect();
+            let properties = match properties.split_last() {
+                Some((last, [])) => last.clone(),
+                Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+                None => anyhow::bail!(
+                    "--format json-nested can't tell apart the {type_name} vertices of `{edge_name}`"
+                ),
+            };
+            anyhow::bail!(
+                "--format json-nested needs {properties} of each vertex of `{edge_name}` \
+                 as outputs, to tell them apart"
+            )
+        }
+    }
+}
+
+/// One object per distinct `vid` vertex in `rows`, grouped by `grouping`.
+fn nest(
+    component: &IRQueryComponent,
+    vid: Vid,
+    grouping: &Grouping<'_>,
+    rows: &[&JsonRow],
+    optional: bool,
+) -> Vec<Value> {
+    let own: Vec<&str> = component
+        .outputs
+        .iter()
+        .filter(|(_, field)| field.vertex_id == vid)
+        .map(|(name, _)| name.as_ref())
+        .collect();
+    let folds: Vec<&IRFold> = component
+        .folds
+        .values()
+        .filter(|fold| fold.from_vid == vid)
+        .map(|fold| fold.as_ref())
+        .collect();
+    let key_names: Vec<&str> = match grouping {
+        Grouping::Identity(outputs) => outputs.clone(),
+        // The outputs only tell whether an `@optional` vertex is there.
+        Grouping::Single => {
+            let fold_counts = folds
+                .iter()
+                .flat_map(|fold| fold.fold_specific_outputs.keys());
+            let fold_outputs = folds.iter().flat_map(|fold| fold_output_names(fold));
+            own.iter()
+                .copied()
+                .chain(fold_counts.map(|name| name.as_ref()))
+                .chain(fold_outputs)
+                .collect()
+        }
+        Grouping::Row => Vec::new(),
+    };
+
+    // Group rows by the vertex they came from, in the order vertices first appear.
+    let mut groups: Vec<(Vec<&Value>, Vec<&JsonRow>)> = Vec::new();
+    let mut index = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        let key: Vec<&Value> = key_names
+            .iter()
+            .map(|name| row.get(*name).unwrap_or(&Value::Null))
+            .collect();
+        let id = match grouping {
+            Grouping::Row => i.to_string(),
+            _ => serde_json::to_string(&key).unwrap_or_default(),
+        };
+        let position = *index.entry(id).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[position].1.push(row);
+    }
+
+    groups
+        .into_iter()
+        .filter(|(key, _)| !(optional && key.iter().all(|value| value.is_null())))
+        .map(|(_, rows)| {
+            let first = rows[0];
+            let mut object = Map::new();
+            for name in &own {
+                object.insert(name.to_string(), first[*name].clone());
+            }
+            for fold in &folds {
+                for name in fold.fold_specific_outputs.keys() {
+                    object.insert(name.to_string(), first[name.as_ref()].clone());
+                }
+                if let Some(elements) = fold_elements(fold, first) {
+                    let elements: Vec<&JsonRow> = elements.iter().collect();
+                    let grouping = fold_grouping(component, fold).expect("checked up front");
+                    let nested = nest(
+                        &fold.component,
+                        fold.component.root,
+                        &grouping,
+                        &elements,
+                        false,
+                    );
+                    object.insert(fold.edge_name.to_string(), Value::Array(nested));
+                }
+            }
+            for edge in component.edges.values() {
+                if edge.from_vid == vid && has_outputs(component, edge.to_vid) {
+                    let grouping = edge_grouping(component, vid, &edge.edge_name, edge.to_vid)
+                        .expect("checked up front");
+                    let children = nest(component, edge.to_vid, &grouping, &rows, edge.optional);
+                    object.insert(edge.edge_name.to_string(), Value::Array(children));
+                }
+            }
+            Value::Object(object)
+        })
+        .collect()
+}
+
+/// Every output inside `fold`, including those of folds within it.
+fn fold_output_names(fold: &IRFold) -> Vec<&str> {
+    let component = &fold.component;
+    component
+        .outputs
+        .keys()
+        .map(|name| name.as_ref())
+        .chain(component.folds.values().flat_map(|inner| {
+            let counts = inner.fold_specific_outputs.keys().map(|name| name.as_ref());
+            counts.chain(fold_output_names(inner))
+        }))
+        .collect()
+}
+
+/// The folded vertices of `fold` in `row`, as one row per element: each of the fold's
+/// outputs is a list with an item per element. `None` if the fold has no outputs.
+fn fold_elements(fold: &IRFold, row: &JsonRow) -> Option<Vec<JsonRow>> {
+    let names = fold_output_names(fold);
+    let lists: Vec<(&str, &Vec<Value>)> = names
+        .iter()
+        .filter_map(|name| Some((*name, row.get(*name)?.as_array()?)))
+        .collect();
+    let len = lists.first()?.1.len();
+    Some(
+        (0..len)
+            .map(|i| {
+                lists
+                    .iter()
+                    .map(|(name, list)| {
+                        (name.to_string(), list.get(i).cloned().unwrap_or_default())
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Whether the vertex `vid` or anything reached from it has outputs.
+fn has_outputs(component: &IRQueryComponent, vid: Vid) -> bool {
+    component
+        .outputs
+        .values()
+        .any(|field| field.vertex_id == vid)
+        || component.folds.values().any(|fold| {
+            fold.from_vid == vid
+                && !(fold.fold_specific_outputs.is_empty() && fold_output_names(fold).is_empty())
+        })
+        || component
+            .edges
+            .values()
+            .any(|edge| edge.from_vid == vid && has_outputs(component, edge.to_vid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_lists() -> ListFormat {
+        ListFormat {
+            encoding: ListEncoding::Json,
+            separator: ";".to_string(),
+        }
+    }
+
+    fn row(fields: &[(&str, FieldValue)]) -> Row {
+        fields
+            .iter()
+            .map(|(name, value)| (Arc::from(*name), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_csv_quotes_fields() {
+        let rows = vec![row(&[
+            ("hash", FieldValue::String("abc".into())),
+            (
+                "message",
+                FieldValue::String("Fix \"quoted\", things\n\nBody".into()),
+            ),
+        ])];
+        let columns = vec![Arc::from("hash"), Arc::from("message")];
+        assert_eq!(
+            render_csv(&rows, &columns, &json_lists()),
+            "hash,message\r\nabc,\"Fix \"\"quoted\"\", things\n\nBody\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tsv_escapes_instead_of_quoting() {
+        let rows = vec![row(&[
+            ("a", FieldValue::String("x, \"y\"".into())),
+            ("b", FieldValue::String("tab\there\nC:\\dir\r\n".into())),
+            ("c", FieldValue::Null),
+        ])];
+        let columns = vec![Arc::from("a"), Arc::from("b"), Arc::from("c")];
+        assert_eq!(
+            render_tsv(&rows, &columns, &json_lists()),
+            "a\tb\tc\nx, \"y\"\ttab\\there\\nC:\\\\dir\\r\\n\t\n"
+        );
+    }
+
+    fn report_rows() -> (Vec<Row>, Vec<Arc<str>>) {
+        let rows = vec![
+            row(&[
+                ("hash", FieldValue::String("abc".into())),
+                ("message", FieldValue::String("Fix a|b\n\n<Body>\n".into())),
+                (
+                    "paths",
+                    FieldValue::List(Arc::from(vec![
+                        FieldValue::String("a.txt".into()),
+                        FieldValue::String("true".into()),
+                    ])),
+                ),
+            ]),
+            row(&[
+                ("hash", FieldValue::String("def".into())),
+                ("message", FieldValue::Null),
+                ("paths", FieldValue::List(Arc::from(Vec::new()))),
+            ]),
+        ];
+        let columns = vec![Arc::from("hash"), Arc::from("message"), Arc::from("paths")];
+        (rows, columns)
+    }
+
+    #[test]
+    fn test_render_markdown_escapes_cells() {
+        let (rows, columns) = report_rows();
+        assert_eq!(
+            render_markdown(&rows, &columns, &json_lists()),
+            "| hash | message | paths |\n\
+             | --- | --- | --- |\n\
+             | abc | Fix a\\|b<br><br><Body><br> | [\"a.txt\",\"true\"] |\n\
+             | def |  | [] |\n"
+        );
+    }
+
+    #[test]
+    fn test_render_html_escapes_cells() {
+        let (rows, columns) = report_rows();
+        let html = render_html(&rows, &columns, &json_lists());
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<tr><th>hash</th><th>message</th><th>paths</th></tr>"));
+        assert!(html.contains("<td>Fix a|b\n\n&lt;Body&gt;\n</td>"));
+        assert!(html.contains("<td>[&quot;a.txt&quot;,&quot;true&quot;]</td>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_render_yaml() {
+        let (rows, columns) = report_rows();
+        assert_eq!(
+            render_yaml(&rows, &columns),
+            "- hash: abc\n\
+             \x20 message: |\n\
+             \x20   Fix a|b\n\
+             \n\
+             \x20   <Body>\n\
+             \x20 paths:\n\
+             \x20 - a.txt\n\
+             \x20 - \"true\"\n\
+             - hash: def\n\
+             \x20 message: null\n\
+             \x20 paths: []\n"
+        );
+        assert_eq!(render_yaml(&[], &columns), "[]\n");
+    }
+
+    fn nested(query: &str) -> Value {
+        let backend = trustfall_git_adapter::backend::MemoryBackend::builder("fixture")
+            .commit("init", |c| {
+                c.message("Initial commit").file("README.md", "hi\n")
+            })
+            .commit("feature", |c| {
+                c.message("Add feature")
+                    .file("a.txt", "a\n")
+                    .file("b.txt", "b\n")
+            })
+            .branch("main", "feature")
+            .branch("old", "init")
+            .build();
+        let adapter = trustfall_git_adapter::GitAdapter::with_backend(backend);
+        let rows = adapter
+            .execute(query, std::collections::BTreeMap::<&str, &str>::new())
+            .unwrap();
+        render_nested(&rows, adapter.schema(), query).unwrap()
+    }
+
+    #[test]
+    fn test_render_nested_groups_rows_by_vertex() {
+        let value = nested(
+            r#"{ repository { repo: name @output branches {
+                name @output
+                commit { message @output changes { path @output } }
+            } } }"#,
+        );
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "repo": "fixture",
+                "branches": [
+                    {
+                        "name": "main",
+                        "commit": [{
+                            "message": "Add feature",
+                            "changes": [{ "path": "a.txt" }, { "path": "b.txt" }],
+                        }],
+                    },
+                    {
+                        "name": "old",
+                        "commit": [{
+                            "message": "Initial commit",
+                            "changes": [{ "path": "README.md" }],
+                        }],
+                    },
+                ],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_render_nested_folds_and_optional_edges() {
+        let value = nested(
+            r#"{ repository { commits {
+                hash @output
+                message @output
+                changes @fold { path @output }
+                parents @optional { parent: hash @output }
+            } } }"#,
+        );
+        let commits = value[0]["commits"].as_array().unwrap();
+        assert_eq!(commits.len(), 2);
+        let initial = commits[1]["hash"].clone();
+        assert_eq!(commits[0]["message"], "Add feature");
+        assert_eq!(
+            commits[0]["changes"],
+            serde_json::json!([{ "path": "a.txt" }, { "path": "b.txt" }])
+        );
+        assert_eq!(
+            commits[0]["parents"],
+            serde_json::json!([{ "parent": initial }])
+        );
+        assert_eq!(commits[1]["message"], "Initial commit");
+        assert_eq!(
+            commits[1]["changes"],
+            serde_json::json!([{ "path": "README.md" }])
+        );
+        assert_eq!(commits[1]["parents"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_render_nested_keeps_commits_with_equal_outputs_apart() {
+        let value = nested(r#"{ repository { commits { hash @output author @output } } }"#);
+        assert_eq!(value[0]["commits"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_check_nested_requires_identifying_outputs() {
+        let adapter = trustfall_git_adapter::GitAdapter::with_backend(
+            trustfall_git_adapter::backend::MemoryBackend::builder("fixture").build(),
+        );
+        let schema = adapter.schema();
+        let err =
+            check_nested(schema, "{ repository { commits { message @output } } }").unwrap_err();
+        assert!(err.to_string().contains("`hash`"), "{err}");
+        assert!(err.to_string().contains("`commits`"), "{err}");
+
+        let err = check_nested(
+            schema,
+            "{ repository { branches { name @output commit { parents { message @output } } } } }",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`parents`"), "{err}");
+
+        // A branch has a single commit, and fold elements without edges are rows of their own.
+        check_nested(
+            schema,
+            "{ repository { branches { name @output commit { message @output changes @fold { path @output } } } } }",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_format_cell_list_encodings() {
+        let list = FieldValue::List(Arc::from(vec![
+            FieldValue::String("a.txt".into()),
+            FieldValue::String("b.txt".into()),
+        ]));
+        assert_eq!(format_cell(&list, &json_lists()), r#"["a.txt","b.txt"]"#);
+
+        let joined = ListFormat {
+            encoding: ListEncoding::Join,
+            separator: "|".to_string(),
+        };
+        assert_eq!(format_cell(&list, &joined), "a.txt|b.txt");
+    }
+}
@@ -0,0 +1,700 @@
+use std::{cmp::Ordering, collections::HashMap, fmt, sync::Arc};
+
+use trustfall::FieldValue;
+use trustfall_git_adapter::Row;
+
+use crate::compute::Computed;
+
+/// A `--sort` key: a column, ascending unless followed by `:desc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// Parse `COLUMN`, `COLUMN:asc` or `COLUMN:desc`.
+pub fn parse_sort(value: &str) -> Result<SortKey, String> {
+    let (column, descending) = match value.rsplit_once(':') {
+        Some((column, "desc")) => (column, true),
+        Some((column, "asc")) => (column, false),
+        Some((_, order)) => {
+            return Err(format!(
+                "unknown sort order '{order}' in '{value}'; use asc or desc"
+            ));
+        }
+        None => (value, false),
+    };
+    if column.is_empty() {
+        return Err(format!("missing column in '{value}'"));
+    }
+    Ok(SortKey {
+        column: column.to_string(),
+        descending,
+    })
+}
+
+/// A `--rename FROM=TO` of a column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Parse `FROM=TO`.
+pub fn parse_rename(value: &str) -> Result<Rename, String> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => Ok(Rename {
+            from: from.trim().to_string(),
+            to: to.trim().to_string(),
+        }),
+        _ => Err(format!("expected FROM=TO, got '{value}'")),
+    }
+}
+
+/// An `--agg` column, computed for each group of rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregate {
+    /// The number of rows in the group.
+    Count,
+    Sum(String),
+    Min(String),
+    Max(String),
+    /// The different values, in the order they first appear.
+    Distinct(String),
+}
+
+/// Parse `count`, `sum(COLUMN)`, `min(COLUMN)`, `max(COLUMN)` or `distinct(COLUMN)`.
+pub fn parse_aggregate(value: &str) -> Result<Aggregate, String> {
+    if value == "count" {
+        return Ok(Aggregate::Count);
+    }
+    let unknown = || {
+        format!(
+            "unknown aggregate '{value}'; use count, sum(COL), min(COL), max(COL) or distinct(COL)"
+        )
+    };
+    let (function, column) = value
+        .strip_suffix(')')
+        .and_then(|value| value.split_once('('))
+        .ok_or_else(unknown)?;
+    let column = column.trim().to_string();
+    if column.is_empty() {
+        return Err(format!("missing column in '{value}'"));
+    }
+    match function.trim() {
+        "sum" => Ok(Aggregate::Sum(column)),
+        "min" => Ok(Aggregate::Min(column)),
+        "max" => Ok(Aggregate::Max(column)),
+        "distinct" => Ok(Aggregate::Distinct(column)),
+        _ => Err(unknown()),
+    }
+}
+
+impl Aggregate {
+    fn column(&self) -> Option<&str> {
+        match self {
+            Self::Count => None,
+            Self::Sum(column) | Self::Min(column) | Self::Max(column) | Self::Distinct(column) => {
+                Some(column)
+            }
+        }
+    }
+
+    fn compute(&self, rows: &[Row]) -> anyhow::Result<FieldValue> {
+        let Some(column) = self.column() else {
+            return Ok(FieldValue::Uint64(rows.len() as u64));
+        };
+        // Lists, such as `@fold` outputs, contribute each of their items.
+        let mut values = Vec::new();
+        for row in rows {
+            flatten(row.get(column).unwrap_or(&FieldValue::Null), &mut values);
+        }
+        Ok(match self {
+            Self::Count => unreachable!("count has no column"),
+            Self::Sum(_) => sum(&values).ok_or_else(|| {
+                anyhow::anyhow!("{self} needs numbers, but '{column}' has other values")
+            })?,
+            Self::Min(_) => values
+                .into_iter()
+                .min_by(|a, b| compare(a, b))
+                .cloned()
+                .unwrap_or(FieldValue::Null),
+            Self::Max(_) => values
+                .into_iter()
+                .max_by(|a, b| compare(a, b))
+                .cloned()
+                .unwrap_or(FieldValue::Null),
+            Self::Distinct(_) => {
+                let mut distinct: Vec<FieldValue> = Vec::new();
+                for value in values {
+                    if !distinct.contains(value) {
+                        distinct.push(value.clone());
+                    }
+                }
+                FieldValue::List(distinct.into())
+            }
+        })
+    }
+}
+
+impl fmt::Display for Aggregate {
+    /// The aggregate as written on the command line, which also names its column.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count => write!(f, "count"),
+            Self::Sum(column) => write!(f, "sum({column})"),
+            Self::Min(column) => write!(f, "min({column})"),
+            Self::Max(column) => write!(f, "max({column})"),
+            Self::Distinct(column) => write!(f, "distinct({column})"),
+        }
+    }
+}
+
+/// Reshaping, sorting, grouping and aggregation applied to the rows of a query before
+/// they are written, since Trustfall has no `ORDER BY` or `GROUP BY` and one query
+/// often serves several reports.
+///
+/// Computed columns are added first, so they can be grouped and sorted by. Columns are
+/// selected and renamed last, so the other flags use the original names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PostProcess {
+    pub compute: Vec<Computed>,
+    pub sort: Vec<SortKey>,
+    pub group_by: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+    pub top: Option<usize>,
+    /// The columns to keep, in the order to write them; all of them when empty.
+    pub columns: Vec<String>,
+    pub rename: Vec<Rename>,
+}
+
+impl PostProcess {
+    /// Whether rows are written as the query produced them.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether rows are grouped, so the columns are no longer the query's outputs.
+    pub fn groups(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty()
+    }
+
+    /// Whether the columns differ from the query's outputs.
+    pub fn reshapes(&self) -> bool {
+        self.groups()
+            || !self.compute.is_empty()
+            || !self.columns.is_empty()
+            || !self.rename.is_empty()
+    }
+
+    /// The column that `column` of the processed rows had before `--rename`.
+    pub fn original_name<'a>(&'a self, column: &'a str) -> &'a str {
+        self.rename
+            .iter()
+            .find(|rename| rename.to == column)
+            .map_or(column, |rename| &rename.from)
+    }
+
+    /// `count` unless other aggregates are given.
+    fn aggregates(&self) -> Vec<Aggregate> {
+        if self.aggregates.is_empty() {
+            vec![Aggregate::Count]
+        } else {
+            self.aggregates.clone()
+        }
+    }
+
+    /// The columns of the processed rows, given the query's `columns`: computed columns
+    /// are added after them, grouping leaves the `--group-by` columns followed by the
+    /// `--agg` ones, and `--columns` and `--rename` apply last. Fails when a flag names a
+    /// column that doesn't exist.
+    pub fn columns(&self, mut columns: Vec<Arc<str>>) -> anyhow::Result<Vec<Arc<str>>> {
+        let check = |column: &str, flag: &str, columns: &[Arc<str>]| {
+            if columns.iter().any(|c| &**c == column) {
+                return Ok(());
+            }
+            let names: Vec<&str> = columns.iter().map(|c| &**c).collect();
+            anyhow::bail!(
+                "{flag} uses '{column}', which isn't a column. Columns: {}",
+                names.join(", ")
+            )
+        };
+
+        for computed in &self.compute {
+            check(computed.expression.column(), "--compute", &columns)?;
+            // A computed column may replace an output, e.g. `message=first_line(message)`.
+            if !columns.iter().any(|c| **c == *computed.name) {
+                columns.push(Arc::from(computed.name.as_str()));
+            }
+        }
+
+        let mut columns = if self.groups() {
+            let aggregates = self.aggregates();
+            for column in &self.group_by {
+                check(column, "--group-by", &columns)?;
+            }
+            for column in aggregates.iter().filter_map(Aggregate::column) {
+                check(column, "--agg", &columns)?;
+            }
+            self.group_by
+                .iter()
+                .map(|column| Arc::from(column.as_str()))
+                .chain(aggregates.iter().map(|agg| Arc::from(agg.to_string())))
+                .collect()
+        } else {
+            columns
+        };
+        for key in &self.sort {
+            check(&key.column, "--sort", &columns)?;
+        }
+
+        if !self.columns.is_empty() {
+            for column in &self.columns {
+                check(column, "--columns", &columns)?;
+            }
+            columns = self
+                .columns
+                .iter()
+                .map(|column| Arc::from(column.as_str()))
+                .collect();
+        }
+        for rename in &self.rename {
+            check(&rename.from, "--rename", &columns)?;
+        }
+        // All renames apply at once, so two columns can swap names.
+        for column in &mut columns {
+            if let Some(rename) = self.rename.iter().find(|r| *r.from == **column) {
+                *column = Arc::from(rename.to.as_str());
+            }
+        }
+        if let Some(duplicate) = columns
+            .iter()
+            .enumerate()
+            .find_map(|(i, column)| columns[..i].contains(column).then_some(column))
+        {
+            anyhow::bail!("There would be two columns called '{duplicate}'");
+        }
+        Ok(columns)
+    }
+
+    /// Add computed columns to `rows`, group and aggregate them, sort them, keep the
+    /// `--top` ones, and finally select and rename their columns.
+    pub fn apply(&self, mut rows: Vec<Row>) -> anyhow::Result<Vec<Row>> {
+        for row in &mut rows {
+            for computed in &self.compute {
+                let value = computed.expression.evaluate(row)?;
+                row.insert(Arc::from(computed.name.as_str()), value);
+            }
+        }
+        if self.groups() {
+            rows = self.group(rows)?;
+        }
+        if !self.sort.is_empty() {
+            rows.sort_by(|a, b| {
+                self.sort
+                    .iter()
+                    .map(|key| {
+                        let column = key.column.as_str();
+                        compare_keys(a.get(column), b.get(column), key.descending)
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        if let Some(top) = self.top {
+            rows.truncate(top);
+        }
+        if !self.columns.is_empty() {
+            for row in &mut rows {
+                row.retain(|column, _| self.columns.iter().any(|c| **c == **column));
+            }
+        }
+        if !self.rename.is_empty() {
+            for row in &mut rows {
+                let values: Vec<_> = self
+                    .rename
+                    .iter()
+                    .filter_map(|rename| Some((&rename.to, row.remove(rename.from.as_str())?)))
+                    .collect();
+                for (to, value) in values {
+                    row.insert(Arc::from(to.as_str()), value);
+                }
+            }
+        }
+        Ok(rows)
+    }
+
+    /// One row per distinct combination of `--group-by` values, in order of first
+    /// appearance, holding those values and the aggregates of the group.
+    fn group(&self, rows: Vec<Row>) -> anyhow::Result<Vec<Row>> {
+        let mut groups: Vec<(Vec<FieldValue>, Vec<Row>)> = Vec::new();
+        let mut index = HashMap::new();
+        for row in rows {
+            let key: Vec<FieldValue> = self
+                .group_by
+                .iter()
+                .map(|column| {
+                    row.get(column.as_str())
+                        .cloned()
+                        .unwrap_or(FieldValue::Null)
+                })
+                .collect();
+            // `FieldValue` isn't `Hash`, so groups are told apart by their debug output.
+            let i = *index.entry(format!("{key:?}")).or_insert_with(|| {
+                groups.push((key, Vec::new()));
+                groups.len() - 1
+            });
+            groups[i].1.push(row);
+        }
+
+        let aggregates = self.aggregates();
+        groups
+            .into_iter()
+            .map(|(key, rows)| {
+                let mut row: Row = self
+                    .group_by
+                    .iter()
+                    .map(|column| Arc::from(column.as_str()))
+                    .zip(key)
+                    .collect();
+                for aggregate in &aggregates {
+                    row.insert(Arc::from(aggregate.to_string()), aggregate.compute(&rows)?);
+                }
+                Ok(row)
+            })
+            .collect()
+    }
+}
+
+fn flatten<'a>(value: &'a FieldValue, values: &mut Vec<&'a FieldValue>) {
+    match value {
+        FieldValue::Null => {}
+        FieldValue::List(items) => items.iter().for_each(|item| flatten(item, values)),
+        _ => values.push(value),
+    }
+}
+
+/// The sum of `values`: an integer unless one is a float, or `None` if any isn't a number.
+fn sum(values: &[&FieldValue]) -> Option<FieldValue> {
+    let mut integer: i128 = 0;
+    let mut float: Option<f64> = None;
+    for value in values {
+        match value {
+            FieldValue::Int64(n) => integer += i128::from(*n),
+            FieldValue::Uint64(n) => integer += i128::from(*n),
+            FieldValue::Float64(f) => *float.get_or_insert(0.0) += f,
+            _ => return None,
+        }
+    }
+    Some(match float {
+        Some(float) => FieldValue::Float64(float + integer as f64),
+        None => match i64::try_from(integer) {
+            Ok(n) => FieldValue::Int64(n),
+            Err(_) => FieldValue::Float64(integer as f64),
+        },
+    })
+}
+
+/// Nulls come last whichever the direction, so they don't crowd out real values.
+fn compare_keys(a: Option<&FieldValue>, b: Option<&FieldValue>, descending: bool) -> Ordering {
+    let a = a.filter(|value| !matches!(value, FieldValue::Null));
+    let b = b.filter(|value| !matches!(value, FieldValue::Null));
+    match (a, b) {
+        (Some(a), Some(b)) if descending => compare(b, a),
+        (Some(a), Some(b)) => compare(a, b),
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    }
+}
+
+/// Order values by their types: numbers by value, whatever their representation,
+/// strings by code point and lists item by item. Values of different kinds, such as a
+/// number and a string, are ordered by kind, with nulls first.
+pub fn compare(a: &FieldValue, b: &FieldValue) -> Ordering {
+    match (a, b) {
+        (FieldValue::Int64(a), FieldValue::Int64(b)) => a.cmp(b),
+        (FieldValue::Uint64(a), FieldValue::Uint64(b)) => a.cmp(b),
+        (FieldValue::Int64(a), FieldValue::Uint64(b)) => i128::from(*a).cmp(&i128::from(*b)),
+        (FieldValue::Uint64(a), FieldValue::Int64(b)) => i128::from(*a).cmp(&i128::from(*b)),
+        (FieldValue::String(a), FieldValue::String(b)) => a.cmp(b),
+        (FieldValue::Boolean(a), FieldValue::Boolean(b)) => a.cmp(b),
+        (FieldValue::List(a), FieldValue::List(b)) => a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => match (as_f64(a), as_f64(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => kind(a).cmp(&kind(b)),
+        },
+    }
+}
+
+fn as_f64(value: &FieldValue) -> Option<f64> {
+    match value {
+        FieldValue::Int64(n) => Some(*n as f64),
+        FieldValue::Uint64(n) => Some(*n as f64),
+        FieldValue::Float64(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn kind(value: &FieldValue) -> u8 {
+    match value {
+        FieldValue::Null => 0,
+        FieldValue::Boolean(_) => 1,
+        FieldValue::Int64(_) | FieldValue::Uint64(_) | FieldValue::Float64(_) => 2,
+        FieldValue::String(_) => 3,
+        FieldValue::List(_) => 4,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[(&str, FieldValue)]) -> Row {
+        fields
+            .iter()
+            .map(|(name, value)| (Arc::from(*name), value.clone()))
+            .collect()
+    }
+
+    fn commits() -> Vec<Row> {
+        let commit = |author: &str, additions: &[i64]| {
+            row(&[
+                ("author", author.into()),
+                (
+                    "additions",
+                    FieldValue::List(additions.iter().map(|&n| n.into()).collect()),
+                ),
+            ])
+        };
+        vec![
+            commit("bob", &[2]),
+            commit("alice", &[10, 1]),
+            commit("bob", &[9]),
+            commit("carol", &[]),
+        ]
+    }
+
+    fn column<'a>(rows: &'a [Row], name: &str) -> Vec<&'a FieldValue> {
+        rows.iter().map(|row| &row[name]).collect()
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        assert_eq!(
+            parse_sort("date:desc"),
+            Ok(SortKey {
+                column: "date".to_string(),
+                descending: true
+            })
+        );
+        assert_eq!(parse_sort("a:b:asc").unwrap().column, "a:b");
+        assert!(parse_sort("date:down").is_err());
+        assert_eq!(parse_aggregate("count"), Ok(Aggregate::Count));
+        assert_eq!(
+            parse_aggregate("sum( additions )"),
+            Ok(Aggregate::Sum("additions".to_string()))
+        );
+        assert!(parse_aggregate("avg(additions)").is_err());
+        assert!(parse_aggregate("sum()").is_err());
+        assert_eq!(
+            Aggregate::Distinct("author".to_string()).to_string(),
+            "distinct(author)"
+        );
+    }
+
+    #[test]
+    fn test_compare_is_numeric() {
+        let mut values: Vec<FieldValue> = vec![
+            "10".into(),
+            FieldValue::Int64(10),
+            FieldValue::Float64(2.5),
+            FieldValue::Uint64(9),
+            FieldValue::Int64(-1),
+            "9".into(),
+        ];
+        values.sort_by(compare);
+        assert_eq!(
+            values,
+            vec![
+                FieldValue::Int64(-1),
+                FieldValue::Float64(2.5),
+                FieldValue::Uint64(9),
+                FieldValue::Int64(10),
+                "10".into(),
+                "9".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_aggregate_sort_and_top() {
+        let process = PostProcess {
+            sort: vec![parse_sort("sum(additions):desc").unwrap()],
+            group_by: vec!["author".to_string()],
+            aggregates: [
+                "count",
+                "sum(additions)",
+                "max(additions)",
+                "distinct(additions)",
+            ]
+            .iter()
+            .map(|agg| parse_aggregate(agg).unwrap())
+            .collect(),
+            top: Some(2),
+            ..PostProcess::default()
+        };
+        let columns = ["author", "additions"].map(Arc::from).to_vec();
+        assert_eq!(
+            process.columns(columns).unwrap(),
+            [
+                "author",
+                "count",
+                "sum(additions)",
+                "max(additions)",
+                "distinct(additions)"
+            ]
+            .map(Arc::from)
+        );
+
+        let rows = process.apply(commits()).unwrap();
+        assert_eq!(column(&rows, "author"), [&"bob".into(), &"alice".into()]);
+        assert_eq!(
+            column(&rows, "count"),
+            [&FieldValue::Uint64(2), &FieldValue::Uint64(1)]
+        );
+        assert_eq!(
+            column(&rows, "sum(additions)"),
+            [&FieldValue::Int64(11), &FieldValue::Int64(11)]
+        );
+        assert_eq!(
+            column(&rows, "max(additions)"),
+            [&FieldValue::Int64(9), &FieldValue::Int64(10)]
+        );
+        assert_eq!(
+            rows[0]["distinct(additions)"],
+            FieldValue::List(vec![2.into(), 9.into()].into())
+        );
+    }
+
+    #[test]
+    fn test_date_trunc_groups_across_offsets() {
+        let process = PostProcess {
+            compute: vec![crate::compute::parse_compute("month=date_trunc(date,month)").unwrap()],
+            group_by: vec!["month".to_string()],
+            ..PostProcess::default()
+        };
+        let rows = vec![
+            row(&[("date", "2024-05-10T09:00:00+02:00".into())]),
+            row(&[("date", "2024-05-20T09:00:00-07:00".into())]),
+        ];
+        let rows = process.apply(rows).unwrap();
+        assert_eq!(
+            rows,
+            [row(&[
+                ("month", "2024-05-01T00:00:00+00:00".into()),
+                ("count", FieldValue::Uint64(2)),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_compute_select_and_rename() {
+        let process = PostProcess {
+            compute: vec![
+                crate::compute::parse_compute("initial=upper(substr(author,0,1))").unwrap(),
+            ],
+            sort: vec![parse_sort("initial").unwrap()],
+            columns: vec!["initial".to_string(), "author".to_string()],
+            rename: vec![
+                parse_rename("author=name").unwrap(),
+                parse_rename("initial=author").unwrap(),
+            ],
+            ..PostProcess::default()
+        };
+        let columns = ["additions", "author"].map(Arc::from).to_vec();
+        let columns = process.columns(columns).unwrap();
+        assert_eq!(columns, ["author", "name"].map(Arc::from));
+        assert_eq!(process.original_name("author"), "initial");
+        assert_eq!(process.original_name("name"), "author");
+
+        let rows = process.apply(commits()).unwrap();
+        assert_eq!(
+            rows[0],
+            row(&[("author", "A".into()), ("name", "alice".into())])
+        );
+        assert_eq!(column(&rows, "author").len(), 4);
+    }
+
+    #[test]
+    fn test_reshaping_errors() {
+        let columns = || ["additions", "author"].map(Arc::from).to_vec();
+        let check = |process: PostProcess| process.columns(columns()).unwrap_err().to_string();
+
+        let unknown = PostProcess {
+            columns: vec!["hash".to_string()],
+            ..PostProcess::default()
+        };
+        assert!(check(unknown).contains("--columns uses 'hash'"));
+        let collision = PostProcess {
+            rename: vec![parse_rename("author=additions").unwrap()],
+            ..PostProcess::default()
+        };
+        assert!(check(collision).contains("two columns called 'additions'"));
+        // Selected away before the rename.
+        let dropped = PostProcess {
+            columns: vec!["author".to_string()],
+            rename: vec![parse_rename("additions=lines").unwrap()],
+            ..PostProcess::default()
+        };
+        assert!(check(dropped).contains("--rename uses 'additions'"));
+        assert!(parse_rename("hash").is_err());
+    }
+
+    #[test]
+    fn test_sort_puts_nulls_last() {
+        let rows: Vec<Row> = [
+            FieldValue::Int64(2),
+            FieldValue::Null,
+            FieldValue::Int64(10),
+        ]
+        .into_iter()
+        .map(|n| row(&[("n", n)]))
+        .collect();
+        for descending in [false, true] {
+            let process = PostProcess {
+                sort: vec![SortKey {
+                    column: "n".to_string(),
+                    descending,
+                }],
+                ..PostProcess::default()
+            };
+            let sorted = process.apply(rows.clone()).unwrap();
+            assert_eq!(sorted[2]["n"], FieldValue::Null);
+        }
+    }
+
+    #[test]
+    fn test_unknown_columns_and_sums() {
+        let columns = || ["author", "additions"].map(Arc::from).to_vec();
+        let grouped = PostProcess {
+            group_by: vec!["author".to_string()],
+            sort: vec![parse_sort("additions").unwrap()],
+            ..PostProcess::default()
+        };
+        let error = grouped.columns(columns()).unwrap_err().to_string();
+        assert!(error.contains("--sort uses 'additions'"));
+        assert!(error.contains("Columns: author, count"));
+
+        let sum_authors = PostProcess {
+            aggregates: vec![Aggregate::Sum("author".to_string())],
+            ..PostProcess::default()
+        };
+        assert!(sum_authors.columns(columns()).is_ok());
+        assert!(sum_authors.apply(commits()).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::{path::Path, sync::Arc};
+
+use rusqlite::{Connection, params_from_iter, types::Value};
+use trustfall::FieldValue;
+use trustfall_git_adapter::Row;
+
+use crate::convert_trustfall_value_to_json;
+
+/// A new table in a SQLite database that `--format sqlite` writes rows into.
+pub struct SqliteTable {
+    connection: Connection,
+    name: String,
+}
+
+impl SqliteTable {
+    /// Open the database at `path`, creating it if needed. Fails if it already has a
+    /// table called `name`, so that's known before the query runs.
+    pub fn open(path: &Path, name: &str) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("Could not open database '{}': {}", path.display(), e))?;
+        let exists: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = ?1)",
+            [name],
+            |row| row.get(0),
+        )?;
+        if exists {
+            anyhow::bail!(
+                "Table '{name}' already exists in '{}'; choose another with --table",
+                path.display()
+            );
+        }
+        Ok(Self {
+            connection,
+            name: name.to_string(),
+        })
+    }
+
+    /// Create the table with `columns`, typed after the values in `rows`, and insert
+    /// the rows in a single transaction.
+    pub fn write(mut self, rows: &[Row], columns: &[Arc<str>]) -> anyhow::Result<()> {
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|column| format!("{} {}", quote(column), column_type(rows, column)))
+            .collect();
+        let names: Vec<String> = columns.iter().map(|column| quote(column)).collect();
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            &format!(
+                "CREATE TABLE {} ({})",
+                quote(&self.name),
+                definitions.join(", ")
+            ),
+            [],
+        )?;
+        {
+            let mut insert = transaction.prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote(&self.name),
+                names.join(", "),
+                placeholders.join(", ")
+            ))?;
+            for row in rows {
+                let values = columns
+                    .iter()
+                    .map(|column| to_sql(row.get(column).unwrap_or(&FieldValue::Null)));
+                insert.execute(params_from_iter(values))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// The SQLite type for a column: `INTEGER` for integers and booleans, `REAL` for floats,
+/// or when integers and floats are mixed, and `TEXT` for everything else, including
+/// lists, unsigned integers too large for SQLite, and columns that are always null.
+fn column_type(rows: &[Row], column: &str) -> &'static str {
+    rows.iter()
+        .filter_map(|row| match row.get(column)? {
+            FieldValue::Null => None,
+            FieldValue::Uint64(n) if i64::try_from(*n).is_err() => Some("TEXT"),
+            FieldValue::Int64(_) | FieldValue::Uint64(_) | FieldValue::Boolean(_) => {
+                Some("INTEGER")
+            }
+            FieldValue::Float64(_) => Some("REAL"),
+            _ => Some("TEXT"),
+        })
+        .reduce(|a, b| match (a, b) {
+            _ if a == b => a,
+            ("INTEGER", "REAL") | ("REAL", "INTEGER") => "REAL",
+            _ => "TEXT",
+        })
+        .unwrap_or("TEXT")
+}
+
+/// `value` as stored in SQLite. Lists are stored as JSON arrays, and unsigned integers
+/// too large for SQLite's signed ones as text.
+fn to_sql(value: &FieldValue) -> Value {
+    match value {
+        FieldValue::Null => Value::Null,
+        FieldValue::Int64(n) => Value::Integer(*n),
+        FieldValue::Uint64(n) => match i64::try_from(*n) {
+            Ok(n) => Value::Integer(n),
+            Err(_) => Value::Text(n.to_string()),
+        },
+        FieldValue::Float64(f) => Value::Real(*f),
+        FieldValue::Boolean(b) => Value::Integer(i64::from(*b)),
+        FieldValue::String(s) => Value::Text(s.to_string()),
+        _ => Value::Text(convert_trustfall_value_to_json(value).to_string()),
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[(&str, FieldValue)]) -> Row {
+        fields
+            .iter()
+            .map(|(name, value)| (Arc::from(*name), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_column_types() {
+        let rows = vec![
+            row(&[
+                ("count", FieldValue::Int64(1)),
+                ("size", FieldValue::Uint64(2)),
+                ("ratio", FieldValue::Int64(1)),
+                ("paths", FieldValue::List(Arc::from(Vec::new()))),
+                ("tag", FieldValue::Null),
+            ]),
+            row(&[
+                ("count", FieldValue::Null),
+                ("size", FieldValue::Boolean(true)),
+                ("ratio", FieldValue::Float64(0.5)),
+                ("paths", FieldValue::Null),
+                ("tag", FieldValue::Null),
+            ]),
+        ];
+        assert_eq!(column_type(&rows, "count"), "INTEGER");
+        assert_eq!(column_type(&rows, "size"), "INTEGER");
+        assert_eq!(column_type(&rows, "ratio"), "REAL");
+        assert_eq!(column_type(&rows, "paths"), "TEXT");
+        assert_eq!(column_type(&rows, "tag"), "TEXT");
+    }
+
+    #[test]
+    fn test_write_and_refuse_existing_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.db");
+        let rows = vec![row(&[
+            ("hash", FieldValue::String("abc".into())),
+            ("lines", FieldValue::Uint64(u64::MAX)),
+            (
+                "paths",
+                FieldValue::List(Arc::from(vec![FieldValue::String("a.txt".into())])),
+            ),
+        ])];
+        let columns = vec![Arc::from("hash"), Arc::from("lines"), Arc::from("paths")];
+        SqliteTable::open(&path, "my \"commits\"")
+            .unwrap()
+            .write(&rows, &columns)
+            .unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let stored: (String, String, String) = connection
+            .query_row(
+                "SELECT hash, lines, paths FROM \"my \"\"commits\"\"\"",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            stored,
+            (
+                "abc".to_string(),
+                u64::MAX.to_string(),
+                "[\"a.txt\"]".to_string()
+            )
+        );
+
+        let error = SqliteTable::open(&path, "my \"commits\"").err().unwrap();
+        assert!(error.to_string().contains("already exists"));
+    }
+}
@@ -0,0 +1,273 @@
+use trustfall::FieldValue;
+use trustfall_git_adapter::Row;
+
+use crate::output::{ListFormat, format_cell};
+
+const FILTERS: &str = "truncate:N, first_line, date[:FORMAT], pad:N, lpad:N, default:TEXT";
+
+/// A parsed `--format template` template, rendered once per row, e.g.
+/// `{{hash|truncate:8}} {{author|pad:20}} {{message|first_line}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field { name: String, filters: Vec<Filter> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    /// Keep at most this many characters.
+    Truncate(usize),
+    FirstLine,
+    /// Reformat an RFC 3339 date or Unix timestamp with a `strftime` format.
+    Date(String),
+    /// Pad with spaces on the right to at least this many characters.
+    Pad(usize),
+    /// Pad with spaces on the left to at least this many characters.
+    LPad(usize),
+    /// Replace a null or empty value.
+    Default(String),
+}
+
+impl Template {
+    /// Parse `source`, where `{{name|filter:arg|...}}` is replaced by the output `name`
+    /// and the rest is copied as is, apart from the escapes `\n`, `\t` and `\\`.
+    /// Arguments containing `|` or `}` can be put in double quotes.
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            push_text(&mut parts, &rest[..start]);
+            let expression = &rest[start + 2..];
+            let end = find_outside_quotes(expression, "}}")
+                .ok_or_else(|| anyhow::anyhow!("unclosed '{{{{' in template"))?;
+            parts.push(parse_field(&expression[..end])?);
+            rest = &expression[end + 2..];
+        }
+        push_text(&mut parts, rest);
+        Ok(Self { parts })
+    }
+
+    /// The outputs the template refers to, in order of appearance.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Field { name, .. } => Some(name.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Render `row`, writing lists with `lists`.
+    pub fn render(&self, row: &Row, lists: &ListFormat) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Field { name, filters } => {
+                    let value = row.get(name.as_str()).unwrap_or(&FieldValue::Null);
+                    let text = filters
+                        .iter()
+                        .fold(format_cell(value, lists), |text, filter| filter.apply(text));
+                    out.push_str(&text);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Filter {
+    fn parse(filter: &str) -> anyhow::Result<Self> {
+        let (name, arg) = match filter.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(unquote(arg.trim()))),
+            None => (filter.trim(), None),
+        };
+        let number = |arg: Option<&str>| -> anyhow::Result<usize> {
+            let arg = arg.ok_or_else(|| anyhow::anyhow!("filter '{name}' needs a number"))?;
+            arg.parse()
+                .map_err(|_| anyhow::anyhow!("filter '{name}' needs a number, got '{arg}'"))
+        };
+        Ok(match name {
+            "truncate" => Self::Truncate(number(arg)?),
+            "first_line" => Self::FirstLine,
+            "date" => Self::Date(arg.unwrap_or("%Y-%m-%d").to_string()),
+            "pad" => Self::Pad(number(arg)?),
+            "lpad" => Self::LPad(number(arg)?),
+            "default" => Self::Default(arg.unwrap_or_default().to_string()),
+            _ => anyhow::bail!("unknown template filter '{name}'; available filters: {FILTERS}"),
+        })
+    }
+
+    fn apply(&self, text: String) -> String {
+        match self {
+            Self::Truncate(max) => text.chars().take(*max).collect(),
+            Self::FirstLine => text.lines().next().unwrap_or_default().to_string(),
+            Self::Date(format) => format_date(&text, format).unwrap_or(text),
+            Self::Pad(width) => format!("{text:<width$}"),
+            Self::LPad(width) => format!("{text:>width$}"),
+            Self::Default(default) if text.is_empty() => default.clone(),
+            Self::Default(_) => text,
+        }
+    }
+}
+
+/// Parse an RFC 3339 date, as the adapter writes them, or a Unix timestamp in seconds.
+pub(crate) fn parse_date(text: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    match chrono::DateTime::parse_from_rfc3339(text) {
+        Ok(date) => Some(date),
+        Err(_) => Some(chrono::DateTime::from_timestamp(text.parse().ok()?, 0)?.fixed_offset()),
+    }
+}
+
+fn format_date(text: &str, format: &str) -> Option<String> {
+    use std::fmt::Write;
+
+    let date = parse_date(text)?;
+    // An invalid format is reported by `write!` rather than by panicking.
+    let mut out = String::new();
+    write!(out, "{}", date.format(format)).ok()?;
+    Some(out)
+}
+
+fn parse_field(expression: &str) -> anyhow::Result<Part> {
+    let mut segments = split_outside_quotes(expression, '|').into_iter();
+    let name = segments.next().unwrap_or_default().trim();
+    if name.is_empty() {
+        anyhow::bail!("empty '{{{{}}}}' in template");
+    }
+    Ok(Part::Field {
+        name: name.to_string(),
+        filters: segments.map(Filter::parse).collect::<anyhow::Result<_>>()?,
+    })
+}
+
+fn push_text(parts: &mut Vec<Part>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('t')) => unescaped.push('\t'),
+            ('\\', Some('\\')) => unescaped.push('\\'),
+            _ => {
+                unescaped.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    parts.push(Part::Text(unescaped));
+}
+
+fn find_outside_quotes(text: &str, pattern: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && text[i..].starts_with(pattern) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(i) = find_outside_quotes(rest, &separator.to_string()) {
+        segments.push(&rest[..i]);
+        rest = &rest[i + separator.len_utf8()..];
+    }
+    segments.push(rest);
+    segments
+}
+
+fn unquote(arg: &str) -> &str {
+    arg.strip_prefix('"')
+        .and_then(|arg| arg.strip_suffix('"'))
+        .unwrap_or(arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ListEncoding;
+    use std::sync::Arc;
+
+    fn render(template: &str, fields: &[(&str, FieldValue)]) -> String {
+        let row: Row = fields
+            .iter()
+            .map(|(name, value)| (Arc::from(*name), value.clone()))
+            .collect();
+        let lists = ListFormat {
+            encoding: ListEncoding::Join,
+            separator: ",".to_string(),
+        };
+        Template::parse(template).unwrap().render(&row, &lists)
+    }
+
+    #[test]
+    fn test_template_fields_and_text() {
+        let template = Template::parse("{{hash}} by {{ author | pad:5 }}").unwrap();
+        assert_eq!(template.fields().collect::<Vec<_>>(), ["hash", "author"]);
+        assert_eq!(
+            render(
+                "{{hash|truncate:4}}\\t{{message|first_line}}",
+                &[
+                    ("hash", FieldValue::String("abcdef".into())),
+                    ("message", FieldValue::String("Subject\n\nBody".into())),
+                ],
+            ),
+            "abcd\tSubject"
+        );
+    }
+
+    #[test]
+    fn test_template_padding_and_default() {
+        let fields = [
+            ("name", FieldValue::String("ab".into())),
+            ("email", FieldValue::Null),
+        ];
+        assert_eq!(
+            render("[{{name|pad:4}}][{{name|lpad:4}}]", &fields),
+            "[ab  ][  ab]"
+        );
+        assert_eq!(
+            render("{{email|default:\"n/a\"}} {{missing|default:-}}", &fields),
+            "n/a -"
+        );
+    }
+
+    #[test]
+    fn test_template_date() {
+        let fields = [
+            (
+                "date",
+                FieldValue::String("2024-03-05T14:30:00+01:00".into()),
+            ),
+            ("time", FieldValue::Int64(0)),
+        ];
+        assert_eq!(render("{{date|date}}", &fields), "2024-03-05");
+        assert_eq!(render("{{date|date:\"%H:%M|%d\"}}", &fields), "14:30|05");
+        assert_eq!(render("{{time|date:%Y}}", &fields), "1970");
+    }
+
+    #[test]
+    fn test_template_errors() {
+        assert!(Template::parse("{{hash").is_err());
+        assert!(Template::parse("{{}}").is_err());
+        assert!(Template::parse("{{hash|truncate}}").is_err());
+        let error = Template::parse("{{hash|upper}}").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("unknown template filter 'upper'")
+        );
+    }
+}
@@ -40,6 +40,15 @@ fn run_cli(args: &[&str], repo_path: &std::path::Path) -> anyhow::Result<()> {
     git_seek::run_with_repo(cli, repo_path)
 }
 
+/// Run the `git-seek` binary in `repo_path`, for checking what it prints.
+fn run_binary(args: &[&str], repo_path: &std::path::Path) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_git-seek"))
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .unwrap()
+}
+
 // --- Backward compatibility ---
 
 #[test]
@@ -175,3 +184,893 @@ fn test_preset_run_with_format_json() {
     )
     .unwrap();
 }
+
+// --- Changelog ---
+
+/// Add an empty commit with `message` on top of `HEAD`, returning its hash.
+fn commit(repo_path: &std::path::Path, message: &str) -> String {
+    let repo = git2::Repository::open(repo_path).unwrap();
+    let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let tree = head.tree().unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &[&head],
+    )
+    .unwrap()
+    .to_string()
+}
+
+/// A repository with Conventional Commits after its initial commit, and a GitHub
+/// `origin`. Returns the hashes of the commits added, oldest first.
+fn create_changelog_repo() -> (TempDir, PathBuf, Vec<String>) {
+    let (temp, path) = create_test_repo();
+    git2::Repository::open(&path)
+        .unwrap()
+        .remote("origin", "git@github.com:owner/project.git")
+        .unwrap();
+    let hashes = [
+        "feat(cli): add a flag",
+        "fix: handle empty input",
+        "docs: explain flags",
+        "feat!: drop the old format",
+    ]
+    .map(|message| commit(&path, message));
+    (temp, path, hashes.to_vec())
+}
+
+#[test]
+fn test_changelog_markdown() {
+    let (_temp, path, hashes) = create_changelog_repo();
+    let output = run_binary(&["changelog"], &path);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let link = |hash: &String| {
+        format!(
+            "[{}](https://github.com/owner/project/commit/{hash})",
+            &hash[..7]
+        )
+    };
+    let features = stdout.find("### Features\n\n").unwrap();
+    let fixes = stdout.find("### Bug Fixes\n\n").unwrap();
+    let breaking = stdout.find("### BREAKING CHANGES\n\n").unwrap();
+    assert!(features < fixes && fixes < breaking, "{stdout}");
+
+    let features_section = &stdout[features..fixes];
+    assert!(features_section.contains(&format!("* drop the old format ({})", link(&hashes[3]))));
+    assert!(features_section.contains(&format!("* **cli:** add a flag ({})", link(&hashes[0]))));
+    assert!(stdout[fixes..].contains(&format!("* handle empty input ({})", link(&hashes[1]))));
+    let docs = stdout.find("### Documentation\n\n").unwrap();
+    assert!(stdout[docs..].starts_with(&format!(
+        "### Documentation\n\n* explain flags ({})\n",
+        link(&hashes[2])
+    )));
+    let other = stdout.find("### Other Changes\n\n").unwrap();
+    assert!(stdout[other..breaking].contains("* Initial commit ("));
+    assert!(stdout[breaking..].contains("drop the old format"));
+    assert!(!stdout[breaking..].contains("add a flag"));
+}
+
+#[test]
+fn test_changelog_local_remote_prints_plain_hashes() {
+    let (_temp, path) = create_test_repo();
+    let hash = commit(&path, "fix: handle empty input");
+    git2::Repository::open(&path)
+        .unwrap()
+        .remote("origin", "file:///srv/git/project.git")
+        .unwrap();
+    let output = run_binary(&["changelog"], &path);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("* handle empty input ({})", &hash[..7])),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("https://"), "{stdout}");
+}
+
+#[test]
+fn test_query_flags_rejected_without_a_query() {
+    let (_temp, path) = create_test_repo();
+    let err = run_cli(&["git-seek", "--sort", "date", "changelog"], &path).unwrap_err();
+    assert!(err.to_string().contains("`changelog`"), "{err}");
+    assert!(err.to_string().contains("--sort"), "{err}");
+
+    let err = run_cli(
+        &[
+            "git-seek",
+            "preset",
+            "list",
+            "--max-rows",
+            "5",
+            "--table",
+            "t",
+        ],
+        &path,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("--max-rows, --table"), "{err}");
+
+    let output = run_binary(
+        &["maintenance", "write-graph", "--list-encoding", "join"],
+        &path,
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--list-encoding"), "{stderr}");
+
+    // The same flags still apply to presets, before or after the subcommand.
+    run_cli(
+        &[
+            "git-seek",
+            "--max-rows",
+            "5",
+            "preset",
+            "run",
+            "recent-commits",
+            "--sort",
+            "hash",
+        ],
+        &path,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_changelog_json_with_range() {
+    let (_temp, path, hashes) = create_changelog_repo();
+    let output = run_binary(
+        &[
+            "changelog",
+            "--from",
+            &hashes[1],
+            "--to",
+            "HEAD",
+            "--format",
+            "json",
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let changelog: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(
+        changelog["repository_url"],
+        "https://github.com/owner/project"
+    );
+    let sections = changelog["sections"].as_array().unwrap();
+    let commits: Vec<_> = sections
+        .iter()
+        .flat_map(|section| section["commits"].as_array().unwrap())
+        .collect();
+    // `--from` is exclusive, so only the last two commits are included.
+    let included: Vec<_> = commits
+        .iter()
+        .map(|c| c["hash"].as_str().unwrap())
+        .collect();
+    assert_eq!(included.len(), 2, "{changelog:#}");
+    assert!(included.contains(&hashes[2].as_str()));
+    assert!(included.contains(&hashes[3].as_str()));
+
+    let feature = commits.iter().find(|c| c["hash"] == hashes[3]).unwrap();
+    assert_eq!(feature["type"], "feat");
+    assert_eq!(feature["breaking"], true);
+    assert_eq!(
+        feature["url"],
+        format!("https://github.com/owner/project/commit/{}", hashes[3])
+    );
+    let section_of = |hash: &str| {
+        sections
+            .iter()
+            .find(|section| {
+                section["commits"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .any(|c| c["hash"] == hash)
+            })
+            .unwrap()["title"]
+            .clone()
+    };
+    assert_eq!(section_of(&hashes[3]), "Features");
+    assert_eq!(section_of(&hashes[2]), "Documentation");
+}
+
+#[test]
+fn test_changelog_unknown_revision() {
+    let (_temp, path) = create_test_repo();
+    let result = run_cli(&["git-seek", "changelog", "--from", "v9.9.9"], &path);
+    assert!(result.is_err());
+}
+
+// --- Multiple repositories ---
+
+#[test]
+fn test_multiple_repo_flags() {
+    let (_first, first_path) = create_test_repo();
+    let (_second, second_path) = create_test_repo();
+    run_cli(
+        &[
+            "git-seek",
+            "--repo",
+            first_path.to_str().unwrap(),
+            "--repo",
+            second_path.to_str().unwrap(),
+            "--query",
+            "{repositories {path @output}}",
+            "--format",
+            "json",
+        ],
+        &first_path,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_repo_flag_not_a_repository() {
+    let (_temp, path) = create_test_repo();
+    let not_repo = tempfile::TempDir::new().unwrap();
+    let result = run_cli(
+        &[
+            "git-seek",
+            "--repo",
+            not_repo.path().to_str().unwrap(),
+            "--query",
+            "{repository {name @output}}",
+        ],
+        &path,
+    );
+    let err = result.unwrap_err().to_string();
+    assert!(
+        err.contains("Could not open repository"),
+        "Expected 'Could not open repository', got: {err}"
+    );
+}
+
+#[test]
+fn test_cache_flag_writes_cache() {
+    let (_temp, path) = create_test_repo();
+    run_cli(
+        &[
+            "git-seek",
+            "--cache",
+            "--query",
+            "{repository {commits {hash @output changes @fold {path @output}}}}",
+            "--format",
+            "json",
+        ],
+        &path,
+    )
+    .unwrap();
+
+    let cache = path.join(".git/git-seek");
+    let commits = std::fs::read_to_string(cache.join("commits-v2.jsonl")).unwrap();
+    assert_eq!(commits.lines().count(), 1);
+    assert!(commits.contains("Initial commit"));
+    assert!(cache.join("changes-v2.jsonl").exists());
+}
+
+#[test]
+fn test_maintenance_write_graph() {
+    let (_temp, path) = create_test_repo();
+    run_cli(&["git-seek", "maintenance", "write-graph"], &path).unwrap();
+    assert!(path.join(".git/objects/info/commit-graph").is_file());
+
+    run_cli(
+        &[
+            "git-seek",
+            "--query",
+            "{repository {commits {hash @output generation @output}}}",
+            "--format",
+            "json",
+        ],
+        &path,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_max_rows_stops_with_error() {
+    let (_temp, path) = create_test_repo();
+    let error = run_cli(
+        &[
+            "git-seek",
+            "--max-rows",
+            "0",
+            "--query",
+            "{repository {commits {hash @output}}}",
+            "--format",
+            "json",
+        ],
+        &path,
+    )
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("query produced more than 0 rows")
+    );
+}
+
+#[test]
+fn test_limits_not_reached() {
+    let (_temp, path) = create_test_repo();
+    run_cli(
+        &[
+            "git-seek",
+            "--timeout",
+            "30s",
+            "--max-rows",
+            "10",
+            "--max-objects",
+            "1000",
+            "preset",
+            "run",
+            "recent-commits",
+        ],
+        &path,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_profile_flag() {
+    let (_temp, path) = create_test_repo();
+    run_cli(
+        &[
+            "git-seek",
+            "--profile",
+            "--query",
+            "{repository {commits {hash @output}}}",
+            "--format",
+            "table",
+        ],
+        &path,
+    )
+    .unwrap();
+    run_cli(
+        &["git-seek", "--profile", "preset", "run", "recent-commits"],
+        &path,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_log_level_flag_logs_spans_to_stderr() {
+    let (_temp, path) = create_test_repo();
+    let output = run_binary(
+        &[
+            "--log-level",
+            "trace",
+            "--query",
+            "{repository {commits {hash @output}}}",
+        ],
+        &path,
+    );
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("resolve_neighbors"));
+    assert!(stderr.contains("find_commit"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("resolve_neighbors"));
+}
+
+#[test]
+fn test_csv_and_tsv_output() {
+    let (_temp, path) = create_test_repo();
+    let query = "{repository {commits {hash @output message @output author @output}}}";
+
+    let output = run_binary(&["--query", query, "--format", "csv"], &path);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("author,hash,message"));
+    let record = lines.next().unwrap();
+    assert!(record.starts_with("Test User,"));
+    assert!(record.ends_with(",Initial commit"));
+
+    let output = run_binary(&["--query", query, "--format", "tsv"], &path);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("author\thash\tmessage\nTest User\t"));
+    assert!(!stdout.contains('\r'));
+}
+
+#[test]
+fn test_markdown_html_and_yaml_output() {
+    let (_temp, path) = create_test_repo();
+    let query = "{repository {commits {hash @output message @output author @output}}}";
+
+    let output = run_binary(&["--query", query, "--format", "markdown"], &path);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("| author | hash | message |"));
+    assert_eq!(lines.next(), Some("| --- | --- | --- |"));
+    let record = lines.next().unwrap();
+    assert!(record.starts_with("| Test User | "));
+    assert!(record.ends_with(" | Initial commit |"));
+
+    let output = run_binary(&["--query", query, "--format", "html"], &path);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("<!DOCTYPE html>"));
+    assert!(stdout.contains("<th>author</th><th>hash</th><th>message</th>"));
+    assert!(stdout.contains("<td>Initial commit</td>"));
+
+    let output = run_binary(&["--query", query, "--format", "yaml"], &path);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("- author: Test User\n  hash: "));
+    assert!(stdout.ends_with("  message: Initial commit\n"));
+}
+
+#[test]
+fn test_sqlite_output() {
+    let (temp, path) = create_test_repo();
+    let db = temp.path().join("results.db");
+    let db_arg = db.to_str().unwrap();
+    let query = "{repository {commits {hash @output changes @fold {path @output}}}}";
+
+    let output = run_binary(
+        &[
+            "--query", query, "--format", "sqlite", "--output", db_arg, "--table", "commits",
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    let connection = rusqlite::Connection::open(&db).unwrap();
+    let schema: String = connection
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE name = 'commits'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(
+        schema,
+        "CREATE TABLE \"commits\" (\"hash\" TEXT, \"path\" TEXT)"
+    );
+    let paths: String = connection
+        .query_row("SELECT path FROM commits", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(paths, "[]");
+
+    let output = run_binary(
+        &[
+            "--query", query, "--format", "sqlite", "--output", db_arg, "--table", "commits",
+        ],
+        &path,
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    let output = run_binary(&["--query", query, "--format", "sqlite"], &path);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--format sqlite needs --output"));
+}
+
+#[test]
+fn test_parquet_and_arrow_output() {
+    use arrow_array::{Array, ListArray, StringArray};
+
+    let (temp, path) = create_test_repo();
+    let query =
+        "{repository {commits {hash @output message @output changes @fold {path @output}}}}";
+    let check = |batch: arrow_array::RecordBatch| {
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(names, ["hash", "message", "path"]);
+        assert_eq!(batch.num_rows(), 1);
+        let messages = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(messages.value(0), "Initial commit");
+        let paths = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap();
+        assert_eq!(paths.value(0).len(), 0);
+    };
+
+    let parquet = temp.path().join("results.parquet");
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "parquet",
+            "--output",
+            parquet.to_str().unwrap(),
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let file = std::fs::File::open(&parquet).unwrap();
+    let mut reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    check(reader.next().unwrap().unwrap());
+
+    let arrow = temp.path().join("results.arrow");
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "arrow",
+            "--output",
+            arrow.to_str().unwrap(),
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let file = std::fs::File::open(&arrow).unwrap();
+    let mut reader = arrow_ipc::reader::FileReader::try_new(file, None).unwrap();
+    check(reader.next().unwrap().unwrap());
+
+    let output = run_binary(
+        &["--query", query, "--format", "json", "--output", "x"],
+        &path,
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--output is only used with"));
+}
+
+#[test]
+fn test_dot_and_mermaid_output() {
+    let (_temp, path) = create_test_repo();
+    let query = "{repository {branches {name @output commit {hash @output}}}}";
+
+    let output = run_binary(&["--query", query, "--format", "dot"], &path);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("digraph commits {\n"));
+    assert!(stdout.contains(" Initial commit\\n("));
+    assert!(stdout.ends_with("}\n"));
+
+    let output = run_binary(&["--query", query, "--format", "mermaid"], &path);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("flowchart TD\n  c0[\""));
+    assert!(stdout.contains(" Initial commit<br/>("));
+
+    let output = run_binary(
+        &[
+            "--query",
+            "{repository {branches {name @output}}}",
+            "--format",
+            "mermaid",
+        ],
+        &path,
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("outputs as `hash`"));
+}
+
+#[test]
+fn test_sort_group_and_top() {
+    let (_temp, path) = create_test_repo();
+    let repo = git2::Repository::open(&path).unwrap();
+    for author in ["Alice", "Bob", "Alice"] {
+        let signature = git2::Signature::now(author, "dev@example.com").unwrap();
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        let tree = parent.tree().unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            author,
+            &tree,
+            &[&parent],
+        )
+        .unwrap();
+    }
+    let query = "{repository {commits {author @output message @output}}}";
+
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "csv",
+            "--group-by",
+            "author",
+            "--agg",
+            "count",
+            "--sort",
+            "count:desc",
+            "--top",
+            "2",
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "author,count\r\nAlice,2\r\nBob,1\r\n"
+    );
+
+    let output = run_binary(
+        &[
+            "--query", query, "--format", "ndjson", "--sort", "message", "--top", "1",
+        ],
+        &path,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"author\":\"Alice\",\"message\":\"Alice\"}\n"
+    );
+
+    let output = run_binary(&["--query", query, "--sort", "date"], &path);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--sort uses 'date'"));
+
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "json-nested",
+            "--group-by",
+            "author",
+        ],
+        &path,
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't be used with --group-by"));
+}
+
+#[test]
+fn test_compute_columns_and_rename() {
+    let (temp, path) = create_test_repo();
+    let query = "{repository {commits {hash @output author @output date @output}}}";
+
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "csv",
+            "--compute",
+            "short=substr(hash,0,8)",
+            "--columns",
+            "short,author",
+            "--rename",
+            "author=who",
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("short,who"));
+    let (short, who) = lines.next().unwrap().split_once(',').unwrap();
+    assert_eq!(short.len(), 8);
+    assert_eq!(who, "Test User");
+
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "json",
+            "--compute",
+            "month=date_trunc(date, 'month')",
+            "--group-by",
+            "month",
+        ],
+        &path,
+    );
+    let rows: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(rows[0]["month"].as_str().unwrap().contains("-01T00:00:00"));
+    assert_eq!(rows[0]["count"], 1);
+
+    let parquet = temp.path().join("results.parquet");
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "parquet",
+            "--output",
+            parquet.to_str().unwrap(),
+            "--columns",
+            "hash",
+            "--rename",
+            "hash=sha",
+        ],
+        &path,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let file = std::fs::File::open(&parquet).unwrap();
+    let builder =
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+    let names: Vec<_> = builder
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(names, ["sha"]);
+
+    let output = run_binary(
+        &["--query", query, "--compute", "short=substr(sha,0,8)"],
+        &path,
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--compute uses 'sha'"));
+}
+
+#[test]
+fn test_csv_header_without_rows() {
+    let (_temp, path) = create_test_repo();
+    let output = run_binary(
+        &[
+            "--query",
+            "{repository {tags {name @output}}}",
+            "--format",
+            "csv",
+        ],
+        &path,
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "name\r\n");
+}
+
+#[test]
+fn test_csv_joined_fold_lists() {
+    let (_temp, path) = create_test_repo();
+    let output = run_binary(
+        &[
+            "--query",
+            "{repository {branches @fold {name @output}}}",
+            "--format",
+            "csv",
+            "--list-encoding",
+            "join",
+            "--list-separator",
+            "|",
+        ],
+        &path,
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("name\r\n"));
+    assert!(!stdout.contains('['));
+}
+
+#[test]
+fn test_ndjson_output() {
+    let (_temp, path) = create_test_repo();
+    for format in ["ndjson", "jsonl"] {
+        let output = run_binary(
+            &[
+                "--query",
+                "{repository {commits {hash @output message @output}}}",
+                "--format",
+                format,
+            ],
+            &path,
+        );
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let rows: Vec<serde_json::Value> = stdout
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["message"], "Initial commit");
+    }
+}
+
+#[test]
+fn test_json_nested_output() {
+    let (_temp, path) = create_test_repo();
+    let output = run_binary(
+        &[
+            "--query",
+            "{repository {repo: name @output commits {hash @output message @output}}}",
+            "--format",
+            "json-nested",
+        ],
+        &path,
+    );
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value[0]["commits"][0]["message"], "Initial commit");
+    assert!(value[0]["commits"][0]["hash"].is_string());
+    assert!(value[0]["repo"].is_string());
+}
+
+#[test]
+fn test_json_nested_requires_identifying_outputs() {
+    let (_temp, path) = create_test_repo();
+    let output = run_binary(
+        &[
+            "--query",
+            "{repository {commits {message @output}}}",
+            "--format",
+            "json-nested",
+        ],
+        &path,
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("needs `hash`"), "{stderr}");
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_template_output() {
+    let (_temp, path) = create_test_repo();
+    let query = "{repository {commits {hash @output author @output message @output}}}";
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "template",
+            "--template",
+            "{{hash|truncate:7}} {{author|pad:12}}|{{message|first_line}}",
+        ],
+        &path,
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout.lines().next().unwrap();
+    assert_eq!(line.len(), 7 + 1 + 12 + 1 + "Initial commit".len());
+    assert!(line.ends_with(" Test User   |Initial commit"));
+
+    let template_file = path.join("oneline.tmpl");
+    std::fs::write(&template_file, "{{message}}\n").unwrap();
+    let output = run_binary(
+        &[
+            "--query",
+            query,
+            "--format",
+            "template",
+            "--template-file",
+            template_file.to_str().unwrap(),
+        ],
+        &path,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Initial commit\n"
+    );
+}
+
+#[test]
+fn test_template_errors() {
+    let (_temp, path) = create_test_repo();
+    let query = "{repository {commits {hash @output}}}";
+    let error = run_cli(
+        &["git-seek", "--query", query, "--format", "template"],
+        &path,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("needs --template"));
+
+    let error = run_cli(
+        &[
+            "git-seek",
+            "--query",
+            query,
+            "--format",
+            "template",
+            "--template",
+            "{{author}}",
+        ],
+        &path,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("doesn't output"));
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use git2::Oid;
+use serde_json::{Value, json};
+
+use super::{
+    Backend, BranchInfo, CommitInfo, Conflict, FileChange, GraphNode, Note, ReferenceInfo,
+    ReflogEntry, StashInfo, StatusEntry, SubmoduleInfo, TagInfo, Worktree,
+};
+
+/// Remembers commit metadata and per-commit file changes on disk.
+///
+/// Commits never change once written, so [`Backend::find_commit`] and
+/// [`Backend::commit_changes`] results are stored by commit id and never invalidated.
+/// Entries are appended to files in the cache directory as they're first computed, one
+/// line each, keyed by commit id, so the cache fills in incrementally as queries reach new
+/// commits, and later processes read it instead of decoding and diffing those commits
+/// again. Everything else is passed through to the wrapped backend. Lookups that fail
+/// aren't remembered, so they're tried again next time.
+///
+/// On first use, each file is scanned for the ids of its lines, and an entry is only read
+/// and parsed when it's looked up. A file holding duplicate, unreadable or unfinished
+/// lines is rewritten without them at that point; entries that another process appends
+/// while that happens may be lost, and are computed again when next needed.
+///
+/// The cache is best effort: if the directory can't be written, results are still
+/// remembered for the lifetime of the backend, and unreadable lines are skipped.
+///
+/// [`Git2Backend::with_cache`](super::Git2Backend::with_cache) keeps the cache in
+/// `.git/git-seek/`.
+#[derive(Debug)]
+pub struct CachedBackend {
+    inner: Box<dyn Backend>,
+    dir: PathBuf,
+    state: Mutex<Option<CacheState>>,
+}
+
+/// The cache files, opened on first use.
+#[derive(Debug)]
+struct CacheState {
+    commits: Entries<CommitInfo>,
+    changes: Entries<Vec<FileChange>>,
+}
+
+/// The entries of one cache file.
+#[derive(Debug)]
+struct Entries<T> {
+    /// Entries read from the file or computed so far.
+    loaded: HashMap<Oid, T>,
+    log: Log,
+}
+
+/// One cache file, whose lines are `<commit id>\t<JSON>`.
+#[derive(Debug, Default)]
+struct Log {
+    /// Where each id's line starts, and its length without the newline.
+    index: HashMap<Oid, (u64, usize)>,
+    reader: Option<File>,
+    writer: Option<File>,
+}
+
+/// Bumped whenever the line format changes, so old caches are ignored rather than misread.
+const COMMITS_FILE: &str = "commits-v2.jsonl";
+const CHANGES_FILE: &str = "changes-v2.jsonl";
+
+impl CachedBackend {
+    /// Cache `inner`'s commits in `dir`, which is created when the first entry is written.
+    pub fn new(inner: impl Backend + 'static, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner: Box::new(inner),
+            dir: dir.into(),
+            state: Mutex::new(None),
+        }
+    }
+
+    /// The directory the cache files live in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Look `oid` up in the cache, or compute it with `compute` and remember the result.
+    fn cached<T: Clone>(
+        &self,
+        oid: Oid,
+        entries: impl Fn(&mut CacheState) -> &mut Entries<T>,
+        parse: impl FnOnce(&Value) -> Option<T>,
+        compute: impl FnOnce() -> Option<T>,
+        to_json: impl FnOnce(&T) -> Value,
+    ) -> Option<T> {
+        if let Some(found) = self.with_state(|state| entries(state).get(oid, parse)) {
+            return Some(found);
+        }
+
+        // Computed without holding the lock, so other threads aren't held up by a slow diff.
+        let value = compute()?;
+        self.with_state(|state| entries(state).insert(oid, value.clone(), to_json));
+        Some(value)
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut CacheState) -> T) -> T {
+        let mut guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(guard.get_or_insert_with(|| CacheState {
+            commits: Entries::open(&self.dir, COMMITS_FILE),
+            changes: Entries::open(&self.dir, CHANGES_FILE),
+        }))
+    }
+}
+
+impl<T: Clone> Entries<T> {
+    fn open(dir: &Path, name: &str) -> Self {
+        Self {
+            loaded: HashMap::new(),
+            log: Log::open(dir, name),
+        }
+    }
+
+    /// The entry for `oid`, read from the file and parsed with `parse` the first time.
+    fn get(&mut self, oid: Oid, parse: impl FnOnce(&Value) -> Option<T>) -> Option<T> {
+        if let Some(found) = self.loaded.get(&oid) {
+            return Some(found.clone());
+        }
+        match self.log.read(oid).and_then(|value| parse(&value)) {
+            Some(value) => {
+                self.loaded.insert(oid, value.clone());
+                Some(value)
+            }
+            None => {
+                // Let a freshly computed entry replace a line that can't be read.
+                self.log.index.remove(&oid);
+                None
+            }
+        }
+    }
+
+    /// Remember `value`, appending it to the file unless it's there already.
+    fn insert(&mut self, oid: Oid, value: T, to_json: impl FnOnce(&T) -> Value) {
+        if self.loaded.contains_key(&oid) {
+            return;
+        }
+        if !self.log.index.contains_key(&oid) {
+            self.log.append(oid, to_json(&value));
+        }
+        self.loaded.insert(oid, value);
+    }
+}
+
+impl Log {
+    fn open(dir: &Path, name: &str) -> Self {
+        let path = dir.join(name);
+        let index = match File::open(&path) {
+            Ok(file) => match index_lines(file) {
+                (index, true) => compact(&path, &index).unwrap_or(index),
+                (index, false) => index,
+            },
+            Err(_) => HashMap::new(),
+        };
+        let writer = std::fs::create_dir_all(dir).ok().and_then(|()| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .ok()
+        });
+        Self {
+            index,
+            reader: File::open(&path).ok(),
+            writer,
+        }
+    }
+
+    /// The JSON on `oid`'s line, if it's there and reads back whole.
+    fn read(&mut self, oid: Oid) -> Option<Value> {
+        let (start, len) = *self.index.get(&oid)?;
+        let reader = self.reader.as_mut()?;
+        let mut line = vec![0; len];
+        reader.seek(SeekFrom::Start(start)).ok()?;
+        reader.read_exact(&mut line).ok()?;
+        let (key, json) = split_line(&line)?;
+        if key != oid {
+            return None;
+        }
+        serde_json::from_slice(json).ok()
+    }
+
+    /// Write one entry as a single line, so concurrent appends from other processes don't
+    /// interleave within it.
+    fn append(&mut self, oid: Oid, value: Value) {
+        if let Some(file) = &mut self.writer {
+            let line = format!("{oid}\t{value}\n");
+            if file.write_all(line.as_bytes()).is_err() {
+                self.writer = None;
+            }
+        }
+    }
+}
+
+/// Where each id's line starts in `file`, and its length, reading only as far as the id on
+/// each line. The last line for an id wins. Also says whether any line was a duplicate,
+/// unreadable or unfinished, so that the file is worth rewriting.
+fn index_lines(file: File) -> (HashMap<Oid, (u64, usize)>, bool) {
+    let mut reader = BufReader::new(file);
+    let mut index = HashMap::new();
+    let mut untidy = false;
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                let start = offset;
+                offset += read as u64;
+                // A line cut short by an interrupted write has no newline.
+                let key = line
+                    .strip_suffix(b"\n")
+                    .and_then(|line| Some((split_line(line)?.0, line.len())));
+                match key {
+                    Some((oid, len)) => untidy |= index.insert(oid, (start, len)).is_some(),
+                    None => untidy = true,
+                }
+            }
+        }
+    }
+    (index, untidy)
+}
+
+/// Rewrite the file at `path` with only the lines in `index`, in the same order, and return
+/// where they are now.
+fn compact(path: &Path, index: &HashMap<Oid, (u64, usize)>) -> Option<HashMap<Oid, (u64, usize)>> {
+    let keep: HashMap<u64, Oid> = index
+        .iter()
+        .map(|(oid, (start, _))| (*start, *oid))
+        .collect();
+    let temporary = path.with_extension("jsonl.tmp");
+    let rewrite = || -> std::io::Result<HashMap<Oid, (u64, usize)>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        let mut compacted = HashMap::with_capacity(index.len());
+        let (mut read_offset, mut write_offset) = (0, 0);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if let Some(&oid) = keep.get(&read_offset) {
+                writer.write_all(&line)?;
+                compacted.insert(oid, (write_offset, line.len() - 1));
+                write_offset += read as u64;
+            }
+            read_offset += read as u64;
+        }
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(&temporary, path)?;
+        Ok(compacted)
+    };
+    let compacted = rewrite();
+    if compacted.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    compacted.ok()
+}
+
+/// The id and JSON of a line without its newline.
+fn split_line(line: &[u8]) -> Option<(Oid, &[u8])> {
+    let tab = line.iter().position(|&b| b == b'\t')?;
+    let oid = std::str::from_utf8(&line[..tab]).ok()?.parse().ok()?;
+    Some((oid, &line[tab + 1..]))
+}
+
+fn commit_to_json(info: &CommitInfo) -> Value {
+    json!({
+        "id": info.id.to_string(),
+        "message": info.message,
+        "author": info.author,
+        "author_email": info.author_email,
+        "committer": info.committer,
+        "committer_email": info.committer_email,
+        "time": info.time,
+        "parents": info.parent_ids.iter().map(Oid::to_string).collect::<Vec<_>>(),
+    })
+}
+
+fn commit_from_json(value: &Value) -> Option<CommitInfo> {
+    let string = |key: &str| value.get(key)?.as_str().map(str::to_string);
+    Some(CommitInfo {
+        id: oid_from_json(value.get("id")?)?,
+        message: string("message"),
+        author: string("author"),
+        author_email: string("author_email"),
+        committer: string("committer"),
+        committer_email: string("committer_email"),
+        time: value.get("time")?.as_i64()?,
+        parent_ids: value
+            .get("parents")?
+            .as_array()?
+            .iter()
+            .map(oid_from_json)
+            .collect::<Option<_>>()?,
+    })
+}
+
+fn changes_to_json(oid: Oid, changes: &[FileChange]) -> Value {
+    let changes: Vec<Value> = changes
+        .iter()
+        .map(|change| {
+            json!({
+                "path": change.path(),
+                "old_path": change.old_path(),
+                "status": change.status(),
+                "additions": change.additions(),
+                "deletions": change.deletions(),
+                "is_binary": change.is_binary(),
+            })
+        })
+        .collect();
+    json!({ "id": oid.to_string(), "changes": changes })
+}
+
+fn changes_from_json(value: &Value) -> Option<(Oid, Vec<FileChange>)> {
+    let oid = oid_from_json(value.get("id")?)?;
+    let changes = value
+        .get("changes")?
+        .as_array()?
+        .iter()
+        .map(|change| {
+            let count = |key: &str| change.get(key)?.as_u64().map(|n| n as usize);
+            let mut parsed = FileChange::new(
+                change.get("path")?.as_str()?.to_string(),
+                FileChange::known_status(change.get("status")?.as_str()?)?,
+                count("additions")?,
+                count("deletions")?,
+                change.get("is_binary")?.as_bool()?,
+            );
+            if let Some(old_path) = change.get("old_path").and_then(Value::as_str) {
+                parsed = parsed.with_old_path(old_path.to_string());
+            }
+            Some(parsed)
+        })
+        .collect::<Option<_>>()?;
+    Some((oid, changes))
+}
+
+fn oid_from_json(value: &Value) -> Option<Oid> {
+    value.as_str().and_then(|s| Oid::from_str(s).ok())
+}
+
+impl Backend for CachedBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn path(&self) -> &str {
+        self.inner.path()
+    }
+
+    fn is_bare(&self) -> bool {
+        self.inner.is_bare()
+    }
+
+    fn head(&self) -> Option<Oid> {
+        self.inner.head()
+    }
+
+    fn find_commit(&self, oid: Oid) -> Option<CommitInfo> {
+        self.cached(
+            oid,
+            |state| &mut state.commits,
+            |value| commit_from_json(value).filter(|info| info.id == oid),
+            || self.inner.find_commit(oid),
+            commit_to_json,
+        )
+    }
+
+    fn graph_node(&self, oid: Oid) -> Option<GraphNode> {
+        self.inner.graph_node(oid)
+    }
+
+    fn peel_to_commit(&self, oid: Oid) -> Option<Oid> {
+        self.inner.peel_to_commit(oid)
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.inner.branches()
+    }
+
+    fn tags(&self) -> Vec<TagInfo> {
+        self.inner.tags()
+    }
+
+    fn references(&self) -> Vec<ReferenceInfo> {
+        self.inner.references()
+    }
+
+    fn commit_changes(&self, oid: Oid) -> Option<Vec<FileChange>> {
+        self.cached(
+            oid,
+            |state| &mut state.changes,
+            |value| {
+                changes_from_json(value)
+                    .filter(|(id, _)| *id == oid)
+                    .map(|(_, changes)| changes)
+            },
+            || self.inner.commit_changes(oid),
+            |changes| changes_to_json(oid, changes),
+        )
+    }
+
+    fn status(&self, include_ignored: bool) -> Vec<StatusEntry> {
+        self.inner.status(include_ignored)
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        self.inner.conflicts()
+    }
+
+    fn staged_changes(&self) -> Vec<FileChange> {
+        self.inner.staged_changes()
+    }
+
+    fn unstaged_changes(&self) -> Vec<FileChange> {
+        self.inner.unstaged_changes()
+    }
+
+    fn stashes(&self) -> Vec<StashInfo> {
+        self.inner.stashes()
+    }
+
+    fn reflog(&self, reference: &str) -> Vec<ReflogEntry> {
+        self.inner.reflog(reference)
+    }
+
+    fn notes(&self, oid: Oid, notes_ref: Option<&str>) -> Vec<Note> {
+        self.inner.notes(oid, notes_ref)
+    }
+
+    fn submodules(&self) -> Vec<SubmoduleInfo> {
+        self.inner.submodules()
+    }
+
+    fn open_submodule(&self, name: &str) -> Option<Arc<dyn Backend>> {
+        self.inner.open_submodule(name)
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        self.inner.worktrees()
+    }
+}
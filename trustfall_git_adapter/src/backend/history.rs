@@ -0,0 +1,172 @@
+//! History declared by label, shared by [`MemoryBackendBuilder`](super::MemoryBackendBuilder)
+//! and, with the `testing` feature, `testing::TestRepoBuilder`. Each builder only decides
+//! how to write the commits, branches and tags once they're worked out.
+
+use std::collections::{BTreeMap, HashMap};
+
+use git2::Oid;
+
+use super::CommitBuilder;
+
+/// Path to file contents.
+pub(crate) type Tree = BTreeMap<String, String>;
+
+/// 2023-11-14T22:13:20Z, the time of the first commit unless told otherwise.
+pub(crate) const DEFAULT_START_TIME: i64 = 1_700_000_000;
+
+/// Commits, branches and tags that refer to commits by the label they were declared with.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    commits: Vec<(String, CommitBuilder)>,
+    /// Branch names and the labels of their commits.
+    pub(crate) branches: Vec<(String, String)>,
+    pub(crate) tags: Vec<TagSpec>,
+    head: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TagSpec {
+    pub(crate) name: String,
+    /// The label of the tagged commit.
+    pub(crate) target: String,
+    /// Set for annotated tags.
+    pub(crate) message: Option<String>,
+}
+
+/// A declared commit with its parents, tree, time and identities worked out.
+pub(crate) struct ResolvedCommit<'a> {
+    pub(crate) parent_ids: Vec<Oid>,
+    pub(crate) tree: &'a Tree,
+    pub(crate) time: i64,
+    pub(crate) author: (String, String),
+    pub(crate) committer: (String, String),
+    pub(crate) message: String,
+}
+
+/// The ids of the written commits, by label.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Labels(HashMap<String, Oid>);
+
+impl Labels {
+    /// # Panics
+    ///
+    /// If no commit was declared with that label.
+    pub(crate) fn get(&self, label: &str) -> Oid {
+        *self
+            .0
+            .get(label)
+            .unwrap_or_else(|| panic!("unknown commit label '{label}'"))
+    }
+}
+
+impl History {
+    pub(crate) fn commit(
+        &mut self,
+        label: impl Into<String>,
+        build: impl FnOnce(CommitBuilder) -> CommitBuilder,
+    ) {
+        self.commits
+            .push((label.into(), build(CommitBuilder::default())));
+    }
+
+    pub(crate) fn branch(&mut self, name: impl Into<String>, commit: impl Into<String>) {
+        self.branches.push((name.into(), commit.into()));
+    }
+
+    pub(crate) fn tag(
+        &mut self,
+        name: impl Into<String>,
+        commit: impl Into<String>,
+        message: Option<String>,
+    ) {
+        self.tags.push(TagSpec {
+            name: name.into(),
+            target: commit.into(),
+            message,
+        });
+    }
+
+    pub(crate) fn head(&mut self, branch: impl Into<String>) {
+        self.head = Some(branch.into());
+    }
+
+    /// The branch `HEAD` points at and its commit's label: the one named by
+    /// [`History::head`], or else the first declared.
+    ///
+    /// # Panics
+    ///
+    /// If `head` names a branch that doesn't exist.
+    pub(crate) fn head_branch(&self) -> Option<&(String, String)> {
+        match &self.head {
+            Some(head) => Some(
+                self.branches
+                    .iter()
+                    .find(|(name, _)| name == head)
+                    .unwrap_or_else(|| panic!("HEAD names unknown branch '{head}'")),
+            ),
+            None => self.branches.first(),
+        }
+    }
+
+    /// Work out each commit in the order declared and hand it to `write`, which returns
+    /// the commit's id. Unless [`CommitBuilder::parents`] says otherwise, a commit's parent
+    /// is the previously declared commit, and its tree is its first parent's tree with the
+    /// commit's file changes applied.
+    ///
+    /// Returns the ids by label and the id of the last commit.
+    ///
+    /// # Panics
+    ///
+    /// If a label is used before it is declared or is declared twice.
+    pub(crate) fn write_commits<E>(
+        &self,
+        mut write: impl FnMut(ResolvedCommit<'_>) -> Result<Oid, E>,
+    ) -> Result<(Labels, Option<Oid>), E> {
+        let mut labels = Labels::default();
+        let mut trees: HashMap<Oid, Tree> = HashMap::new();
+        let mut previous: Option<(Oid, i64)> = None;
+
+        for (label, spec) in &self.commits {
+            assert!(
+                !labels.0.contains_key(label),
+                "commit label '{label}' declared twice"
+            );
+            let parent_ids: Vec<Oid> = match &spec.parents {
+                Some(parents) => parents.iter().map(|parent| labels.get(parent)).collect(),
+                None => previous.map(|(oid, _)| oid).into_iter().collect(),
+            };
+
+            let mut tree = parent_ids
+                .first()
+                .map(|parent| trees[parent].clone())
+                .unwrap_or_default();
+            for (path, contents) in &spec.files {
+                match contents {
+                    Some(contents) => tree.insert(path.clone(), contents.clone()),
+                    None => tree.remove(path),
+                };
+            }
+
+            let time = spec
+                .time
+                .unwrap_or_else(|| previous.map_or(DEFAULT_START_TIME, |(_, time)| time + 60));
+            let oid = write(ResolvedCommit {
+                parent_ids,
+                tree: &tree,
+                time,
+                author: spec.author.clone(),
+                committer: spec
+                    .committer
+                    .clone()
+                    .unwrap_or_else(|| spec.author.clone()),
+                message: spec.message.clone(),
+            })?;
+
+            labels.0.insert(label.clone(), oid);
+            trees.insert(oid, tree);
+            previous = Some((oid, time));
+        }
+
+        Ok((labels, previous.map(|(oid, _)| oid)))
+    }
+}
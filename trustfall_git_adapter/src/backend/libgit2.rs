@@ -0,0 +1,497 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use git2::Oid;
+
+use crate::{budget, commit_graph::CommitGraph, trace::trace_span};
+
+use super::{
+    Backend, BranchInfo, CachedBackend, CommitInfo, Conflict, FileChange, GraphNode, Note,
+    ReferenceInfo, ReflogEntry, StashInfo, StatusEntry, SubmoduleInfo, TagInfo, Worktree,
+    directory_trees,
+    pool::{PooledRepo, RepoPool},
+};
+
+/// Reads an on-disk repository through `git2`.
+///
+/// Handles are opened on demand from a pool, one per concurrent call, so the backend
+/// can be shared between threads even though a `git2::Repository` can't.
+///
+/// The commits, trees, blobs and tags each call reads are counted against the running
+/// query's [`Limits::max_objects`](crate::Limits::max_objects). Diffs count the trees of
+/// the directories holding changes and the blobs of the changed files, which are the
+/// ones `git2` reads to compare them.
+#[derive(Debug)]
+pub struct Git2Backend {
+    pool: RepoPool,
+    name: String,
+    path: String,
+    git_dir: PathBuf,
+    objects_dir: PathBuf,
+    is_bare: bool,
+    /// Loaded on first use; `None` inside if the repository has no commit-graph.
+    graph: OnceLock<Option<CommitGraph>>,
+}
+
+impl Git2Backend {
+    /// Open the repository at `path`, failing if it isn't one.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, git2::Error> {
+        git2::Repository::open(path).map(Self::from_repository)
+    }
+
+    /// Find the repository containing `path`, searching parent directories like `git` does.
+    pub fn discover(path: impl AsRef<Path>) -> Result<Self, git2::Error> {
+        git2::Repository::discover(path).map(Self::from_repository)
+    }
+
+    /// A backend for the same repository as `repo`. It opens its own handles, so `repo`
+    /// does not need to outlive it.
+    pub fn new(repo: &git2::Repository) -> Self {
+        Self::with_pool(RepoPool::reopening(repo), repo)
+    }
+
+    fn from_repository(repo: git2::Repository) -> Self {
+        let mut backend = Self::new(&repo);
+        // Keep the handle we already have rather than reopening the repository.
+        backend.pool = RepoPool::from_repository(repo);
+        backend
+    }
+
+    fn with_pool(pool: RepoPool, repo: &git2::Repository) -> Self {
+        let path = pool.root();
+        let name = match repo.find_remote("origin") {
+            Ok(remote) => remote.url().ok().and_then(|url| {
+                url.trim_end_matches(".git")
+                    .rsplit('/')
+                    .next()
+                    .map(|s| s.to_string())
+            }),
+            Err(_) => None,
+        }
+        .unwrap_or_else(|| {
+            // Fallback to directory name if no remote origin
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        });
+
+        Self {
+            name,
+            path: path.to_string_lossy().into_owned(),
+            git_dir: repo.path().to_path_buf(),
+            objects_dir: repo.commondir().join("objects"),
+            is_bare: repo.is_bare(),
+            graph: OnceLock::new(),
+            pool,
+        }
+    }
+
+    /// Remember commit metadata and changes across runs in `.git/git-seek/`.
+    pub fn with_cache(self) -> CachedBackend {
+        let dir = self.git_dir.join("git-seek");
+        CachedBackend::new(self, dir)
+    }
+
+    /// Run `read` with a handle on the repository, or return `T::default()` if it can no
+    /// longer be opened.
+    fn read<T: Default>(&self, read: impl FnOnce(&PooledRepo<'_>) -> T) -> T {
+        self.pool
+            .checkout()
+            .map(|repo| read(&repo))
+            .unwrap_or_default()
+    }
+}
+
+impl Backend for Git2Backend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn is_bare(&self) -> bool {
+        self.is_bare
+    }
+
+    fn head(&self) -> Option<Oid> {
+        self.read(|repo| repo.head().ok().and_then(|head| head.target()))
+    }
+
+    fn find_commit(&self, oid: Oid) -> Option<CommitInfo> {
+        let _span = trace_span!("find_commit", %oid).entered();
+        self.read(|repo| {
+            let commit = repo.find_commit(oid).ok()?;
+            budget::charge(1);
+            Some(commit_info(&commit))
+        })
+    }
+
+    fn graph_node(&self, oid: Oid) -> Option<GraphNode> {
+        self.graph
+            .get_or_init(|| CommitGraph::open(&self.objects_dir))
+            .as_ref()?
+            .node(oid)
+    }
+
+    fn peel_to_commit(&self, oid: Oid) -> Option<Oid> {
+        self.read(|repo| {
+            let object = repo.find_object(oid, None).ok()?;
+            let commit = object.peel_to_commit().ok()?;
+            budget::charge(if commit.id() == oid { 1 } else { 2 });
+            Some(commit.id())
+        })
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.read(|repo| match repo.branches(Some(git2::BranchType::Local)) {
+            Ok(branches) => branches
+                .filter_map(|branch_result| {
+                    let (branch, _) = branch_result.ok()?;
+                    Some(BranchInfo {
+                        name: branch.name().ok().flatten()?.to_string(),
+                        target: branch.get().target(),
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+
+    fn tags(&self) -> Vec<TagInfo> {
+        self.read(|repo| match repo.tag_names(None) {
+            Ok(tag_names) => tag_names
+                .iter()
+                .flatten()
+                .flatten()
+                .filter_map(|name| {
+                    let refname = format!("refs/tags/{}", name);
+                    let reference = repo.find_reference(&refname).ok()?;
+
+                    // Try to peel to a tag object (annotated tag)
+                    let (target, message, tagger_name, tagger_email) =
+                        if let Ok(tag_obj) = reference.peel_to_tag() {
+                            let msg = tag_obj.message().ok().flatten().map(|m| m.to_string());
+                            let t_name = tag_obj
+                                .tagger()
+                                .and_then(|t| t.name().ok().map(|n| n.to_string()));
+                            let t_email = tag_obj
+                                .tagger()
+                                .and_then(|t| t.email().ok().map(|e| e.to_string()));
+                            let oid = tag_obj
+                                .target()
+                                .ok()
+                                .and_then(|obj| obj.into_commit().ok())
+                                .map(|c| c.id())?;
+                            budget::charge(2);
+                            (oid, msg, t_name, t_email)
+                        } else {
+                            // Lightweight tag — points directly to a commit
+                            let oid = reference.peel_to_commit().ok()?.id();
+                            budget::charge(1);
+                            (oid, None, None, None)
+                        };
+
+                    Some(TagInfo {
+                        name: name.to_string(),
+                        target,
+                        message,
+                        tagger_name,
+                        tagger_email,
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+
+    fn references(&self) -> Vec<ReferenceInfo> {
+        self.read(|repo| {
+            // `references()` doesn't list HEAD, but its reflog is often the most useful one.
+            let head = repo.find_reference("HEAD").ok();
+            head.into_iter()
+                .chain(
+                    repo.references()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|reference| reference.ok()),
+                )
+                .filter_map(|reference| reference_info(&reference))
+                .collect()
+        })
+    }
+
+    fn commit_changes(&self, oid: Oid) -> Option<Vec<FileChange>> {
+        let _span = trace_span!("diff", commit = %oid).entered();
+        self.read(|repo| {
+            let commit = repo.find_commit(oid).ok()?;
+
+            // Diff against the first parent; root commits are diffed against an empty tree.
+            let parent_tree = match commit.parent_count() {
+                0 => None,
+                _ => Some(commit.parent(0).and_then(|parent| parent.tree()).ok()?),
+            };
+            let tree = commit.tree().ok()?;
+            let mut diff = repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+                .ok()?;
+            let old = if parent_tree.is_some() {
+                // The parent commit as well as its tree.
+                budget::charge(1);
+                Side::Tree
+            } else {
+                Side::Empty
+            };
+            budget::charge(1 + diff_objects(&diff, old, Side::Tree));
+            FileChange::from_diff(&mut diff).ok()
+        })
+    }
+
+    fn status(&self, include_ignored: bool) -> Vec<StatusEntry> {
+        let _span = trace_span!("status", include_ignored).entered();
+        self.read(|repo| {
+            let mut options = git2::StatusOptions::new();
+            options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .include_ignored(include_ignored);
+
+            match repo.statuses(Some(&mut options)) {
+                Ok(statuses) => statuses
+                    .iter()
+                    .filter_map(|entry| {
+                        let path = entry.path().ok()?.to_string();
+                        Some(StatusEntry::new(path, entry.status()))
+                    })
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        })
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        self.read(|repo| {
+            repo.index()
+                .and_then(|index| {
+                    index.conflicts().map(|conflicts| {
+                        conflicts
+                            .filter_map(|conflict| conflict.ok())
+                            .map(|conflict| Conflict::new(&conflict))
+                            .collect()
+                    })
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    fn staged_changes(&self) -> Vec<FileChange> {
+        let _span = trace_span!("diff", to = "index").entered();
+        self.read(|repo| {
+            // An unborn HEAD has no tree yet, so everything in the index counts as added.
+            let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+            let old = match head_tree {
+                // HEAD's commit as well as its tree.
+                Some(_) => {
+                    budget::charge(1);
+                    Side::Tree
+                }
+                None => Side::Empty,
+            };
+
+            repo.diff_tree_to_index(head_tree.as_ref(), None, None)
+                .and_then(|mut diff| {
+                    budget::charge(diff_objects(&diff, old, Side::Index));
+                    FileChange::from_diff(&mut diff)
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    fn unstaged_changes(&self) -> Vec<FileChange> {
+        let _span = trace_span!("diff", to = "workdir").entered();
+        self.read(|repo| {
+            repo.diff_index_to_workdir(None, None)
+                .and_then(|mut diff| {
+                    budget::charge(diff_objects(&diff, Side::Index, Side::WorkTree));
+                    FileChange::from_diff(&mut diff)
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    fn stashes(&self) -> Vec<StashInfo> {
+        self.read(|repo| {
+            // Stashes live in the reflog of `refs/stash`, newest first, which is exactly
+            // the `stash@{N}` numbering.
+            match repo.reflog("refs/stash") {
+                Ok(reflog) => reflog
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| StashInfo {
+                        index,
+                        message: entry.message().ok().flatten().map(|m| m.to_string()),
+                        commit: entry.id_new(),
+                    })
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        })
+    }
+
+    fn reflog(&self, reference: &str) -> Vec<ReflogEntry> {
+        self.read(|repo| match repo.reflog(reference) {
+            Ok(reflog) => reflog
+                .iter()
+                .map(|entry| ReflogEntry::new(&entry))
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+
+    fn notes(&self, oid: Oid, notes_ref: Option<&str>) -> Vec<Note> {
+        self.read(|repo| {
+            // Without an explicit ref, look the commit up in every notes ref.
+            let notes_refs = match notes_ref {
+                Some(notes_ref) => vec![notes_ref.to_string()],
+                None => repo
+                    .references_glob("refs/notes/*")
+                    .map(|references| {
+                        references
+                            .filter_map(|reference| {
+                                reference.ok()?.name().ok().map(|n| n.to_string())
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            };
+
+            notes_refs
+                .into_iter()
+                .filter_map(|notes_ref| {
+                    let note = repo.find_note(Some(&notes_ref), oid).ok()?;
+                    // The notes commit, its tree and the note's blob.
+                    budget::charge(3);
+                    Some(Note::new(notes_ref, &note))
+                })
+                .collect()
+        })
+    }
+
+    fn submodules(&self) -> Vec<SubmoduleInfo> {
+        self.read(|repo| match repo.submodules() {
+            Ok(submodules) => submodules
+                .iter()
+                .filter_map(|submodule| {
+                    Some(SubmoduleInfo {
+                        name: submodule.name().ok()?.to_string(),
+                        path: submodule.path().to_string_lossy().into_owned(),
+                        url: submodule.url().ok().flatten().map(|s| s.to_string()),
+                        branch: submodule.branch().ok().flatten().map(|s| s.to_string()),
+                        head_oid: submodule.head_id(),
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+
+    fn open_submodule(&self, name: &str) -> Option<Arc<dyn Backend>> {
+        // Submodules that aren't initialized and checked out have nothing to open.
+        self.read(|repo| {
+            repo.find_submodule(name)
+                .and_then(|submodule| submodule.open())
+                .ok()
+                .map(|opened| Arc::new(Self::from_repository(opened)) as Arc<dyn Backend>)
+        })
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        self.read(|repo| match repo.worktrees() {
+            Ok(names) => names
+                .iter()
+                .flatten()
+                .flatten()
+                .filter_map(|name| repo.find_worktree(name).ok())
+                .map(|worktree| Worktree::new(&worktree))
+                .collect(),
+            Err(_) => Vec::new(),
+        })
+    }
+}
+
+/// Where one side of a diff comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Tree,
+    Index,
+    WorkTree,
+    /// The empty tree a root commit is compared with.
+    Empty,
+}
+
+/// The objects comparing `old` with `new` reads for `diff`, besides any commits: the root
+/// tree and the trees of changed directories on tree sides, and the blobs of the changed
+/// files on sides kept in the object database.
+fn diff_objects(diff: &git2::Diff<'_>, old: Side, new: Side) -> u64 {
+    [old, new]
+        .into_iter()
+        .enumerate()
+        .map(|(i, side)| {
+            let files = diff.deltas().map(|delta| match i {
+                0 => delta.old_file(),
+                _ => delta.new_file(),
+            });
+            let files: Vec<_> = files.filter(|file| !file.id().is_zero()).collect();
+            match side {
+                Side::Tree => {
+                    1 + directory_trees(files.iter().filter_map(|file| file.path()))
+                        + files.len() as u64
+                }
+                Side::Index => files.len() as u64,
+                Side::WorkTree | Side::Empty => 0,
+            }
+        })
+        .sum()
+}
+
+fn commit_info(commit: &git2::Commit<'_>) -> CommitInfo {
+    let (author, committer) = (commit.author(), commit.committer());
+    CommitInfo {
+        id: commit.id(),
+        message: commit.message().ok().map(|s| s.to_string()),
+        author: author.name().ok().map(|s| s.to_string()),
+        author_email: author.email().ok().map(|s| s.to_string()),
+        committer: committer.name().ok().map(|s| s.to_string()),
+        committer_email: committer.email().ok().map(|s| s.to_string()),
+        time: commit.time().seconds(),
+        parent_ids: commit.parent_ids().collect(),
+    }
+}
+
+fn reference_info(reference: &git2::Reference<'_>) -> Option<ReferenceInfo> {
+    let name = reference.name().ok()?.to_string();
+    let kind = if reference.is_branch() {
+        "branch"
+    } else if reference.is_remote() {
+        "remote"
+    } else if reference.is_tag() {
+        "tag"
+    } else if reference.is_note() {
+        "note"
+    } else if name == "HEAD" {
+        "head"
+    } else {
+        "other"
+    };
+
+    Some(ReferenceInfo {
+        shorthand: reference.shorthand().unwrap_or(&name).to_string(),
+        kind: kind.to_string(),
+        target: reference.resolve().ok().and_then(|r| r.target()),
+        name,
+    })
+}
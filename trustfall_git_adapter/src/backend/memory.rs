@@ -0,0 +1,449 @@
+use std::{collections::HashMap, convert::Infallible, path::Path};
+
+use git2::{ObjectType, Oid};
+
+use crate::budget;
+
+use super::{
+    Backend, BranchInfo, CommitInfo, FileChange, GraphNode, ReferenceInfo, TagInfo,
+    directory_trees,
+    history::{History, Tree},
+};
+
+/// A repository held entirely in memory, built with [`MemoryBackend::builder`].
+///
+/// Commit ids are hashed from each commit's tree, parents, identities, time and message, so
+/// a fixture always produces the same ids. They are not the ids `git` would compute.
+/// Only history is modelled: there is no index, work tree, stash, reflog or notes, and
+/// diffs don't detect renames.
+///
+/// ```
+/// use trustfall_git_adapter::{GitAdapter, backend::MemoryBackend};
+///
+/// let backend = MemoryBackend::builder("fixture")
+///     .commit("init", |c| c.message("Initial commit").file("README.md", "hello\n"))
+///     .commit("feature", |c| {
+///         c.message("Add feature")
+///             .author("Alice", "alice@example.com")
+///             .file("src/lib.rs", "pub fn feature() {}\n")
+///     })
+///     .branch("main", "feature")
+///     .tag("v1.0.0", "feature")
+///     .build();
+///
+/// let adapter = GitAdapter::with_backend(backend);
+/// let rows = adapter
+///     .execute(
+///         "{ repository { commits { hash @output } } }",
+///         std::collections::BTreeMap::<&str, &str>::new(),
+///     )
+///     .unwrap();
+/// assert_eq!(rows.len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryBackend {
+    name: String,
+    path: String,
+    head: Option<Oid>,
+    commits: HashMap<Oid, MemoryCommit>,
+    branches: Vec<BranchInfo>,
+    tags: Vec<TagInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryCommit {
+    info: CommitInfo,
+    tree: Tree,
+    generation: u32,
+}
+
+impl MemoryBackend {
+    pub fn builder(name: impl Into<String>) -> MemoryBackendBuilder {
+        MemoryBackendBuilder {
+            name: name.into(),
+            history: History::default(),
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// There is no work tree.
+    fn is_bare(&self) -> bool {
+        true
+    }
+
+    fn head(&self) -> Option<Oid> {
+        self.head
+    }
+
+    fn find_commit(&self, oid: Oid) -> Option<CommitInfo> {
+        let commit = self.commits.get(&oid)?;
+        budget::charge(1);
+        Some(commit.info.clone())
+    }
+
+    /// Every commit is in the graph, since the whole history is in memory anyway.
+    fn graph_node(&self, oid: Oid) -> Option<GraphNode> {
+        self.commits.get(&oid).map(|commit| GraphNode {
+            id: oid,
+            parent_ids: commit.info.parent_ids.clone(),
+            time: commit.info.time,
+            generation: Some(commit.generation),
+        })
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.branches.clone()
+    }
+
+    fn tags(&self) -> Vec<TagInfo> {
+        self.tags.clone()
+    }
+
+    fn references(&self) -> Vec<ReferenceInfo> {
+        let head = self.head.map(|target| ReferenceInfo {
+            name: "HEAD".to_string(),
+            shorthand: "HEAD".to_string(),
+            kind: "head".to_string(),
+            target: Some(target),
+        });
+        let branches = self.branches.iter().map(|branch| ReferenceInfo {
+            name: format!("refs/heads/{}", branch.name),
+            shorthand: branch.name.clone(),
+            kind: "branch".to_string(),
+            target: branch.target,
+        });
+        let tags = self.tags.iter().map(|tag| ReferenceInfo {
+            name: format!("refs/tags/{}", tag.name),
+            shorthand: tag.name.clone(),
+            kind: "tag".to_string(),
+            target: Some(tag.target),
+        });
+        head.into_iter().chain(branches).chain(tags).collect()
+    }
+
+    fn commit_changes(&self, oid: Oid) -> Option<Vec<FileChange>> {
+        let commit = self.commits.get(&oid)?;
+        let empty = Tree::new();
+        let parent_tree = commit
+            .info
+            .parent_ids
+            .first()
+            .and_then(|parent| self.commits.get(parent))
+            .map_or(&empty, |parent| &parent.tree);
+
+        let changes = diff_trees(parent_tree, &commit.tree);
+        // Charge what the same diff reads in a repository: each side's commit and root
+        // tree, and the trees and blobs along the changed paths it has.
+        let mut sides = vec![&commit.tree];
+        if !commit.info.parent_ids.is_empty() {
+            sides.push(parent_tree);
+        }
+        let objects: u64 = sides
+            .into_iter()
+            .map(|tree| {
+                let paths: Vec<&Path> = changes
+                    .iter()
+                    .map(FileChange::path)
+                    .filter(|path| tree.contains_key(*path))
+                    .map(Path::new)
+                    .collect();
+                2 + directory_trees(paths.iter().copied()) + paths.len() as u64
+            })
+            .sum();
+        budget::charge(objects);
+        Some(changes)
+    }
+}
+
+fn diff_trees(old: &Tree, new: &Tree) -> Vec<FileChange> {
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let (before, after) = (old.get(path), new.get(path));
+            let status = match (before, after) {
+                (None, Some(_)) => "added",
+                (Some(_), None) => "deleted",
+                (Some(before), Some(after)) if before != after => "modified",
+                _ => return None,
+            };
+            let (before, after) = (
+                before.map_or("", String::as_str),
+                after.map_or("", String::as_str),
+            );
+
+            let is_binary = before.contains('\0') || after.contains('\0');
+            let (additions, deletions) = if is_binary {
+                (0, 0)
+            } else {
+                line_stats(Path::new(path), before, after).unwrap_or((0, 0))
+            };
+            Some(FileChange::new(
+                path.clone(),
+                status,
+                additions,
+                deletions,
+                is_binary,
+            ))
+        })
+        .collect()
+}
+
+fn line_stats(path: &Path, before: &str, after: &str) -> Result<(usize, usize), git2::Error> {
+    let patch = git2::Patch::from_buffers(
+        before.as_bytes(),
+        Some(path),
+        after.as_bytes(),
+        Some(path),
+        None,
+    )?;
+    let (_, additions, deletions) = patch.line_stats()?;
+    Ok((additions, deletions))
+}
+
+/// Declares the history of a [`MemoryBackend`].
+///
+/// Commits, branches and tags refer to commits by the label they were declared with.
+///
+/// # Panics
+///
+/// [`MemoryBackendBuilder::build`] panics if a label is used before it is declared, is
+/// declared twice, or if `head` names a branch that doesn't exist.
+#[derive(Debug, Clone)]
+pub struct MemoryBackendBuilder {
+    name: String,
+    history: History,
+}
+
+impl MemoryBackendBuilder {
+    /// Add a commit. Unless [`CommitBuilder::parents`] says otherwise, its parent is the
+    /// previously declared commit, and its tree is its first parent's tree with the
+    /// commit's file changes applied.
+    pub fn commit(
+        mut self,
+        label: impl Into<String>,
+        build: impl FnOnce(CommitBuilder) -> CommitBuilder,
+    ) -> Self {
+        self.history.commit(label, build);
+        self
+    }
+
+    pub fn branch(mut self, name: impl Into<String>, commit: impl Into<String>) -> Self {
+        self.history.branch(name, commit);
+        self
+    }
+
+    pub fn tag(mut self, name: impl Into<String>, commit: impl Into<String>) -> Self {
+        self.history.tag(name, commit, None);
+        self
+    }
+
+    /// Add an annotated tag. The tagger is the tagged commit's committer.
+    pub fn annotated_tag(
+        mut self,
+        name: impl Into<String>,
+        commit: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.history.tag(name, commit, Some(message.into()));
+        self
+    }
+
+    /// Point `HEAD` at `branch`. By default it points at the first declared branch, or at
+    /// the last commit if there are no branches.
+    pub fn head(mut self, branch: impl Into<String>) -> Self {
+        self.history.head(branch);
+        self
+    }
+
+    pub fn build(self) -> MemoryBackend {
+        let mut commits: HashMap<Oid, MemoryCommit> = HashMap::new();
+        let (labels, last) = self
+            .history
+            .write_commits(|commit| {
+                let (author, author_email) = commit.author;
+                let (committer, committer_email) = commit.committer;
+                let mut info = CommitInfo {
+                    id: Oid::ZERO_SHA1,
+                    message: Some(commit.message),
+                    author: Some(author),
+                    author_email: Some(author_email),
+                    committer: Some(committer),
+                    committer_email: Some(committer_email),
+                    time: commit.time,
+                    parent_ids: commit.parent_ids,
+                };
+                info.id = commit_id(&info, commit.tree);
+
+                // Parents are always declared first, so their generations are known.
+                let generation = 1 + info
+                    .parent_ids
+                    .iter()
+                    .map(|parent| commits[parent].generation)
+                    .max()
+                    .unwrap_or(0);
+                let id = info.id;
+                commits.insert(
+                    id,
+                    MemoryCommit {
+                        info,
+                        tree: commit.tree.clone(),
+                        generation,
+                    },
+                );
+                Ok::<_, Infallible>(id)
+            })
+            .unwrap_or_else(|never| match never {});
+
+        let mut branches: Vec<BranchInfo> = self
+            .history
+            .branches
+            .iter()
+            .map(|(name, commit)| BranchInfo {
+                name: name.clone(),
+                target: Some(labels.get(commit)),
+            })
+            .collect();
+
+        let head = match self.history.head_branch() {
+            Some((_, commit)) => Some(labels.get(commit)),
+            None => last,
+        };
+
+        let mut tags: Vec<TagInfo> = self
+            .history
+            .tags
+            .into_iter()
+            .map(|spec| {
+                let target = labels.get(&spec.target);
+                let tagger = spec.message.as_ref().map(|_| &commits[&target].info);
+                TagInfo {
+                    name: spec.name,
+                    target,
+                    tagger_name: tagger.and_then(|info| info.committer.clone()),
+                    tagger_email: tagger.and_then(|info| info.committer_email.clone()),
+                    message: spec.message,
+                }
+            })
+            .collect();
+
+        // git lists references by name.
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+
+        MemoryBackend {
+            path: format!("memory:{}", self.name),
+            name: self.name,
+            head,
+            commits,
+            branches,
+            tags,
+        }
+    }
+}
+
+fn commit_id(info: &CommitInfo, tree: &Tree) -> Oid {
+    let mut object = String::new();
+    for (path, contents) in tree {
+        object.push_str(&format!("{path}\0{}\n", contents.len()));
+        object.push_str(contents);
+    }
+    let tree_id = Oid::hash_object(ObjectType::Tree, object.as_bytes())
+        .expect("hashing an in-memory buffer cannot fail");
+
+    let mut object = format!("tree {tree_id}\n");
+    for parent in &info.parent_ids {
+        object.push_str(&format!("parent {parent}\n"));
+    }
+    object.push_str(&format!(
+        "author {} <{}> {} +0000\ncommitter {} <{}> {} +0000\n\n{}",
+        info.author.as_deref().unwrap_or_default(),
+        info.author_email.as_deref().unwrap_or_default(),
+        info.time,
+        info.committer.as_deref().unwrap_or_default(),
+        info.committer_email.as_deref().unwrap_or_default(),
+        info.time,
+        info.message.as_deref().unwrap_or_default(),
+    ));
+    Oid::hash_object(ObjectType::Commit, object.as_bytes())
+        .expect("hashing an in-memory buffer cannot fail")
+}
+
+/// One commit of a [`MemoryBackendBuilder`], or of a `testing::TestRepoBuilder` with the
+/// `testing` feature. Authored by `Test User <test@example.com>` a minute after the
+/// previous commit unless told otherwise.
+#[derive(Debug, Clone)]
+pub struct CommitBuilder {
+    pub(crate) message: String,
+    pub(crate) author: (String, String),
+    pub(crate) committer: Option<(String, String)>,
+    pub(crate) time: Option<i64>,
+    pub(crate) parents: Option<Vec<String>>,
+    pub(crate) files: Vec<(String, Option<String>)>,
+}
+
+impl Default for CommitBuilder {
+    fn default() -> Self {
+        Self {
+            message: String::new(),
+            author: ("Test User".to_string(), "test@example.com".to_string()),
+            committer: None,
+            time: None,
+            parents: None,
+            files: Vec::new(),
+        }
+    }
+}
+
+impl CommitBuilder {
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    /// Set the author, who is also the committer unless [`CommitBuilder::committer`] is set.
+    pub fn author(mut self, name: impl Into<String>, email: impl Into<String>) -> Self {
+        self.author = (name.into(), email.into());
+        self
+    }
+
+    pub fn committer(mut self, name: impl Into<String>, email: impl Into<String>) -> Self {
+        self.committer = Some((name.into(), email.into()));
+        self
+    }
+
+    /// Commit time in seconds since the Unix epoch.
+    pub fn time(mut self, seconds: i64) -> Self {
+        self.time = Some(seconds);
+        self
+    }
+
+    /// Labels of the parent commits; more than one makes a merge and none a root commit.
+    pub fn parents<S: Into<String>>(mut self, labels: impl IntoIterator<Item = S>) -> Self {
+        self.parents = Some(labels.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Add or replace a file.
+    pub fn file(mut self, path: impl Into<String>, contents: impl Into<String>) -> Self {
+        self.files.push((path.into(), Some(contents.into())));
+        self
+    }
+
+    pub fn remove(mut self, path: impl Into<String>) -> Self {
+        self.files.push((path.into(), None));
+        self
+    }
+}
@@ -0,0 +1,206 @@
+//! Where the adapter reads git data from.
+//!
+//! Resolvers never touch `git2` directly; they go through a [`Backend`]. [`Git2Backend`]
+//! reads an on-disk repository and is what [`GitAdapter::open`](crate::GitAdapter::open)
+//! and friends use. [`MemoryBackend`] holds a declaratively built history in memory, which
+//! makes it a fast fixture for testing queries.
+
+use std::{collections::HashSet, path::Path, sync::Arc};
+
+use git2::Oid;
+
+pub use crate::types::{
+    Conflict, FileChange, IndexEntry, Note, ReflogEntry, StatusEntry, Worktree,
+};
+
+mod cache;
+mod history;
+mod libgit2;
+mod memory;
+mod pool;
+
+pub use cache::CachedBackend;
+#[cfg(feature = "testing")]
+pub(crate) use history::{History, Labels, Tree};
+pub use libgit2::Git2Backend;
+pub use memory::{CommitBuilder, MemoryBackend, MemoryBackendBuilder};
+
+/// Source of the data behind every vertex.
+///
+/// History walks only need [`Backend::head`] and [`Backend::find_commit`]; the adapter
+/// follows `parent_ids` itself. Methods describing state that only exists in an on-disk
+/// repository (the index, work tree, stashes, reflogs, notes, submodules and worktrees)
+/// default to returning nothing.
+///
+/// Lookups that fail are reported as missing data rather than errors, which resolvers
+/// turn into an empty result for that vertex.
+pub trait Backend: Send + Sync + std::fmt::Debug {
+    /// The last path segment of the `origin` remote, or else the directory name.
+    fn name(&self) -> &str;
+
+    /// The work tree for regular repositories, or the git directory for bare ones.
+    fn path(&self) -> &str;
+
+    fn is_bare(&self) -> bool;
+
+    /// The commit `HEAD` points to, or `None` on an unborn branch.
+    fn head(&self) -> Option<Oid>;
+
+    fn find_commit(&self, oid: Oid) -> Option<CommitInfo>;
+
+    /// Parents, time and generation number of `oid`, read from a commit-graph without
+    /// decoding the commit. History walks and reachability checks use this when it's
+    /// available and fall back to [`Backend::find_commit`] otherwise.
+    fn graph_node(&self, _oid: Oid) -> Option<GraphNode> {
+        None
+    }
+
+    /// The commit `oid` names, peeling annotated tag objects.
+    fn peel_to_commit(&self, oid: Oid) -> Option<Oid> {
+        Some(oid)
+    }
+
+    /// Local branches.
+    fn branches(&self) -> Vec<BranchInfo>;
+
+    fn tags(&self) -> Vec<TagInfo>;
+
+    /// Every reference, with `HEAD` first.
+    fn references(&self) -> Vec<ReferenceInfo>;
+
+    /// Files changed by commit `oid` against its first parent. Root commits are compared
+    /// with an empty tree. `None` if the diff couldn't be computed, e.g. because `oid`
+    /// isn't a commit or one of the trees is missing.
+    fn commit_changes(&self, oid: Oid) -> Option<Vec<FileChange>>;
+
+    fn status(&self, _include_ignored: bool) -> Vec<StatusEntry> {
+        Vec::new()
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        Vec::new()
+    }
+
+    /// Changes between `HEAD` and the index.
+    fn staged_changes(&self) -> Vec<FileChange> {
+        Vec::new()
+    }
+
+    /// Changes between the index and the work tree.
+    fn unstaged_changes(&self) -> Vec<FileChange> {
+        Vec::new()
+    }
+
+    fn stashes(&self) -> Vec<StashInfo> {
+        Vec::new()
+    }
+
+    fn reflog(&self, _reference: &str) -> Vec<ReflogEntry> {
+        Vec::new()
+    }
+
+    /// Notes attached to `oid`, from `notes_ref` or, if `None`, from every `refs/notes/*`.
+    fn notes(&self, _oid: Oid, _notes_ref: Option<&str>) -> Vec<Note> {
+        Vec::new()
+    }
+
+    fn submodules(&self) -> Vec<SubmoduleInfo> {
+        Vec::new()
+    }
+
+    /// The repository of a checked-out submodule.
+    fn open_submodule(&self, _name: &str) -> Option<Arc<dyn Backend>> {
+        None
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        Vec::new()
+    }
+}
+
+/// The trees a diff reads besides the root one: those of every directory holding one of
+/// `paths`, counting each directory once.
+pub(crate) fn directory_trees<'a>(paths: impl IntoIterator<Item = &'a Path>) -> u64 {
+    let mut directories = HashSet::new();
+    for path in paths {
+        let mut directory = path.parent();
+        while let Some(dir) = directory.filter(|dir| !dir.as_os_str().is_empty()) {
+            if !directories.insert(dir) {
+                break;
+            }
+            directory = dir.parent();
+        }
+    }
+    directories.len() as u64
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    pub id: Oid,
+    pub message: Option<String>,
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    pub committer: Option<String>,
+    pub committer_email: Option<String>,
+    /// Commit time in seconds since the Unix epoch.
+    pub time: i64,
+    pub parent_ids: Vec<Oid>,
+}
+
+/// A commit's entry in a commit-graph.
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub id: Oid,
+    pub parent_ids: Vec<Oid>,
+    /// Commit time in seconds since the Unix epoch.
+    pub time: i64,
+    /// The topological level: 1 for root commits, otherwise one more than the highest
+    /// parent. `None` if the graph was written without generation numbers.
+    pub generation: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    pub name: String,
+    /// The commit at the tip of the branch.
+    pub target: Option<Oid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagInfo {
+    pub name: String,
+    /// The tagged commit, already peeled for annotated tags.
+    pub target: Oid,
+    /// The tag message; it and the tagger are only set for annotated tags.
+    pub message: Option<String>,
+    pub tagger_name: Option<String>,
+    pub tagger_email: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReferenceInfo {
+    pub name: String,
+    pub shorthand: String,
+    /// One of `branch`, `remote`, `tag`, `note`, `head` or `other`.
+    pub kind: String,
+    /// The object the reference points to, after following symbolic references.
+    pub target: Option<Oid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StashInfo {
+    /// Position in the stash list, i.e. the `N` in `stash@{N}`.
+    pub index: usize,
+    pub message: Option<String>,
+    pub commit: Oid,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmoduleInfo {
+    pub name: String,
+    pub path: String,
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    /// The commit recorded for this submodule in the superproject's `HEAD`.
+    pub head_oid: Option<Oid>,
+}
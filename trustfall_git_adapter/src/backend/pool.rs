@@ -0,0 +1,90 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Hands out `git2::Repository` handles for one repository.
+///
+/// A `git2::Repository` can move between threads but can't be shared by them, so each
+/// backend call checks out its own handle for as long as it needs one. Handles are returned
+/// on drop and reused, so the pool only grows to the number of handles in use at once.
+pub(super) struct RepoPool {
+    root: PathBuf,
+    idle: Mutex<Vec<git2::Repository>>,
+}
+
+impl RepoPool {
+    /// Create a pool whose first handle is `repo`.
+    pub(super) fn from_repository(repo: git2::Repository) -> Self {
+        Self {
+            root: root(&repo).to_path_buf(),
+            idle: Mutex::new(vec![repo]),
+        }
+    }
+
+    /// Create a pool that opens its own handles on the same repository as `repo`.
+    pub(super) fn reopening(repo: &git2::Repository) -> Self {
+        Self {
+            root: root(repo).to_path_buf(),
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The work tree for regular repositories, or the git directory for bare ones.
+    pub(super) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Take an idle handle, opening a new one if every handle is in use.
+    pub(super) fn checkout(&self) -> Result<PooledRepo<'_>, git2::Error> {
+        // A panic can't leave a list of idle handles half-updated, so a poisoned lock is
+        // still safe to use.
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let repo = match idle {
+            Some(repo) => repo,
+            None => git2::Repository::open(&self.root)?,
+        };
+        Ok(PooledRepo {
+            pool: self,
+            repo: Some(repo),
+        })
+    }
+}
+
+impl std::fmt::Debug for RepoPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RepoPool")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+/// A repository handle checked out of a [`RepoPool`], returned to it when dropped.
+pub(super) struct PooledRepo<'p> {
+    pool: &'p RepoPool,
+    repo: Option<git2::Repository>,
+}
+
+impl Deref for PooledRepo<'_> {
+    type Target = git2::Repository;
+
+    fn deref(&self) -> &git2::Repository {
+        self.repo.as_ref().expect("handle is only taken on drop")
+    }
+}
+
+impl Drop for PooledRepo<'_> {
+    fn drop(&mut self) {
+        if let Some(repo) = self.repo.take() {
+            let mut idle = self.pool.idle.lock().unwrap_or_else(|e| e.into_inner());
+            idle.push(repo);
+        }
+    }
+}
+
+fn root(repo: &git2::Repository) -> &Path {
+    let path = repo.workdir().unwrap_or(repo.path());
+    // git2 reports directories with a trailing separator; `components` drops it.
+    path.components().as_path()
+}
@@ -0,0 +1,292 @@
+use std::{
+    cell::RefCell,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use git2::Oid;
+
+use crate::{
+    Row,
+    backend::{
+        Backend, BranchInfo, CommitInfo, Conflict, FileChange, GraphNode, Note, ReferenceInfo,
+        ReflogEntry, StashInfo, StatusEntry, SubmoduleInfo, TagInfo, Worktree,
+    },
+};
+
+/// Bounds on the work a single query may do, for
+/// [`GitAdapter::execute_with_limits`](crate::GitAdapter::execute_with_limits).
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Wall-clock time allowed for the whole query.
+    pub timeout: Option<Duration>,
+    /// Result rows to return; producing one more stops the query.
+    pub max_rows: Option<usize>,
+    /// Git objects (commits, trees, blobs and tags) the query may read from the object
+    /// database. Commits found in the commit-graph or in a [`CachedBackend`] aren't read,
+    /// so they don't count.
+    ///
+    /// [`CachedBackend`]: crate::backend::CachedBackend
+    pub max_objects: Option<u64>,
+}
+
+/// Which limit stopped a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Timeout(Duration),
+    MaxRows(usize),
+    MaxObjects(u64),
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(timeout) => write!(f, "query timed out after {timeout:?}"),
+            Self::MaxRows(rows) => write!(f, "query produced more than {rows} rows"),
+            Self::MaxObjects(objects) => {
+                write!(f, "query read more than {objects} git objects")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// The rows a limited query produced, and the limit that stopped it early, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitedResults {
+    /// Every row completed before the query stopped.
+    pub rows: Vec<Row>,
+    pub exceeded: Option<LimitExceeded>,
+    /// Git objects read while running the query.
+    pub objects_read: u64,
+}
+
+/// How a streamed query ended: the number of rows passed on, and the limit that stopped
+/// it early, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamedResults {
+    pub rows: usize,
+    pub exceeded: Option<LimitExceeded>,
+    /// Git objects read while running the query.
+    pub objects_read: u64,
+}
+
+impl StreamedResults {
+    /// The [`LimitedResults`] for the same query, given the rows it streamed.
+    pub(crate) fn with_rows(self, rows: Vec<Row>) -> LimitedResults {
+        LimitedResults {
+            rows,
+            exceeded: self.exceeded,
+            objects_read: self.objects_read,
+        }
+    }
+}
+
+/// The budget one query draws from, shared by every backend it reads through.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: Limits,
+    deadline: Option<Instant>,
+    objects_read: AtomicU64,
+    exceeded: Mutex<Option<LimitExceeded>>,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            limits,
+            objects_read: AtomicU64::new(0),
+            exceeded: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn objects_read(&self) -> u64 {
+        self.objects_read.load(Ordering::Relaxed)
+    }
+
+    /// The limit the query has run into, checking the clock first.
+    pub(crate) fn exceeded(&self) -> Option<LimitExceeded> {
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout)
+            && Instant::now() >= deadline
+        {
+            self.exceed(LimitExceeded::Timeout(timeout));
+        }
+        self.recorded()
+    }
+
+    /// The limit recorded so far, without checking the clock.
+    pub(crate) fn recorded(&self) -> Option<LimitExceeded> {
+        *self.exceeded.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record that `limit` was hit, keeping the first limit reported.
+    pub(crate) fn exceed(&self, limit: LimitExceeded) {
+        self.exceeded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert(limit);
+    }
+
+    fn charge(&self, objects: u64) {
+        let total = self.objects_read.fetch_add(objects, Ordering::Relaxed) + objects;
+        if let Some(max) = self.limits.max_objects
+            && total > max
+        {
+            self.exceed(LimitExceeded::MaxObjects(max));
+        }
+    }
+
+    /// Run `f` with this as the thread's current budget, which [`charge`] and [`spent`]
+    /// use. Backends are shared between queries, so this is how the one doing the
+    /// reading finds out whose budget to count them against.
+    pub(crate) fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<Arc<Budget>>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.set(self.0.take());
+            }
+        }
+        let _restore = Restore(CURRENT.replace(Some(self.clone())));
+        f()
+    }
+}
+
+thread_local! {
+    /// The budget of the query running on this thread, if it has one.
+    static CURRENT: RefCell<Option<Arc<Budget>>> = const { RefCell::new(None) };
+}
+
+/// Count `objects` read from the object database against the current query's budget.
+/// Backends call this where they read objects, so cache hits cost nothing.
+pub(crate) fn charge(objects: u64) {
+    CURRENT.with_borrow(|budget| {
+        if let Some(budget) = budget {
+            budget.charge(objects);
+        }
+    });
+}
+
+/// Whether the current query has run into a limit, checking the clock. Long loops
+/// within a single backend call, such as diffs and history walks, check this as they go.
+pub(crate) fn spent() -> bool {
+    CURRENT.with_borrow(|budget| budget.as_ref().is_some_and(|b| b.exceeded().is_some()))
+}
+
+/// Stops reading once a [`Budget`] is spent: every call then returns nothing, so the
+/// query winds down quickly. The objects read are counted by the backends themselves,
+/// through [`charge`].
+#[derive(Debug)]
+pub(crate) struct BudgetedBackend {
+    inner: Arc<dyn Backend>,
+    budget: Arc<Budget>,
+}
+
+impl BudgetedBackend {
+    pub(crate) fn wrap(inner: Arc<dyn Backend>, budget: &Arc<Budget>) -> Arc<dyn Backend> {
+        Arc::new(Self {
+            inner,
+            budget: budget.clone(),
+        })
+    }
+
+    /// Run `read` unless the budget is spent.
+    fn read<T: Default>(&self, read: impl FnOnce() -> T) -> T {
+        if self.budget.exceeded().is_some() {
+            return T::default();
+        }
+        read()
+    }
+}
+
+impl Backend for BudgetedBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn path(&self) -> &str {
+        self.inner.path()
+    }
+
+    fn is_bare(&self) -> bool {
+        self.inner.is_bare()
+    }
+
+    fn head(&self) -> Option<Oid> {
+        self.read(|| self.inner.head())
+    }
+
+    fn find_commit(&self, oid: Oid) -> Option<CommitInfo> {
+        self.read(|| self.inner.find_commit(oid))
+    }
+
+    fn graph_node(&self, oid: Oid) -> Option<GraphNode> {
+        self.read(|| self.inner.graph_node(oid))
+    }
+
+    fn peel_to_commit(&self, oid: Oid) -> Option<Oid> {
+        self.read(|| self.inner.peel_to_commit(oid))
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.read(|| self.inner.branches())
+    }
+
+    fn tags(&self) -> Vec<TagInfo> {
+        self.read(|| self.inner.tags())
+    }
+
+    fn references(&self) -> Vec<ReferenceInfo> {
+        self.read(|| self.inner.references())
+    }
+
+    fn commit_changes(&self, oid: Oid) -> Option<Vec<FileChange>> {
+        self.read(|| self.inner.commit_changes(oid))
+    }
+
+    fn status(&self, include_ignored: bool) -> Vec<StatusEntry> {
+        self.read(|| self.inner.status(include_ignored))
+    }
+
+    fn conflicts(&self) -> Vec<Conflict> {
+        self.read(|| self.inner.conflicts())
+    }
+
+    fn staged_changes(&self) -> Vec<FileChange> {
+        self.read(|| self.inner.staged_changes())
+    }
+
+    fn unstaged_changes(&self) -> Vec<FileChange> {
+        self.read(|| self.inner.unstaged_changes())
+    }
+
+    fn stashes(&self) -> Vec<StashInfo> {
+        self.read(|| self.inner.stashes())
+    }
+
+    fn reflog(&self, reference: &str) -> Vec<ReflogEntry> {
+        self.read(|| self.inner.reflog(reference))
+    }
+
+    fn notes(&self, oid: Oid, notes_ref: Option<&str>) -> Vec<Note> {
+        self.read(|| self.inner.notes(oid, notes_ref))
+    }
+
+    fn submodules(&self) -> Vec<SubmoduleInfo> {
+        self.read(|| self.inner.submodules())
+    }
+
+    /// The submodule's repository draws from the same budget.
+    fn open_submodule(&self, name: &str) -> Option<Arc<dyn Backend>> {
+        self.read(|| self.inner.open_submodule(name))
+            .map(|inner| Self::wrap(inner, &self.budget))
+    }
+
+    fn worktrees(&self) -> Vec<Worktree> {
+        self.read(|| self.inner.worktrees())
+    }
+}
@@ -0,0 +1,387 @@
+//! Reading and writing git's `commit-graph` file.
+//!
+//! The commit-graph stores each commit's parents, commit time and generation number in a
+//! sorted table, so history can be walked without inflating and parsing commit objects.
+//! [`Git2Backend`](crate::backend::Git2Backend) uses it when the repository has one, and
+//! [`write`] creates one in the same format `git commit-graph write --reachable` does.
+//!
+//! See `Documentation/gitformat-commit-graph.txt` in git for the format.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use git2::Oid;
+
+use crate::{backend::GraphNode, trace::trace_span};
+
+const SIGNATURE: &[u8; 4] = b"CGPH";
+const VERSION: u8 = 1;
+/// Hash version 1 is SHA-1.
+const HASH_VERSION: u8 = 1;
+const HASH_LEN: usize = 20;
+
+const CHUNK_FANOUT: u32 = u32::from_be_bytes(*b"OIDF");
+const CHUNK_OID_LOOKUP: u32 = u32::from_be_bytes(*b"OIDL");
+const CHUNK_COMMIT_DATA: u32 = u32::from_be_bytes(*b"CDAT");
+const CHUNK_EXTRA_EDGES: u32 = u32::from_be_bytes(*b"EDGE");
+
+/// Tree id, two parent positions, then generation and commit time packed in 64 bits.
+const COMMIT_DATA_LEN: usize = HASH_LEN + 16;
+const PARENT_NONE: u32 = 0x7000_0000;
+/// Set on the second parent position when it indexes the extra edges list instead, and on
+/// the last entry of each commit's run of extra edges.
+const EDGE_FLAG: u32 = 0x8000_0000;
+const GENERATION_MAX: u32 = 0x3FFF_FFFF;
+
+/// A commit-graph loaded into memory, possibly split into a chain of layers.
+#[derive(Debug)]
+pub(crate) struct CommitGraph {
+    /// Base layer first. Parent positions count through every layer in this order.
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug)]
+struct Layer {
+    data: Vec<u8>,
+    fanout: usize,
+    oid_lookup: usize,
+    commit_data: usize,
+    extra_edges: Option<usize>,
+    len: u32,
+    /// Number of commits in the layers below this one.
+    base: u32,
+}
+
+impl CommitGraph {
+    /// Load the commit-graph of the repository whose object directory is `objects`,
+    /// preferring a single `info/commit-graph` file over a split chain as git does.
+    pub(crate) fn open(objects: &Path) -> Option<Self> {
+        let _span = trace_span!("commit_graph", objects = %objects.display()).entered();
+        let info = objects.join("info");
+        let files: Vec<PathBuf> = if info.join("commit-graph").is_file() {
+            vec![info.join("commit-graph")]
+        } else {
+            let chain =
+                std::fs::read_to_string(info.join("commit-graphs/commit-graph-chain")).ok()?;
+            chain
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|hash| info.join(format!("commit-graphs/graph-{hash}.graph")))
+                .collect()
+        };
+
+        let mut layers = Vec::with_capacity(files.len());
+        let mut base = 0;
+        for file in files {
+            let layer = Layer::parse(std::fs::read(file).ok()?, base)?;
+            base += layer.len;
+            layers.push(layer);
+        }
+        (!layers.is_empty()).then_some(Self { layers })
+    }
+
+    pub(crate) fn node(&self, oid: Oid) -> Option<GraphNode> {
+        let position = self.position(oid)?;
+        let (layer, index) = self.layer(position)?;
+        let entry = layer.commit_data + index as usize * COMMIT_DATA_LEN;
+        let parent1 = layer.u32_at(entry + HASH_LEN)?;
+        let parent2 = layer.u32_at(entry + HASH_LEN + 4)?;
+        let packed = layer.u32_at(entry + HASH_LEN + 8)?;
+        let time_low = layer.u32_at(entry + HASH_LEN + 12)?;
+
+        let mut parent_ids = Vec::new();
+        if parent1 != PARENT_NONE {
+            parent_ids.push(self.oid_at(parent1)?);
+        }
+        if parent2 & EDGE_FLAG != 0 {
+            // An octopus merge: the rest of the parents are in the extra edges list.
+            let edges = layer.extra_edges?;
+            let mut edge = (parent2 & !EDGE_FLAG) as usize;
+            loop {
+                let value = layer.u32_at(edges + edge * 4)?;
+                parent_ids.push(self.oid_at(value & !EDGE_FLAG)?);
+                if value & EDGE_FLAG != 0 {
+                    break;
+                }
+                edge += 1;
+            }
+        } else if parent2 != PARENT_NONE {
+            parent_ids.push(self.oid_at(parent2)?);
+        }
+
+        let generation = packed >> 2;
+        Some(GraphNode {
+            id: oid,
+            parent_ids,
+            time: (i64::from(packed & 0b11) << 32) | i64::from(time_low),
+            // Zero means the file was written without generation numbers.
+            generation: (generation != 0).then_some(generation),
+        })
+    }
+
+    /// Position of `oid` across every layer.
+    fn position(&self, oid: Oid) -> Option<u32> {
+        self.layers
+            .iter()
+            .find_map(|layer| layer.find(oid).map(|index| layer.base + index))
+    }
+
+    fn layer(&self, position: u32) -> Option<(&Layer, u32)> {
+        self.layers
+            .iter()
+            .find(|layer| position >= layer.base && position < layer.base + layer.len)
+            .map(|layer| (layer, position - layer.base))
+    }
+
+    fn oid_at(&self, position: u32) -> Option<Oid> {
+        let (layer, index) = self.layer(position)?;
+        layer.oid(index)
+    }
+}
+
+impl Layer {
+    fn parse(data: Vec<u8>, base: u32) -> Option<Self> {
+        if data.len() < 8
+            || &data[0..4] != SIGNATURE
+            || data[4] != VERSION
+            || data[5] != HASH_VERSION
+        {
+            return None;
+        }
+        let chunk_count = data[6] as usize;
+
+        let mut chunks = HashMap::new();
+        for i in 0..chunk_count {
+            let entry = 8 + i * 12;
+            let id = u32::from_be_bytes(data.get(entry..entry + 4)?.try_into().ok()?);
+            let offset = u64::from_be_bytes(data.get(entry + 4..entry + 12)?.try_into().ok()?);
+            chunks.insert(id, usize::try_from(offset).ok()?);
+        }
+
+        let mut layer = Self {
+            fanout: *chunks.get(&CHUNK_FANOUT)?,
+            oid_lookup: *chunks.get(&CHUNK_OID_LOOKUP)?,
+            commit_data: *chunks.get(&CHUNK_COMMIT_DATA)?,
+            extra_edges: chunks.get(&CHUNK_EXTRA_EDGES).copied(),
+            len: 0,
+            base,
+            data,
+        };
+        layer.len = layer.u32_at(layer.fanout + 255 * 4)?;
+
+        // Check the tables fit, so lookups can't run off the end of the file.
+        let len = layer.len as usize;
+        let fits = |start: usize, size: usize| start.checked_add(size) <= Some(layer.data.len());
+        (fits(layer.oid_lookup, len * HASH_LEN) && fits(layer.commit_data, len * COMMIT_DATA_LEN))
+            .then_some(layer)
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?))
+    }
+
+    fn oid_bytes(&self, index: u32) -> &[u8] {
+        let start = self.oid_lookup + index as usize * HASH_LEN;
+        &self.data[start..start + HASH_LEN]
+    }
+
+    fn oid(&self, index: u32) -> Option<Oid> {
+        (index < self.len)
+            .then(|| Oid::from_bytes(self.oid_bytes(index)).ok())
+            .flatten()
+    }
+
+    /// Index of `oid` in this layer, found with the fanout table and a binary search.
+    fn find(&self, oid: Oid) -> Option<u32> {
+        let first_byte = oid.as_bytes()[0] as usize;
+        let start = match first_byte {
+            0 => 0,
+            _ => self.u32_at(self.fanout + (first_byte - 1) * 4)?,
+        };
+        let end = self.u32_at(self.fanout + first_byte * 4)?.min(self.len);
+
+        let (mut low, mut high) = (start, end);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.oid_bytes(mid).cmp(oid.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+}
+
+/// Write a commit-graph covering every commit reachable from the repository's references
+/// to `objects/info/commit-graph`, replacing any existing one. Returns the number of
+/// commits in the graph.
+///
+/// Shallow repositories are refused, since their history is missing parents the graph
+/// would have to point at.
+pub fn write(repo: &git2::Repository) -> Result<usize, git2::Error> {
+    if repo.is_shallow() {
+        return Err(git2::Error::from_str(
+            "cannot write a commit-graph for a shallow repository",
+        ));
+    }
+
+    let commits = reachable_commits(repo)?;
+    let mut oids: Vec<Oid> = commits.keys().copied().collect();
+    oids.sort();
+    let positions: HashMap<Oid, u32> = oids
+        .iter()
+        .enumerate()
+        .map(|(i, oid)| (*oid, i as u32))
+        .collect();
+    let generations = generations(&commits);
+
+    let mut fanout = Vec::with_capacity(256 * 4);
+    for byte in 0..=255u8 {
+        let count = oids.partition_point(|oid| oid.as_bytes()[0] <= byte) as u32;
+        fanout.extend_from_slice(&count.to_be_bytes());
+    }
+
+    let mut oid_lookup = Vec::with_capacity(oids.len() * HASH_LEN);
+    let mut commit_data = Vec::with_capacity(oids.len() * COMMIT_DATA_LEN);
+    let mut extra_edges = Vec::new();
+    for oid in &oids {
+        oid_lookup.extend_from_slice(oid.as_bytes());
+
+        let commit = &commits[oid];
+        let parent = |i: usize| commit.parents.get(i).map_or(PARENT_NONE, |p| positions[p]);
+        commit_data.extend_from_slice(commit.tree.as_bytes());
+        commit_data.extend_from_slice(&parent(0).to_be_bytes());
+        let second = if commit.parents.len() > 2 {
+            let start = (extra_edges.len() / 4) as u32;
+            let rest = &commit.parents[1..];
+            for (i, parent) in rest.iter().enumerate() {
+                let last = if i == rest.len() - 1 { EDGE_FLAG } else { 0 };
+                extra_edges.extend_from_slice(&(positions[parent] | last).to_be_bytes());
+            }
+            start | EDGE_FLAG
+        } else {
+            parent(1)
+        };
+        commit_data.extend_from_slice(&second.to_be_bytes());
+
+        let time = commit.time.max(0) as u64;
+        let packed = (generations[oid] << 2) | ((time >> 32) as u32 & 0b11);
+        commit_data.extend_from_slice(&packed.to_be_bytes());
+        commit_data.extend_from_slice(&(time as u32).to_be_bytes());
+    }
+
+    let mut chunks = vec![
+        (CHUNK_FANOUT, fanout),
+        (CHUNK_OID_LOOKUP, oid_lookup),
+        (CHUNK_COMMIT_DATA, commit_data),
+    ];
+    if !extra_edges.is_empty() {
+        chunks.push((CHUNK_EXTRA_EDGES, extra_edges));
+    }
+
+    let mut file = Vec::new();
+    file.extend_from_slice(SIGNATURE);
+    file.extend_from_slice(&[VERSION, HASH_VERSION, chunks.len() as u8, 0]);
+    let mut offset = (8 + (chunks.len() + 1) * 12) as u64;
+    for (id, contents) in &chunks {
+        file.extend_from_slice(&id.to_be_bytes());
+        file.extend_from_slice(&offset.to_be_bytes());
+        offset += contents.len() as u64;
+    }
+    // The table ends with a zero id pointing just past the last chunk.
+    file.extend_from_slice(&0u32.to_be_bytes());
+    file.extend_from_slice(&offset.to_be_bytes());
+    for (_, contents) in &chunks {
+        file.extend_from_slice(contents);
+    }
+    let checksum = sha1_smol::Sha1::from(&file).digest().bytes();
+    file.extend_from_slice(&checksum);
+
+    let info = repo.commondir().join("objects/info");
+    let io_error = |e: std::io::Error| git2::Error::from_str(&e.to_string());
+    std::fs::create_dir_all(&info).map_err(io_error)?;
+    // Write next to the target and rename over it, so readers never see a partial file.
+    let temporary = info.join("commit-graph.tmp");
+    std::fs::write(&temporary, &file).map_err(io_error)?;
+    std::fs::rename(&temporary, info.join("commit-graph")).map_err(io_error)?;
+
+    Ok(oids.len())
+}
+
+struct GraphCommit {
+    tree: Oid,
+    parents: Vec<Oid>,
+    time: i64,
+}
+
+/// Every commit reachable from a reference or `HEAD`.
+fn reachable_commits(repo: &git2::Repository) -> Result<HashMap<Oid, GraphCommit>, git2::Error> {
+    let mut pending: Vec<Oid> = Vec::new();
+    if let Ok(head) = repo.head()
+        && let Ok(commit) = head.peel_to_commit()
+    {
+        pending.push(commit.id());
+    }
+    for reference in repo.references()? {
+        // References to trees or blobs, and broken ones, have no history to record.
+        if let Ok(commit) = reference?.peel_to_commit() {
+            pending.push(commit.id());
+        }
+    }
+
+    let mut commits = HashMap::new();
+    while let Some(oid) = pending.pop() {
+        if commits.contains_key(&oid) {
+            continue;
+        }
+        let commit = repo.find_commit(oid)?;
+        let parents: Vec<Oid> = commit.parent_ids().collect();
+        pending.extend(parents.iter().copied());
+        commits.insert(
+            oid,
+            GraphCommit {
+                tree: commit.tree_id(),
+                parents,
+                time: commit.time().seconds(),
+            },
+        );
+    }
+    Ok(commits)
+}
+
+/// Topological levels: 1 for root commits, otherwise one more than the highest parent.
+fn generations(commits: &HashMap<Oid, GraphCommit>) -> HashMap<Oid, u32> {
+    let mut generations: HashMap<Oid, u32> = HashMap::with_capacity(commits.len());
+    for &start in commits.keys() {
+        // Depth-first without recursion, since history can be far deeper than the stack.
+        let mut stack = vec![start];
+        while let Some(&oid) = stack.last() {
+            if generations.contains_key(&oid) {
+                stack.pop();
+                continue;
+            }
+            let parents = &commits[&oid].parents;
+            let missing: Vec<Oid> = parents
+                .iter()
+                .copied()
+                .filter(|parent| !generations.contains_key(parent))
+                .collect();
+            if missing.is_empty() {
+                let generation = parents
+                    .iter()
+                    .map(|parent| generations[parent])
+                    .max()
+                    .map_or(1, |highest| (highest + 1).min(GENERATION_MAX));
+                generations.insert(oid, generation);
+                stack.pop();
+            } else {
+                stack.extend(missing);
+            }
+        }
+    }
+    generations
+}
@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+
+/// Find git repositories below `root`, sorted by path.
+///
+/// A directory counts as a repository if it has a `.git` entry (a directory, or a
+/// file for worktrees and submodules) or if it is itself a bare repository. The
+/// search doesn't descend into repositories it has found, so submodules and
+/// vendored checkouts are not reported separately.
+pub(crate) fn find_repositories(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        if dir.join(".git").exists() || is_bare_repository(&dir) {
+            found.push(dir);
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Skip symlinks so a link back up the tree can't make us loop forever.
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+
+    found.sort();
+    found
+}
+
+fn is_bare_repository(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+}
@@ -1,152 +1,383 @@
+use std::sync::Arc;
+
 use trustfall::provider::{
     AsVertex, ContextIterator, ContextOutcomeIterator, VertexIterator, resolve_neighbors_with,
 };
 
-use crate::{GitAdapter, types, vertex::Vertex};
+use trustfall_core::ir::IRQueryComponent;
+
+use crate::{
+    backend::Backend,
+    types,
+    vertex::Vertex,
+    walk::{CommitWalk, is_ancestor},
+};
+
+fn repository_backend(vertex: &Vertex) -> &Arc<dyn Backend> {
+    vertex
+        .as_repository()
+        .expect("vertex was not a Repository")
+        .backend()
+}
+
+/// Wrap each item produced by `backend` into a vertex that remembers the backend.
+fn backend_vertices<'a, T: 'a>(
+    backend: &Arc<dyn Backend>,
+    items: Vec<T>,
+    vertex: fn(Arc<dyn Backend>, T) -> Vertex,
+) -> VertexIterator<'a, Vertex> {
+    let backend = backend.clone();
+    Box::new(
+        items
+            .into_iter()
+            .map(move |item| vertex(backend.clone(), item)),
+    )
+}
+
+fn vertices<'a, T: 'a>(items: Vec<T>, vertex: fn(T) -> Vertex) -> VertexIterator<'a, Vertex> {
+    Box::new(items.into_iter().map(vertex))
+}
 
-pub(super) fn resolve_repository_edge<'a, V: AsVertex<Vertex<'a>> + 'a>(
-    adapter: &'a GitAdapter<'a>,
+pub(super) fn resolve_repository_edge<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     edge_name: &str,
     parameters: &trustfall_core::ir::EdgeParameters,
-) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex<'a>>> {
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
     match edge_name {
         "commits" => {
-            let limit = parameters.get("limit").map(|v| v.as_usize()).flatten();
-
-            resolve_neighbors_with(contexts, move |_| {
-                match adapter.git2_repo.revwalk().map(|mut revwalk| {
-                    revwalk.push_head().expect("Could not push HEAD");
-
-                    revwalk
-                        .filter_map(|rev| {
-                            rev.ok().and_then(|oid| {
-                                adapter
-                                    .git2_repo
-                                    .find_commit(oid)
-                                    .ok()
-                                    .map(|commit| Vertex::Commit(types::Commit::new(commit)))
-                            })
-                        })
-                        .take(limit.unwrap_or(usize::MAX))
-                }) {
-                    Ok(commits) => Box::new(commits),
-                    Err(_) => Box::new(std::iter::empty()),
+            let limit = parameters.get("limit").and_then(|v| v.as_usize());
+            // `GitAdapter`'s execute methods reject dates that don't parse with
+            // `check_parameters`, but a query run through the `Adapter` impl directly
+            // isn't checked, so there a bad date matches no commits rather than all of them.
+            let since = parameters
+                .get("since")
+                .and_then(|v| v.as_str())
+                .map(parse_date);
+            let all = parameters
+                .get("all")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            resolve_neighbors_with(contexts, move |vertex| {
+                let backend = repository_backend(vertex);
+                let since = match since {
+                    Some(None) => return Box::new(std::iter::empty()),
+                    since => since.flatten(),
+                };
+                let mut tips: Vec<git2::Oid> = backend.head().into_iter().collect();
+                // Like `git log --all`, also start from every branch and tag.
+                if all {
+                    tips.extend(backend.branches().into_iter().filter_map(|b| b.target));
+                    tips.extend(backend.tags().into_iter().map(|t| t.target));
                 }
+                let commits = CommitWalk::new(backend.clone(), tips)
+                    .since(since)
+                    .map(Vertex::Commit)
+                    .take(limit.unwrap_or(usize::MAX));
+                Box::new(commits)
             })
         }
-        "branches" => resolve_neighbors_with(contexts, |_| {
-            let filter = git2::BranchType::Local;
-            match adapter.git2_repo.branches(Some(filter)) {
-                Ok(branches) => {
-                    let branch_vertices = branches.filter_map(|branch_result| {
-                        branch_result
-                            .ok()
-                            .map(|(branch, _)| Vertex::Branch(types::Branch::new(branch)))
-                    });
-
-                    Box::new(branch_vertices)
-                }
-                Err(_) => Box::new(std::iter::empty()),
-            }
+        "commit" => {
+            // A hash that isn't a whole commit id finds nothing, like one of a commit
+            // that isn't in the repository.
+            let oid = parameters
+                .get("hash")
+                .and_then(|v| v.as_str())
+                .and_then(|hash| git2::Oid::from_str(hash).ok());
+            resolve_neighbors_with(contexts, move |vertex| {
+                let backend = repository_backend(vertex);
+                let commit = oid
+                    .and_then(|oid| backend.find_commit(oid))
+                    .map(|info| Vertex::Commit(types::Commit::new(backend.clone(), info)));
+                Box::new(commit.into_iter())
+            })
+        }
+        "branches" => resolve_neighbors_with(contexts, |vertex| {
+            let backend = repository_backend(vertex);
+            backend_vertices(backend, backend.branches(), |backend, info| {
+                Vertex::Branch(types::Branch::new(backend, info))
+            })
         }),
-        "tags" => resolve_neighbors_with(contexts, |_| {
-            match adapter.git2_repo.tag_names(None) {
-                Ok(tag_names) => {
-                    let tags: Vec<_> = tag_names
-                        .iter()
-                        .flatten()
-                        .flatten()
-                        .filter_map(|name| {
-                            let refname = format!("refs/tags/{}", name);
-                            let reference = adapter.git2_repo.find_reference(&refname).ok()?;
-
-                            // Try to peel to a tag object (annotated tag)
-                            let (target_oid, message, tagger_name, tagger_email) =
-                                if let Ok(tag_obj) = reference.peel_to_tag() {
-                                    let msg =
-                                        tag_obj.message().ok().flatten().map(|m| m.to_string());
-                                    let t_name = tag_obj
-                                        .tagger()
-                                        .and_then(|t| t.name().ok().map(|n| n.to_string()));
-                                    let t_email = tag_obj
-                                        .tagger()
-                                        .and_then(|t| t.email().ok().map(|e| e.to_string()));
-                                    let oid = tag_obj
-                                        .target()
-                                        .ok()
-                                        .and_then(|obj| obj.into_commit().ok())
-                                        .map(|c| c.id())?;
-                                    (oid, msg, t_name, t_email)
-                                } else {
-                                    // Lightweight tag — points directly to a commit
-                                    let oid = reference.peel_to_commit().ok()?.id();
-                                    (oid, None, None, None)
-                                };
-
-                            Some(Vertex::Tag(types::Tag::new(
-                                name.to_string(),
-                                target_oid,
-                                message,
-                                tagger_name,
-                                tagger_email,
-                            )))
-                        })
-                        .collect();
-
-                    Box::new(tags.into_iter()) as VertexIterator<'a, Vertex>
-                }
-                Err(_) => Box::new(std::iter::empty()),
-            }
+        "tags" => resolve_neighbors_with(contexts, |vertex| {
+            let backend = repository_backend(vertex);
+            backend_vertices(backend, backend.tags(), |backend, info| {
+                Vertex::Tag(types::Tag::new(backend, info))
+            })
+        }),
+        "status" => {
+            let include_ignored = parameters
+                .get("include_ignored")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            resolve_neighbors_with(contexts, move |vertex| {
+                let backend = repository_backend(vertex);
+                vertices(backend.status(include_ignored), Vertex::StatusEntry)
+            })
+        }
+        "conflicts" => resolve_neighbors_with(contexts, |vertex| {
+            vertices(repository_backend(vertex).conflicts(), Vertex::Conflict)
+        }),
+        "staged_changes" => resolve_neighbors_with(contexts, |vertex| {
+            vertices(
+                repository_backend(vertex).staged_changes(),
+                Vertex::FileChange,
+            )
+        }),
+        "unstaged_changes" => resolve_neighbors_with(contexts, |vertex| {
+            vertices(
+                repository_backend(vertex).unstaged_changes(),
+                Vertex::FileChange,
+            )
+        }),
+        "stashes" => resolve_neighbors_with(contexts, |vertex| {
+            let backend = repository_backend(vertex);
+            backend_vertices(backend, backend.stashes(), |backend, info| {
+                Vertex::Stash(types::Stash::new(backend, info))
+            })
+        }),
+        "references" => resolve_neighbors_with(contexts, |vertex| {
+            let backend = repository_backend(vertex);
+            backend_vertices(backend, backend.references(), |backend, info| {
+                Vertex::Reference(types::Reference::new(backend, info))
+            })
+        }),
+        "submodules" => resolve_neighbors_with(contexts, |vertex| {
+            let backend = repository_backend(vertex);
+            backend_vertices(backend, backend.submodules(), |backend, info| {
+                Vertex::Submodule(types::Submodule::new(backend, info))
+            })
+        }),
+        "worktrees" => resolve_neighbors_with(contexts, |vertex| {
+            vertices(repository_backend(vertex).worktrees(), Vertex::Worktree)
         }),
         _ => unreachable!("resolve_repository_edge {edge_name}"),
     }
 }
 
-pub(super) fn resolve_branch_edge<'a, V: AsVertex<Vertex<'a>> + 'a>(
-    adapter: &'a GitAdapter<'a>,
+pub(super) fn resolve_commit_edge<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     edge_name: &str,
-) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex<'a>>> {
+    parameters: &trustfall_core::ir::EdgeParameters,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "changes" => resolve_neighbors_with(contexts, |vertex| {
+            let commit = vertex.as_commit().expect("vertex was not a Commit");
+            vertices(
+                commit
+                    .backend()
+                    .commit_changes(commit.id())
+                    .unwrap_or_default(),
+                Vertex::FileChange,
+            )
+        }),
+        "notes" => {
+            let notes_ref = parameters.get("ref").and_then(|v| v.as_str()).map(|r| {
+                if r.starts_with("refs/") {
+                    r.to_string()
+                } else {
+                    format!("refs/notes/{r}")
+                }
+            });
+
+            resolve_neighbors_with(contexts, move |vertex| {
+                let commit = vertex.as_commit().expect("vertex was not a Commit");
+                let notes = commit.backend().notes(commit.id(), notes_ref.as_deref());
+                vertices(notes, Vertex::Note)
+            })
+        }
+        "parents" => resolve_neighbors_with(contexts, |vertex| {
+            let commit = vertex.as_commit().expect("vertex was not a Commit");
+            let backend = commit.backend().clone();
+            let parents = commit
+                .parent_ids()
+                .to_vec()
+                .into_iter()
+                .filter_map(move |oid| {
+                    let info = backend.find_commit(oid)?;
+                    Some(Vertex::Commit(types::Commit::new(backend.clone(), info)))
+                });
+            Box::new(parents)
+        }),
+        "containing_branches" => resolve_neighbors_with(contexts, |vertex| {
+            let commit = vertex.as_commit().expect("vertex was not a Commit");
+            let backend = commit.backend();
+            let branches = backend
+                .branches()
+                .into_iter()
+                .filter(|branch| {
+                    branch
+                        .target
+                        .is_some_and(|tip| is_ancestor(backend.as_ref(), commit.id(), tip))
+                })
+                .collect();
+            backend_vertices(backend, branches, |backend, info| {
+                Vertex::Branch(types::Branch::new(backend, info))
+            })
+        }),
+        _ => unreachable!("resolve_commit_edge {edge_name}"),
+    }
+}
+
+/// Reject edge arguments that resolvers can't use, before `query` runs. Resolvers have no
+/// way to report errors, so a bad `since` date would otherwise quietly match nothing.
+///
+/// Queries that don't parse are left for execution to report.
+pub(crate) fn check_parameters(schema: &trustfall::Schema, query: &str) -> anyhow::Result<()> {
+    fn check(component: &IRQueryComponent) -> anyhow::Result<()> {
+        let edges = component
+            .edges
+            .values()
+            .map(|edge| (&edge.edge_name, &edge.parameters));
+        let folds = component
+            .folds
+            .values()
+            .map(|fold| (&fold.edge_name, &fold.parameters));
+        for (edge_name, parameters) in edges.chain(folds) {
+            if &**edge_name == "commits"
+                && let Some(since) = parameters.get("since").and_then(|v| v.as_str())
+                && parse_date(since).is_none()
+            {
+                anyhow::bail!(
+                    "Invalid `since` date '{since}' for `commits`: expected YYYY-MM-DD or an \
+                     RFC 3339 timestamp"
+                );
+            }
+        }
+        component
+            .folds
+            .values()
+            .try_for_each(|fold| check(&fold.component))
+    }
+
+    match trustfall_core::frontend::parse(schema, query) {
+        Ok(query) => check(&query.ir_query.root_component),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Seconds since the Unix epoch for an RFC 3339 timestamp, or for local midnight at the
+/// start of a `YYYY-MM-DD` date.
+fn parse_date(date: &str) -> Option<i64> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(time.timestamp());
+    }
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(0, 0, 0)?
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|time| time.timestamp())
+}
+
+/// The commit `oid` peels to, e.g. through an annotated tag object.
+fn commit_vertex<'a>(backend: &Arc<dyn Backend>, oid: git2::Oid) -> VertexIterator<'a, Vertex> {
+    let commit = backend
+        .peel_to_commit(oid)
+        .and_then(|oid| backend.find_commit(oid))
+        .map(|info| Vertex::Commit(types::Commit::new(backend.clone(), info)));
+    Box::new(commit.into_iter())
+}
+
+pub(super) fn resolve_branch_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
     match edge_name {
         "commit" => resolve_neighbors_with(contexts, |vertex| {
             let branch = vertex.as_branch().expect("vertex was not a Branch");
 
-            match branch.inner().name() {
-                Ok(Some(name)) => adapter
-                    .git2_repo
-                    .find_branch(name, git2::BranchType::Local)
-                    .ok()
-                    .and_then(|git2_branch| git2_branch.get().target())
-                    .and_then(|oid| adapter.git2_repo.find_commit(oid).ok())
-                    .map(|commit| {
-                        Box::new(std::iter::once(Vertex::Commit(types::Commit::new(commit))))
-                            as VertexIterator<'a, Vertex>
-                    })
-                    .unwrap_or_else(|| Box::new(std::iter::empty()) as VertexIterator<'a, Vertex>),
-                _ => Box::new(std::iter::empty()),
+            match branch.target() {
+                Some(oid) => commit_vertex(branch.backend(), oid),
+                None => Box::new(std::iter::empty()),
             }
         }),
         _ => unreachable!("resolve_branch_edge {edge_name}"),
     }
 }
 
-pub(super) fn resolve_tag_edge<'a, V: AsVertex<Vertex<'a>> + 'a>(
-    adapter: &'a GitAdapter<'a>,
+pub(super) fn resolve_tag_edge<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     edge_name: &str,
-) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex<'a>>> {
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
     match edge_name {
         "commit" => resolve_neighbors_with(contexts, |vertex| {
             let tag = vertex.as_tag().expect("vertex was not a Tag");
-            let oid = tag.target_oid();
+            commit_vertex(tag.backend(), tag.target_oid())
+        }),
+        _ => unreachable!("resolve_tag_edge {edge_name}"),
+    }
+}
+
+pub(super) fn resolve_conflict_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let side: fn(&types::Conflict) -> Option<&types::IndexEntry> = match edge_name {
+        "ancestor" => types::Conflict::ancestor,
+        "ours" => types::Conflict::ours,
+        "theirs" => types::Conflict::theirs,
+        _ => unreachable!("resolve_conflict_edge {edge_name}"),
+    };
+
+    resolve_neighbors_with(contexts, move |vertex| {
+        let conflict = vertex.as_conflict().expect("vertex was not a Conflict");
+        let entry = side(conflict).cloned().map(Vertex::IndexEntry);
+        Box::new(entry.into_iter()) as VertexIterator<'a, Vertex>
+    })
+}
 
-            match adapter.git2_repo.find_commit(oid) {
-                Ok(commit) => Box::new(std::iter::once(Vertex::Commit(types::Commit::new(commit))))
-                    as VertexIterator<'a, Vertex>,
-                Err(_) => Box::new(std::iter::empty()),
+pub(super) fn resolve_stash_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "commit" => resolve_neighbors_with(contexts, |vertex| {
+            let stash = vertex.as_stash().expect("vertex was not a Stash");
+            commit_vertex(stash.backend(), stash.commit_oid())
+        }),
+        _ => unreachable!("resolve_stash_edge {edge_name}"),
+    }
+}
+
+pub(super) fn resolve_reference_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "commit" => resolve_neighbors_with(contexts, |vertex| {
+            let reference = vertex.as_reference().expect("vertex was not a Reference");
+
+            // Annotated tags point at a tag object, which `commit_vertex` peels through.
+            match reference.target() {
+                Some(oid) => commit_vertex(reference.backend(), oid),
+                None => Box::new(std::iter::empty()),
             }
         }),
-        _ => unreachable!("resolve_tag_edge {edge_name}"),
+        "reflog" => resolve_neighbors_with(contexts, |vertex| {
+            let reference = vertex.as_reference().expect("vertex was not a Reference");
+            vertices(
+                reference.backend().reflog(reference.name()),
+                Vertex::ReflogEntry,
+            )
+        }),
+        _ => unreachable!("resolve_reference_edge {edge_name}"),
+    }
+}
+
+pub(super) fn resolve_submodule_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "open" => resolve_neighbors_with(contexts, |vertex| {
+            let submodule = vertex.as_submodule().expect("vertex was not a Submodule");
+            let opened = submodule
+                .backend()
+                .open_submodule(submodule.name())
+                .map(|backend| Vertex::Repository(types::Repository::new(backend)));
+            Box::new(opened.into_iter())
+        }),
+        _ => unreachable!("resolve_submodule_edge {edge_name}"),
     }
 }
@@ -1,71 +1,322 @@
-use std::sync::LazyLock;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+    time::Instant,
+};
 
 use trustfall::{
-    Schema,
+    FieldValue, Schema,
     provider::{Adapter, resolve_coercion_using_schema},
 };
 
-use crate::{types::Repository, vertex::Vertex};
+use crate::{
+    backend::{Backend, Git2Backend},
+    budget::{Budget, BudgetedBackend},
+    profile::Profiler,
+    types::Repository,
+    vertex::Vertex,
+};
 
+pub mod backend;
+mod budget;
+pub mod commit_graph;
+mod discovery;
 mod edges;
+mod profile;
 mod properties;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod trace;
 mod types;
 mod vertex;
+mod walk;
 
 static SCHEMA: LazyLock<Schema> =
     LazyLock::new(|| Schema::parse(include_str!("schema.graphql")).expect("schema not valid"));
 
-pub struct GitAdapter<'a> {
-    git2_repo: &'a git2::Repository,
+/// A single query result row, mapping each `@output` name to its value.
+pub type Row = BTreeMap<Arc<str>, FieldValue>;
+
+pub use budget::{LimitExceeded, LimitedResults, Limits, StreamedResults};
+pub use profile::{Profile, ProfileEntry, Resolver};
+
+/// Queries one or more git repositories.
+///
+/// The adapter is `Send + Sync`: each repository is read through a shared [`Backend`],
+/// and vertices hold owned data, so a shared adapter can serve queries on several threads.
+/// Repositories on disk use [`Git2Backend`], which opens its own `git2::Repository`
+/// handles through a per-repository pool.
+#[derive(Clone)]
+pub struct GitAdapter {
+    repo: Arc<dyn Backend>,
+    /// Further repositories registered with [`GitAdapter::add_repository`]; together with
+    /// `repo` they are what `repositories` yields when given no arguments.
+    extra_repos: Vec<Arc<dyn Backend>>,
+    /// Set while running [`GitAdapter::execute_with_limits`], so repositories opened
+    /// during the query draw from the same budget.
+    budget: Option<Arc<Budget>>,
+    /// Set while running [`GitAdapter::execute_profiled`].
+    profiler: Option<Arc<Profiler>>,
 }
 
-impl<'a> GitAdapter<'a> {
-    pub fn new(git2_repo: &'a git2::Repository) -> Self {
-        GitAdapter { git2_repo }
+impl GitAdapter {
+    /// Create an adapter for the same repository as `git2_repo`. The adapter opens its
+    /// own handles on it, so `git2_repo` does not need to outlive the adapter.
+    pub fn new(git2_repo: &git2::Repository) -> Self {
+        Self::with_backend(Git2Backend::new(git2_repo))
+    }
+
+    /// Open the repository at `path` and create an adapter for it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, git2::Error> {
+        Git2Backend::open(path).map(Self::with_backend)
+    }
+
+    /// Find the repository containing `path`, searching parent directories like `git` does,
+    /// and create an adapter for it.
+    pub fn discover(path: impl AsRef<Path>) -> Result<Self, git2::Error> {
+        Git2Backend::discover(path).map(Self::with_backend)
+    }
+
+    /// Create an adapter that reads the repository through `backend`, e.g. a
+    /// [`MemoryBackend`](backend::MemoryBackend) fixture.
+    pub fn with_backend(backend: impl Backend + 'static) -> Self {
+        GitAdapter {
+            repo: Arc::new(backend),
+            extra_repos: Vec::new(),
+            budget: None,
+            profiler: None,
+        }
+    }
+
+    /// Register another repository for multi-repository queries. The repository
+    /// the adapter was created with stays the one returned by the `repository` edge.
+    pub fn add_repository(&mut self, git2_repo: &git2::Repository) {
+        self.add_backend(Git2Backend::new(git2_repo));
+    }
+
+    /// Like [`GitAdapter::add_repository`], for a repository read through `backend`.
+    pub fn add_backend(&mut self, backend: impl Backend + 'static) {
+        self.extra_repos.push(Arc::new(backend));
     }
 
     pub fn schema(&self) -> &Schema {
         &SCHEMA
     }
+
+    /// Execute `query` and collect all of its result rows.
+    pub fn execute(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+    ) -> anyhow::Result<Vec<Row>> {
+        edges::check_parameters(&SCHEMA, query)?;
+        let rows = trustfall::execute_query(&SCHEMA, Arc::new(self), query, variables)?;
+        Ok(rows.collect())
+    }
+
+    /// Execute `query` within `limits`.
+    ///
+    /// Hitting a limit isn't an error: the query stops, and the result holds the rows
+    /// completed until then along with the limit that was hit. Errors are reserved for
+    /// queries that can't run at all, e.g. because they don't parse.
+    pub fn execute_with_limits(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+        limits: &Limits,
+    ) -> anyhow::Result<LimitedResults> {
+        let mut rows = Vec::new();
+        self.execute_streaming(query, variables, limits, |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .map(|streamed| streamed.with_rows(rows))
+    }
+
+    /// Like [`GitAdapter::execute_with_limits`], also counting how often each resolver
+    /// ran and over how many vertices, to see where a query spends its time.
+    pub fn execute_profiled(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+        limits: &Limits,
+    ) -> anyhow::Result<(LimitedResults, Profile)> {
+        let mut rows = Vec::new();
+        self.execute_streaming_profiled(query, variables, limits, |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .map(|(streamed, profile)| (streamed.with_rows(rows), profile))
+    }
+
+    /// Like [`GitAdapter::execute_with_limits`], passing each row to `on_row` as soon as
+    /// it's produced instead of collecting them. An error from `on_row` stops the query
+    /// and is returned.
+    pub fn execute_streaming(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+        limits: &Limits,
+        on_row: impl FnMut(Row) -> anyhow::Result<()>,
+    ) -> anyhow::Result<StreamedResults> {
+        self.execute_instrumented(query, variables, limits, None, on_row)
+    }
+
+    /// [`GitAdapter::execute_streaming`] with the profile of
+    /// [`GitAdapter::execute_profiled`].
+    pub fn execute_streaming_profiled(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+        limits: &Limits,
+        on_row: impl FnMut(Row) -> anyhow::Result<()>,
+    ) -> anyhow::Result<(StreamedResults, Profile)> {
+        let started = Instant::now();
+        let profiler = Arc::new(Profiler::default());
+        let streamed =
+            self.execute_instrumented(query, variables, limits, Some(profiler.clone()), on_row)?;
+        let profile = profiler.finish(streamed.objects_read, started.elapsed());
+        Ok((streamed, profile))
+    }
+
+    fn execute_instrumented(
+        &self,
+        query: &str,
+        variables: BTreeMap<impl Into<Arc<str>>, impl Into<FieldValue>>,
+        limits: &Limits,
+        profiler: Option<Arc<Profiler>>,
+        mut on_row: impl FnMut(Row) -> anyhow::Result<()>,
+    ) -> anyhow::Result<StreamedResults> {
+        edges::check_parameters(&SCHEMA, query)?;
+        let budget = Arc::new(Budget::new(limits.clone()));
+        let adapter = GitAdapter {
+            repo: BudgetedBackend::wrap(self.repo.clone(), &budget),
+            extra_repos: self
+                .extra_repos
+                .iter()
+                .map(|backend| BudgetedBackend::wrap(backend.clone(), &budget))
+                .collect(),
+            budget: Some(budget.clone()),
+            profiler,
+        };
+
+        let _span = trace::info_span!("query", query).entered();
+        // Resolvers run lazily as rows are pulled, so the budget is entered around each
+        // step of the query, but not around `on_row`.
+        let mut results = budget
+            .enter(|| trustfall::execute_query(&SCHEMA, Arc::new(&adapter), query, variables))?;
+        let mut rows = 0;
+        let exceeded = loop {
+            if let Some(exceeded) = budget.exceeded() {
+                break Some(exceeded);
+            }
+            // Once the budget is spent every read comes back empty, which can also end the
+            // query, so check whether that's why there are no more rows.
+            let Some(row) = budget.enter(|| results.next()) else {
+                break budget.recorded();
+            };
+            // A row finished after the budget ran out may be missing data, so drop it.
+            if let Some(exceeded) = budget.exceeded() {
+                break Some(exceeded);
+            }
+            if let Some(max) = limits.max_rows
+                && rows == max
+            {
+                break Some(LimitExceeded::MaxRows(max));
+            }
+            on_row(row)?;
+            rows += 1;
+        };
+
+        Ok(StreamedResults {
+            rows,
+            exceeded,
+            objects_read: budget.objects_read(),
+        })
+    }
+
+    /// `items`, counted towards `type_name.name` when the query is being profiled.
+    fn profiled<'a, T: 'a>(
+        &self,
+        resolver: Resolver,
+        type_name: &str,
+        name: &str,
+        items: Box<dyn Iterator<Item = T> + 'a>,
+    ) -> Box<dyn Iterator<Item = T> + 'a> {
+        match &self.profiler {
+            Some(profiler) => Profiler::count(items, profiler.record(resolver, type_name, name)),
+            None => items,
+        }
+    }
+
+    /// `backend`, drawing from the current query's budget if it has one.
+    fn budgeted(&self, backend: Arc<dyn Backend>) -> Arc<dyn Backend> {
+        match &self.budget {
+            Some(budget) => BudgetedBackend::wrap(backend, budget),
+            None => backend,
+        }
+    }
 }
 
-impl<'a> Adapter<'a> for &'a GitAdapter<'a> {
-    type Vertex = Vertex<'a>;
+impl<'a> Adapter<'a> for &'a GitAdapter {
+    type Vertex = Vertex;
 
     fn resolve_starting_vertices(
         &self,
         edge_name: &std::sync::Arc<str>,
-        _parameters: &trustfall_core::ir::EdgeParameters,
+        parameters: &trustfall_core::ir::EdgeParameters,
         _resolve_info: &trustfall::provider::ResolveInfo,
     ) -> trustfall::provider::VertexIterator<'a, Self::Vertex> {
-        match edge_name.as_ref() {
-            "repository" => {
-                let repo_name = match self.git2_repo.find_remote("origin") {
-                    Ok(remote) => remote.url().ok().and_then(|url| {
-                        url.trim_end_matches(".git")
-                            .rsplit('/')
-                            .next()
-                            .map(|s| s.to_string())
-                    }),
-                    Err(_) => None,
+        let span = trace::debug_span!(
+            "resolve_starting_vertices",
+            edge = %edge_name,
+            parameters = ?parameters,
+        );
+        let _entered = span.clone().entered();
+        let vertices: trustfall::provider::VertexIterator<'a, Self::Vertex> = match edge_name
+            .as_ref()
+        {
+            "repository" => Box::new(std::iter::once(Vertex::Repository(Repository::new(
+                self.repo.clone(),
+            )))),
+            "repositories" => {
+                let paths = parameters
+                    .get("paths")
+                    .and_then(|v| v.as_vec_with(|p| p.as_str().map(PathBuf::from)));
+                let under = parameters.get("under").and_then(|v| v.as_str());
+
+                if paths.is_none() && under.is_none() {
+                    let configured = std::iter::once(&self.repo)
+                        .chain(&self.extra_repos)
+                        .map(|backend| Vertex::Repository(Repository::new(backend.clone())));
+                    Box::new(configured.collect::<Vec<_>>().into_iter())
+                } else {
+                    let discovered = under
+                        .map(|root| discovery::find_repositories(Path::new(root)))
+                        .unwrap_or_default();
+
+                    // Paths that aren't repositories are skipped rather than failing the query.
+                    let repos = paths
+                        .unwrap_or_default()
+                        .into_iter()
+                        .chain(discovered)
+                        .filter_map(|path| Git2Backend::open(path).ok())
+                        .map(|backend| {
+                            Vertex::Repository(Repository::new(self.budgeted(Arc::new(backend))))
+                        });
+                    Box::new(repos)
                 }
-                .unwrap_or_else(|| {
-                    // Fallback to directory name if no remote origin
-                    self.git2_repo
-                        .path()
-                        .parent()
-                        .and_then(|p| p.file_name())
-                        .and_then(|name| name.to_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| "unknown".to_string())
-                });
-
-                Box::new(std::iter::once(Vertex::Repository(Repository::new(
-                    repo_name,
-                ))))
             }
             _ => unreachable!("resolve_starting_vertices {edge_name}"),
-        }
+        };
+        let vertices = trace::instrument(span, vertices);
+        self.profiled(
+            Resolver::StartingVertices,
+            "RootSchemaQuery",
+            edge_name,
+            vertices,
+        )
     }
 
     fn resolve_property<V: trustfall::provider::AsVertex<Self::Vertex> + 'a>(
@@ -75,13 +326,30 @@ impl<'a> Adapter<'a> for &'a GitAdapter<'a> {
         property_name: &std::sync::Arc<str>,
         _resolve_info: &trustfall::provider::ResolveInfo,
     ) -> trustfall::provider::ContextOutcomeIterator<'a, V, trustfall::FieldValue> {
-        match type_name.as_ref() {
+        let span = trace::debug_span!(
+            "resolve_property",
+            type_name = %type_name,
+            property = %property_name,
+        );
+        let contexts = self.profiled(Resolver::Property, type_name, property_name, contexts);
+        let values = span.in_scope(|| match type_name.as_ref() {
             "Repository" => properties::resolve_repository_property(contexts, property_name),
             "Branch" => properties::resolve_branch_property(contexts, property_name),
             "Commit" => properties::resolve_commit_property(contexts, property_name),
             "Tag" => properties::resolve_tag_property(contexts, property_name),
+            "StatusEntry" => properties::resolve_status_entry_property(contexts, property_name),
+            "Conflict" => properties::resolve_conflict_property(contexts, property_name),
+            "IndexEntry" => properties::resolve_index_entry_property(contexts, property_name),
+            "FileChange" => properties::resolve_file_change_property(contexts, property_name),
+            "Stash" => properties::resolve_stash_property(contexts, property_name),
+            "Reference" => properties::resolve_reference_property(contexts, property_name),
+            "ReflogEntry" => properties::resolve_reflog_entry_property(contexts, property_name),
+            "Note" => properties::resolve_note_property(contexts, property_name),
+            "Submodule" => properties::resolve_submodule_property(contexts, property_name),
+            "Worktree" => properties::resolve_worktree_property(contexts, property_name),
             _ => unreachable!("resolve_property {type_name}"),
-        }
+        });
+        trace::instrument(span, values)
     }
 
     fn resolve_neighbors<V: trustfall::provider::AsVertex<Self::Vertex> + 'a>(
@@ -96,12 +364,25 @@ impl<'a> Adapter<'a> for &'a GitAdapter<'a> {
         V,
         trustfall::provider::VertexIterator<'a, Self::Vertex>,
     > {
-        match type_name.as_ref() {
-            "Repository" => edges::resolve_repository_edge(self, contexts, edge_name, parameters),
-            "Branch" => edges::resolve_branch_edge(self, contexts, edge_name),
-            "Tag" => edges::resolve_tag_edge(self, contexts, edge_name),
+        let span = trace::debug_span!(
+            "resolve_neighbors",
+            type_name = %type_name,
+            edge = %edge_name,
+            parameters = ?parameters,
+        );
+        let contexts = self.profiled(Resolver::Neighbors, type_name, edge_name, contexts);
+        let neighbors = span.in_scope(|| match type_name.as_ref() {
+            "Repository" => edges::resolve_repository_edge(contexts, edge_name, parameters),
+            "Commit" => edges::resolve_commit_edge(contexts, edge_name, parameters),
+            "Branch" => edges::resolve_branch_edge(contexts, edge_name),
+            "Tag" => edges::resolve_tag_edge(contexts, edge_name),
+            "Conflict" => edges::resolve_conflict_edge(contexts, edge_name),
+            "Stash" => edges::resolve_stash_edge(contexts, edge_name),
+            "Reference" => edges::resolve_reference_edge(contexts, edge_name),
+            "Submodule" => edges::resolve_submodule_edge(contexts, edge_name),
             _ => unreachable!("resolve_neighbors {type_name}"),
-        }
+        });
+        trace::instrument_neighbors(span, neighbors)
     }
 
     fn resolve_coercion<V: trustfall::provider::AsVertex<Self::Vertex> + 'a>(
@@ -0,0 +1,108 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+/// The adapter entry point a [`ProfileEntry`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Resolver {
+    StartingVertices,
+    Neighbors,
+    Property,
+}
+
+impl std::fmt::Display for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::StartingVertices => "resolve_starting_vertices",
+            Self::Neighbors => "resolve_neighbors",
+            Self::Property => "resolve_property",
+        })
+    }
+}
+
+/// Work done for one edge or property of one vertex type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub resolver: Resolver,
+    /// `RootSchemaQuery` for starting vertices.
+    pub type_name: String,
+    /// The edge or property name.
+    pub name: String,
+    /// Times the adapter method was called.
+    pub calls: u64,
+    /// Vertices passed through those calls: the vertices produced for starting vertices,
+    /// and the vertices being resolved from otherwise. Filters that the adapter or
+    /// Trustfall apply early show up here as fewer vertices reaching later resolvers.
+    pub vertices: u64,
+}
+
+/// What running one query cost, from
+/// [`GitAdapter::execute_profiled`](crate::GitAdapter::execute_profiled).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// One entry per resolver, vertex type and edge or property, in that order.
+    pub entries: Vec<ProfileEntry>,
+    pub objects_read: u64,
+    pub elapsed: Duration,
+}
+
+type Key = (Resolver, String, String);
+
+#[derive(Debug, Default)]
+struct Counters {
+    calls: u64,
+    vertices: Arc<AtomicU64>,
+}
+
+/// Collects counts while a query runs. Vertices are counted as resolvers consume them,
+/// which happens lazily after the call that registered them returns.
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    counters: Mutex<BTreeMap<Key, Counters>>,
+}
+
+impl Profiler {
+    /// Count a call, returning the counter its vertices should be added to.
+    pub(crate) fn record(&self, resolver: Resolver, type_name: &str, name: &str) -> Arc<AtomicU64> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = counters
+            .entry((resolver, type_name.to_string(), name.to_string()))
+            .or_default();
+        entry.calls += 1;
+        entry.vertices.clone()
+    }
+
+    /// Wrap `items` so each one is counted in `counter` as it's consumed.
+    pub(crate) fn count<'a, T: 'a>(
+        items: Box<dyn Iterator<Item = T> + 'a>,
+        counter: Arc<AtomicU64>,
+    ) -> Box<dyn Iterator<Item = T> + 'a> {
+        Box::new(items.inspect(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }))
+    }
+
+    pub(crate) fn finish(&self, objects_read: u64, elapsed: Duration) -> Profile {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = counters
+            .iter()
+            .map(|((resolver, type_name, name), counters)| ProfileEntry {
+                resolver: *resolver,
+                type_name: type_name.clone(),
+                name: name.clone(),
+                calls: counters.calls,
+                vertices: counters.vertices.load(Ordering::Relaxed),
+            })
+            .collect();
+        Profile {
+            entries,
+            objects_read,
+            elapsed,
+        }
+    }
+}
@@ -6,77 +6,66 @@ use trustfall_core::accessor_property;
 
 use crate::vertex::Vertex;
 
-pub(super) fn resolve_repository_property<'a, V: AsVertex<Vertex<'a>> + 'a>(
+/// Render seconds since the Unix epoch as an RFC 3339 timestamp in local time.
+fn format_timestamp(seconds: i64) -> FieldValue {
+    let utc_datetime = chrono::DateTime::from_timestamp(seconds, 0).unwrap();
+    let local_datetime = utc_datetime.with_timezone(&chrono::Local);
+    local_datetime.to_rfc3339().into()
+}
+
+pub(super) fn resolve_repository_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
     match property_name {
         "name" => resolve_property_with(contexts, accessor_property!(as_repository, name)),
+        "path" => resolve_property_with(contexts, accessor_property!(as_repository, path)),
+        "is_bare" => resolve_property_with(contexts, accessor_property!(as_repository, is_bare)),
         _ => unreachable!("resolve_repository_property {property_name}"),
     }
 }
 
-pub(super) fn resolve_branch_property<'a, V: AsVertex<Vertex<'a>> + 'a>(
+pub(super) fn resolve_branch_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
     match property_name {
-        "name" => resolve_property_with(
-            contexts,
-            accessor_property!(as_branch, inner, {
-                match inner.name() {
-                    Ok(Some(name)) => name.into(),
-                    _ => FieldValue::Null,
-                }
-            }),
-        ),
+        "name" => resolve_property_with(contexts, accessor_property!(as_branch, name)),
         _ => unreachable!("resolve_branch_property {property_name}"),
     }
 }
 
-pub(super) fn resolve_commit_property<'a, V: AsVertex<Vertex<'a>> + 'a>(
+pub(super) fn resolve_commit_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
     match property_name {
         "hash" => resolve_property_with(
             contexts,
-            accessor_property!(as_commit, inner, { inner.id().to_string().into() }),
-        ),
-        "message" => resolve_property_with(
-            contexts,
-            accessor_property!(as_commit, inner, { inner.message().ok().into() }),
+            accessor_property!(as_commit, id, { id.to_string().into() }),
         ),
-        "author" => resolve_property_with(
-            contexts,
-            accessor_property!(as_commit, inner, { inner.author().name().ok().into() }),
-        ),
-        "author_email" => resolve_property_with(
+        "message" => resolve_property_with(contexts, accessor_property!(as_commit, message)),
+        "author" => resolve_property_with(contexts, accessor_property!(as_commit, author)),
+        "author_email" => {
+            resolve_property_with(contexts, accessor_property!(as_commit, author_email))
+        }
+        "committer" => resolve_property_with(contexts, accessor_property!(as_commit, committer)),
+        "committer_email" => {
+            resolve_property_with(contexts, accessor_property!(as_commit, committer_email))
+        }
+        "date" => resolve_property_with(
             contexts,
-            accessor_property!(as_commit, inner, { inner.author().email().ok().into() }),
+            accessor_property!(as_commit, time, { format_timestamp(time) }),
         ),
-        "committer" => resolve_property_with(
+        "generation" => resolve_property_with(
             contexts,
-            accessor_property!(as_commit, inner, { inner.committer().name().ok().into() }),
-        ),
-        "committer_email" => resolve_property_with(
-            contexts,
-            accessor_property!(as_commit, inner, { inner.committer().email().ok().into() }),
-        ),
-        "date" => resolve_property_with(
-            contexts,
-            accessor_property!(as_commit, inner, {
-                let time = inner.time();
-                let utc_datetime = chrono::DateTime::from_timestamp(time.seconds(), 0).unwrap();
-                let local_datetime = utc_datetime.with_timezone(&chrono::Local);
-                local_datetime.to_rfc3339().into()
-            }),
+            accessor_property!(as_commit, generation, { generation.map(i64::from).into() }),
         ),
         _ => unreachable!("resolve_commit_property {property_name}"),
     }
 }
 
-pub(super) fn resolve_tag_property<'a, V: AsVertex<Vertex<'a>> + 'a>(
+pub(super) fn resolve_tag_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
@@ -100,3 +89,190 @@ pub(super) fn resolve_tag_property<'a, V: AsVertex<Vertex<'a>> + 'a>(
         _ => unreachable!("resolve_tag_property {property_name}"),
     }
 }
+
+pub(super) fn resolve_status_entry_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "path" => resolve_property_with(contexts, accessor_property!(as_status_entry, path)),
+        "index_status" => {
+            resolve_property_with(contexts, accessor_property!(as_status_entry, index_status))
+        }
+        "worktree_status" => resolve_property_with(
+            contexts,
+            accessor_property!(as_status_entry, worktree_status),
+        ),
+        "is_conflicted" => {
+            resolve_property_with(contexts, accessor_property!(as_status_entry, is_conflicted))
+        }
+        "is_ignored" => {
+            resolve_property_with(contexts, accessor_property!(as_status_entry, is_ignored))
+        }
+        "is_untracked" => {
+            resolve_property_with(contexts, accessor_property!(as_status_entry, is_untracked))
+        }
+        _ => unreachable!("resolve_status_entry_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_conflict_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "path" => resolve_property_with(contexts, accessor_property!(as_conflict, path)),
+        _ => unreachable!("resolve_conflict_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_index_entry_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "path" => resolve_property_with(contexts, accessor_property!(as_index_entry, path)),
+        "oid" => resolve_property_with(
+            contexts,
+            accessor_property!(as_index_entry, oid, { oid.to_string().into() }),
+        ),
+        "mode" => resolve_property_with(
+            contexts,
+            accessor_property!(as_index_entry, mode, { i64::from(mode).into() }),
+        ),
+        _ => unreachable!("resolve_index_entry_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_file_change_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "path" => resolve_property_with(contexts, accessor_property!(as_file_change, path)),
+        "old_path" => resolve_property_with(contexts, accessor_property!(as_file_change, old_path)),
+        "status" => resolve_property_with(contexts, accessor_property!(as_file_change, status)),
+        "additions" => resolve_property_with(
+            contexts,
+            accessor_property!(as_file_change, additions, { (additions as i64).into() }),
+        ),
+        "deletions" => resolve_property_with(
+            contexts,
+            accessor_property!(as_file_change, deletions, { (deletions as i64).into() }),
+        ),
+        "is_binary" => {
+            resolve_property_with(contexts, accessor_property!(as_file_change, is_binary))
+        }
+        _ => unreachable!("resolve_file_change_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_stash_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "index" => resolve_property_with(
+            contexts,
+            accessor_property!(as_stash, index, { (index as i64).into() }),
+        ),
+        "message" => resolve_property_with(contexts, accessor_property!(as_stash, message)),
+        _ => unreachable!("resolve_stash_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_reference_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "name" => resolve_property_with(contexts, accessor_property!(as_reference, name)),
+        "shorthand" => resolve_property_with(contexts, accessor_property!(as_reference, shorthand)),
+        "kind" => resolve_property_with(contexts, accessor_property!(as_reference, kind)),
+        "target" => resolve_property_with(
+            contexts,
+            accessor_property!(as_reference, target, {
+                target.map(|oid| oid.to_string()).into()
+            }),
+        ),
+        _ => unreachable!("resolve_reference_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_reflog_entry_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "old_oid" => resolve_property_with(
+            contexts,
+            accessor_property!(as_reflog_entry, old_oid, { old_oid.to_string().into() }),
+        ),
+        "new_oid" => resolve_property_with(
+            contexts,
+            accessor_property!(as_reflog_entry, new_oid, { new_oid.to_string().into() }),
+        ),
+        "committer" => {
+            resolve_property_with(contexts, accessor_property!(as_reflog_entry, committer))
+        }
+        "committer_email" => resolve_property_with(
+            contexts,
+            accessor_property!(as_reflog_entry, committer_email),
+        ),
+        "message" => resolve_property_with(contexts, accessor_property!(as_reflog_entry, message)),
+        "timestamp" => resolve_property_with(
+            contexts,
+            accessor_property!(as_reflog_entry, timestamp, { format_timestamp(timestamp) }),
+        ),
+        _ => unreachable!("resolve_reflog_entry_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_note_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "ref" => resolve_property_with(contexts, accessor_property!(as_note, notes_ref)),
+        "message" => resolve_property_with(contexts, accessor_property!(as_note, message)),
+        "author" => resolve_property_with(contexts, accessor_property!(as_note, author)),
+        "author_email" => {
+            resolve_property_with(contexts, accessor_property!(as_note, author_email))
+        }
+        _ => unreachable!("resolve_note_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_submodule_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "name" => resolve_property_with(contexts, accessor_property!(as_submodule, name)),
+        "path" => resolve_property_with(contexts, accessor_property!(as_submodule, path)),
+        "url" => resolve_property_with(contexts, accessor_property!(as_submodule, url)),
+        "branch" => resolve_property_with(contexts, accessor_property!(as_submodule, branch)),
+        "head_oid" => resolve_property_with(
+            contexts,
+            accessor_property!(as_submodule, head_oid, {
+                head_oid.map(|oid| oid.to_string()).into()
+            }),
+        ),
+        _ => unreachable!("resolve_submodule_property {property_name}"),
+    }
+}
+
+pub(super) fn resolve_worktree_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    match property_name {
+        "name" => resolve_property_with(contexts, accessor_property!(as_worktree, name)),
+        "path" => resolve_property_with(contexts, accessor_property!(as_worktree, path)),
+        "is_locked" => resolve_property_with(contexts, accessor_property!(as_worktree, is_locked)),
+        "is_prunable" => {
+            resolve_property_with(contexts, accessor_property!(as_worktree, is_prunable))
+        }
+        _ => unreachable!("resolve_worktree_property {property_name}"),
+    }
+}
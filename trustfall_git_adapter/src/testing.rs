@@ -0,0 +1,323 @@
+//! Temporary on-disk repositories for tests, enabled by the `testing` feature.
+//!
+//! [`TestRepo::builder`] declares history the same way
+//! [`MemoryBackend::builder`](crate::backend::MemoryBackend::builder) does, but writes real
+//! objects and references into a fresh repository in a temporary directory. Use it when a
+//! test needs what only a repository on disk has: a work tree, an index, remotes, a
+//! mailmap, or ids that `git` itself would compute.
+//!
+//! ```
+//! use trustfall_git_adapter::testing::TestRepo;
+//!
+//! let repo = TestRepo::builder()
+//!     .commit("init", |c| c.message("Initial commit").file("README.md", "hello\n"))
+//!     .commit("feature", |c| {
+//!         c.message("Add feature")
+//!             .author("Alice", "alice@example.com")
+//!             .file("src/lib.rs", "pub fn feature() {}\n")
+//!     })
+//!     .branch("main", "feature")
+//!     .annotated_tag("v1.0.0", "feature", "First release")
+//!     .remote("origin", "https://example.com/fixture.git")
+//!     .build();
+//!
+//! let rows = repo
+//!     .adapter()
+//!     .execute(
+//!         "{ repository { name @output commits { hash @output } } }",
+//!         std::collections::BTreeMap::<&str, &str>::new(),
+//!     )
+//!     .unwrap();
+//! assert_eq!(rows.len(), 2);
+//! assert_eq!(rows[0]["hash"], repo.oid("feature").to_string().into());
+//! ```
+
+use std::path::Path;
+
+use git2::{IndexEntry, IndexTime, Oid, Repository, Signature, Time, build::CheckoutBuilder};
+use tempfile::TempDir;
+
+use crate::{
+    GitAdapter,
+    backend::{CommitBuilder, History, Labels, Tree},
+};
+
+/// A repository in a temporary directory, removed when this is dropped.
+pub struct TestRepo {
+    dir: TempDir,
+    repo: Repository,
+    labels: Labels,
+}
+
+impl std::fmt::Debug for TestRepo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestRepo")
+            .field("path", &self.dir.path())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TestRepo {
+    pub fn builder() -> TestRepoBuilder {
+        TestRepoBuilder::default()
+    }
+
+    /// The work tree.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    /// An adapter over this repository.
+    pub fn adapter(&self) -> GitAdapter {
+        GitAdapter::new(&self.repo)
+    }
+
+    /// The id of the commit declared as `label`.
+    ///
+    /// # Panics
+    ///
+    /// If no commit was declared with that label.
+    pub fn oid(&self, label: &str) -> Oid {
+        self.labels.get(label)
+    }
+
+    /// The temporary directory and the repository in it. The directory must outlive the
+    /// repository.
+    pub fn into_parts(self) -> (TempDir, Repository) {
+        (self.dir, self.repo)
+    }
+}
+
+/// Declares the contents of a [`TestRepo`].
+///
+/// Commits, branches and tags refer to commits by the label they were declared with, and
+/// commits are declared with the same [`CommitBuilder`] as for
+/// [`MemoryBackendBuilder`](crate::backend::MemoryBackendBuilder).
+///
+/// The repository's `user.name` and `user.email` are set to `Test User` and
+/// `test@example.com`, so tests can go on to commit, stash or tag with the repository's
+/// default signature.
+///
+/// # Panics
+///
+/// [`TestRepoBuilder::build`] panics if a label is used before it is declared, is
+/// declared twice, if `head` names a branch that doesn't exist, or if git rejects the
+/// repository being written, e.g. for an invalid reference name.
+#[derive(Debug, Clone, Default)]
+pub struct TestRepoBuilder {
+    history: History,
+    remotes: Vec<(String, String)>,
+    mailmap: Vec<String>,
+}
+
+impl TestRepoBuilder {
+    /// Add a commit. Unless [`CommitBuilder::parents`] says otherwise, its parent is the
+    /// previously declared commit, and its tree is its first parent's tree with the
+    /// commit's file changes applied.
+    pub fn commit(
+        mut self,
+        label: impl Into<String>,
+        build: impl FnOnce(CommitBuilder) -> CommitBuilder,
+    ) -> Self {
+        self.history.commit(label, build);
+        self
+    }
+
+    pub fn branch(mut self, name: impl Into<String>, commit: impl Into<String>) -> Self {
+        self.history.branch(name, commit);
+        self
+    }
+
+    /// Add a lightweight tag.
+    pub fn tag(mut self, name: impl Into<String>, commit: impl Into<String>) -> Self {
+        self.history.tag(name, commit, None);
+        self
+    }
+
+    /// Add an annotated tag. The tagger is the tagged commit's committer, at the commit's
+    /// time.
+    pub fn annotated_tag(
+        mut self,
+        name: impl Into<String>,
+        commit: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.history.tag(name, commit, Some(message.into()));
+        self
+    }
+
+    /// Point `HEAD` at `branch`. By default it points at the first declared branch.
+    ///
+    /// With no branches declared, commits are made on git's default branch the way
+    /// `git commit` would, and the branch ends at the last commit.
+    pub fn head(mut self, branch: impl Into<String>) -> Self {
+        self.history.head(branch);
+        self
+    }
+
+    pub fn remote(mut self, name: impl Into<String>, url: impl Into<String>) -> Self {
+        self.remotes.push((name.into(), url.into()));
+        self
+    }
+
+    /// Add a mailmap line, e.g. `Jane Doe <jane@example.com> <jdoe@old.example.com>`.
+    ///
+    /// The mailmap is written inside the git directory and named by `mailmap.file`, so it
+    /// shows up in neither the history nor the work tree.
+    pub fn mailmap(mut self, entry: impl Into<String>) -> Self {
+        self.mailmap.push(entry.into());
+        self
+    }
+
+    pub fn build(self) -> TestRepo {
+        self.try_build().expect("failed to write test repository")
+    }
+
+    fn try_build(self) -> Result<TestRepo, git2::Error> {
+        let dir = TempDir::new().map_err(|e| git2::Error::from_str(&e.to_string()))?;
+        let repo = Repository::init(dir.path())?;
+
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Test User")?;
+        config.set_str("user.email", "test@example.com")?;
+
+        let history = &self.history;
+        let (labels, last) = history.write_commits(|commit| {
+            let when = Time::new(commit.time, 0);
+            let author = Signature::new(&commit.author.0, &commit.author.1, &when)?;
+            let committer = Signature::new(&commit.committer.0, &commit.committer.1, &when)?;
+
+            let parents = commit
+                .parent_ids
+                .iter()
+                .map(|oid| repo.find_commit(*oid))
+                .collect::<Result<Vec<_>, _>>()?;
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            // Without declared branches, commits that extend HEAD go through it like
+            // `git commit` does, leaving a reflog entry each.
+            let update_ref = (history.branches.is_empty()
+                && commit.parent_ids.first() == repo.head().ok().and_then(|h| h.target()).as_ref())
+            .then_some("HEAD");
+            repo.commit(
+                update_ref,
+                &author,
+                &committer,
+                &commit.message,
+                &repo.find_tree(write_tree(&repo, commit.tree)?)?,
+                &parents,
+            )
+        })?;
+
+        for (name, commit) in &history.branches {
+            let commit = repo.find_commit(labels.get(commit))?;
+            repo.branch(name, &commit, true)?;
+        }
+
+        match history.head_branch() {
+            Some((head, _)) => repo.set_head(&format!("refs/heads/{head}"))?,
+            // Leave HEAD on the branch `git init` chose, moving it to the last commit.
+            None => {
+                if let Some(oid) = last {
+                    let head = repo.find_reference("HEAD")?;
+                    let name = head.symbolic_target()?.unwrap_or("refs/heads/main");
+                    repo.reference(name, oid, true, "test repository")?;
+                }
+            }
+        }
+        if last.is_some() {
+            repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+        }
+
+        for spec in &history.tags {
+            let commit = repo.find_commit(labels.get(&spec.target))?;
+            match &spec.message {
+                Some(message) => {
+                    repo.tag(
+                        &spec.name,
+                        commit.as_object(),
+                        &commit.committer(),
+                        message,
+                        false,
+                    )?;
+                }
+                None => {
+                    repo.tag_lightweight(&spec.name, commit.as_object(), false)?;
+                }
+            }
+        }
+
+        for (name, url) in &self.remotes {
+            repo.remote(name, url)?;
+        }
+
+        if !self.mailmap.is_empty() {
+            let path = repo.path().join("mailmap");
+            let mut contents = self.mailmap.join("\n");
+            contents.push('\n');
+            std::fs::write(&path, contents).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+            config.set_str("mailmap.file", &path.to_string_lossy())?;
+        }
+
+        drop(config);
+        Ok(TestRepo { dir, repo, labels })
+    }
+}
+
+/// Write `contents` to `path` in the work tree, stage it and commit it on `HEAD` with the
+/// repository's default signature.
+///
+/// # Panics
+///
+/// If the repository is bare, `HEAD` is unborn, or git fails to write the commit.
+pub fn commit_file(repo: &Repository, path: &str, contents: &str, message: &str) -> Oid {
+    let workdir = repo.workdir().expect("bare repositories have no work tree");
+    let file = workdir.join(path);
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent).expect("failed to create directory");
+    }
+    std::fs::write(file, contents).expect("failed to write file");
+
+    let commit = || -> Result<Oid, git2::Error> {
+        let signature = repo.signature()?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new(path))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let parent = repo.head()?.peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &[&parent],
+        )
+    };
+    commit().expect("failed to commit file")
+}
+
+/// Write the blobs of `tree` and the tree objects holding them, returning the root tree.
+fn write_tree(repo: &Repository, tree: &Tree) -> Result<Oid, git2::Error> {
+    let mut index = git2::Index::new()?;
+    for (path, contents) in tree {
+        index.add(&IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: contents.len() as u32,
+            id: repo.blob(contents.as_bytes())?,
+            flags: path.len().min(0xfff) as u16,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        })?;
+    }
+    index.write_tree_to(repo)
+}
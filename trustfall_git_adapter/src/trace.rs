@@ -0,0 +1,121 @@
+//! Spans for the `tracing` feature.
+//!
+//! The macros here take the same arguments as `tracing`'s span macros. Without the
+//! feature they expand to a [`Span`] that does nothing, so call sites don't need their
+//! own `cfg` attributes.
+//!
+//! Queries get `info` spans, resolver calls `debug` spans and git operations `trace`
+//! spans. Resolvers return lazy iterators, so their spans are also entered whenever the
+//! iterators are advanced, see [`instrument`] and [`instrument_neighbors`].
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+#[cfg(feature = "tracing")]
+macro_rules! info_span {
+    ($($args:tt)*) => { tracing::info_span!(target: "trustfall_git_adapter", $($args)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! debug_span {
+    ($($args:tt)*) => { tracing::debug_span!(target: "trustfall_git_adapter", $($args)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($($args:tt)*) => { tracing::trace_span!(target: "trustfall_git_adapter", $($args)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! info_span {
+    ($($args:tt)*) => {
+        $crate::trace::Span
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug_span {
+    ($($args:tt)*) => {
+        $crate::trace::Span
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args:tt)*) => {
+        $crate::trace::Span
+    };
+}
+
+pub(crate) use {debug_span, info_span, trace_span};
+
+/// Stands in for `tracing::Span` when the feature is off.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn entered(self) -> Entered {
+        Entered
+    }
+
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+
+/// `items`, entering `span` while each one is produced.
+pub(crate) fn instrument<'a, T: 'a>(
+    span: Span,
+    items: Box<dyn Iterator<Item = T> + 'a>,
+) -> Box<dyn Iterator<Item = T> + 'a> {
+    #[cfg(feature = "tracing")]
+    {
+        Box::new(Instrumented { span, items })
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = span;
+        items
+    }
+}
+
+/// Like [`instrument`], also entering `span` while each context's neighbors are produced.
+pub(crate) fn instrument_neighbors<'a, C: 'a, N: 'a>(
+    span: Span,
+    items: Box<dyn Iterator<Item = (C, Box<dyn Iterator<Item = N> + 'a>)> + 'a>,
+) -> Box<dyn Iterator<Item = (C, Box<dyn Iterator<Item = N> + 'a>)> + 'a> {
+    #[cfg(feature = "tracing")]
+    {
+        let neighbors_span = span.clone();
+        let items = Box::new(items.map(move |(context, neighbors)| {
+            (context, instrument(neighbors_span.clone(), neighbors))
+        }));
+        instrument(span, items)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = span;
+        items
+    }
+}
+
+#[cfg(feature = "tracing")]
+struct Instrumented<I> {
+    span: Span,
+    items: I,
+}
+
+#[cfg(feature = "tracing")]
+impl<I: Iterator> Iterator for Instrumented<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let _entered = self.span.enter();
+        self.items.next()
+    }
+}
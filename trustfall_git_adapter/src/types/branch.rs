@@ -1,27 +1,39 @@
-use git2::Branch as Git2Branch;
-use std::rc::Rc;
+use std::sync::Arc;
+
+use git2::Oid;
+
+use crate::backend::{Backend, BranchInfo};
 
 #[derive(Clone)]
-pub struct Branch<'a> {
-    branch: Rc<Git2Branch<'a>>,
+pub struct Branch {
+    backend: Arc<dyn Backend>,
+    info: BranchInfo,
 }
 
-impl<'a> Branch<'a> {
-    pub fn new(branch: Git2Branch<'a>) -> Self {
-        Branch {
-            branch: Rc::new(branch),
-        }
+impl Branch {
+    pub fn new(backend: Arc<dyn Backend>, info: BranchInfo) -> Self {
+        Branch { backend, info }
+    }
+
+    /// The repository this branch belongs to.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
-    pub fn inner(&self) -> &Git2Branch<'a> {
-        &self.branch
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// The commit at the tip of the branch.
+    pub fn target(&self) -> Option<Oid> {
+        self.info.target
     }
 }
 
-impl<'a> std::fmt::Debug for Branch<'a> {
+impl std::fmt::Debug for Branch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let name = self.branch.name().unwrap_or(None).unwrap_or("<unnamed>");
-
-        f.debug_struct("Branch").field("name", &name).finish()
+        f.debug_struct("Branch")
+            .field("name", &self.info.name)
+            .finish()
     }
 }
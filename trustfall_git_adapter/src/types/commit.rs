@@ -1,16 +1,68 @@
-use git2::Commit as Git2Commit;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
-pub struct Commit<'a> {
-    commit: Git2Commit<'a>,
+use git2::Oid;
+
+use crate::backend::{Backend, CommitInfo};
+
+#[derive(Clone)]
+pub struct Commit {
+    backend: Arc<dyn Backend>,
+    info: CommitInfo,
 }
 
-impl<'a> Commit<'a> {
-    pub fn new(commit: Git2Commit<'a>) -> Self {
-        Self { commit }
+impl Commit {
+    pub fn new(backend: Arc<dyn Backend>, info: CommitInfo) -> Self {
+        Self { backend, info }
+    }
+
+    /// The repository this commit was read from.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    pub fn id(&self) -> Oid {
+        self.info.id
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.info.message.as_deref()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.info.author.as_deref()
+    }
+
+    pub fn author_email(&self) -> Option<&str> {
+        self.info.author_email.as_deref()
     }
 
-    pub fn inner(&self) -> &Git2Commit<'a> {
-        &self.commit
+    pub fn committer(&self) -> Option<&str> {
+        self.info.committer.as_deref()
+    }
+
+    pub fn committer_email(&self) -> Option<&str> {
+        self.info.committer_email.as_deref()
+    }
+
+    /// Commit time in seconds since the Unix epoch.
+    pub fn time(&self) -> i64 {
+        self.info.time
+    }
+
+    pub fn parent_ids(&self) -> &[Oid] {
+        &self.info.parent_ids
+    }
+
+    /// The commit's generation number, if the repository has a commit-graph containing it.
+    pub fn generation(&self) -> Option<u32> {
+        self.backend
+            .graph_node(self.info.id)
+            .and_then(|node| node.generation)
+    }
+}
+
+impl std::fmt::Debug for Commit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Commit").field("id", &self.info.id).finish()
     }
 }
@@ -0,0 +1,70 @@
+use git2::Oid;
+
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    path: String,
+    oid: Oid,
+    mode: u32,
+}
+
+impl IndexEntry {
+    pub fn new(entry: &git2::IndexEntry) -> Self {
+        Self {
+            path: String::from_utf8_lossy(&entry.path).into_owned(),
+            oid: entry.id,
+            mode: entry.mode,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn oid(&self) -> Oid {
+        self.oid
+    }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+}
+
+/// A path with unmerged index entries, one per side of the merge that touched it.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    ancestor: Option<IndexEntry>,
+    ours: Option<IndexEntry>,
+    theirs: Option<IndexEntry>,
+}
+
+impl Conflict {
+    pub fn new(conflict: &git2::IndexConflict) -> Self {
+        Self {
+            ancestor: conflict.ancestor.as_ref().map(IndexEntry::new),
+            ours: conflict.our.as_ref().map(IndexEntry::new),
+            theirs: conflict.their.as_ref().map(IndexEntry::new),
+        }
+    }
+
+    /// The conflicted path, taken from whichever side still has an entry.
+    pub fn path(&self) -> &str {
+        self.ours
+            .as_ref()
+            .or(self.theirs.as_ref())
+            .or(self.ancestor.as_ref())
+            .map(|entry| entry.path())
+            .unwrap_or_default()
+    }
+
+    pub fn ancestor(&self) -> Option<&IndexEntry> {
+        self.ancestor.as_ref()
+    }
+
+    pub fn ours(&self) -> Option<&IndexEntry> {
+        self.ours.as_ref()
+    }
+
+    pub fn theirs(&self) -> Option<&IndexEntry> {
+        self.theirs.as_ref()
+    }
+}
@@ -0,0 +1,138 @@
+/// A single file touched by a diff, with its line counts.
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    path: String,
+    old_path: Option<String>,
+    status: &'static str,
+    additions: usize,
+    deletions: usize,
+    is_binary: bool,
+}
+
+impl FileChange {
+    /// A change that doesn't involve a rename, for diffs computed outside `git2`.
+    pub(crate) fn new(
+        path: String,
+        status: &'static str,
+        additions: usize,
+        deletions: usize,
+        is_binary: bool,
+    ) -> Self {
+        Self {
+            path,
+            old_path: None,
+            status,
+            additions,
+            deletions,
+            is_binary,
+        }
+    }
+
+    /// The change moved or copied the file from `old_path`.
+    pub(crate) fn with_old_path(mut self, old_path: String) -> Self {
+        self.old_path = Some(old_path);
+        self
+    }
+
+    /// `status` as one of the values [`FileChange::status`] can return.
+    pub(crate) fn known_status(status: &str) -> Option<&'static str> {
+        STATUSES.iter().copied().find(|known| *known == status)
+    }
+
+    /// Build one `FileChange` per delta in `diff`, detecting renames first.
+    pub fn from_diff(diff: &mut git2::Diff<'_>) -> Result<Vec<Self>, git2::Error> {
+        diff.find_similar(None)?;
+
+        let mut changes = Vec::with_capacity(diff.deltas().len());
+        for (idx, delta) in diff.deltas().enumerate() {
+            // Loading each file's blobs can add up to far longer than a query may take.
+            if crate::budget::spent() {
+                return Err(git2::Error::from_str("the query's budget is spent"));
+            }
+            let new_path = delta
+                .new_file()
+                .path()
+                .map(|p| p.to_string_lossy().into_owned());
+            let old_path = delta
+                .old_file()
+                .path()
+                .map(|p| p.to_string_lossy().into_owned());
+            let Some(path) = new_path.clone().or_else(|| old_path.clone()) else {
+                continue;
+            };
+
+            // `Patch::from_diff` yields `None` for binary files, which have no line stats.
+            let (additions, deletions, is_binary) = match git2::Patch::from_diff(diff, idx)? {
+                Some(patch) => {
+                    let (_, additions, deletions) = patch.line_stats()?;
+                    (additions, deletions, false)
+                }
+                None => (0, 0, delta.flags().is_binary()),
+            };
+
+            changes.push(Self {
+                old_path: old_path.filter(|old| *old != path),
+                path,
+                status: delta_status(delta.status()),
+                additions,
+                deletions,
+                is_binary,
+            });
+        }
+        Ok(changes)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn old_path(&self) -> Option<&str> {
+        self.old_path.as_deref()
+    }
+
+    pub fn status(&self) -> &str {
+        self.status
+    }
+
+    pub fn additions(&self) -> usize {
+        self.additions
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.deletions
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.is_binary
+    }
+}
+
+const STATUSES: [&str; 11] = [
+    "added",
+    "deleted",
+    "modified",
+    "renamed",
+    "copied",
+    "typechange",
+    "untracked",
+    "ignored",
+    "conflicted",
+    "unreadable",
+    "unmodified",
+];
+
+fn delta_status(delta: git2::Delta) -> &'static str {
+    match delta {
+        git2::Delta::Added => "added",
+        git2::Delta::Deleted => "deleted",
+        git2::Delta::Modified => "modified",
+        git2::Delta::Renamed => "renamed",
+        git2::Delta::Copied => "copied",
+        git2::Delta::Typechange => "typechange",
+        git2::Delta::Untracked => "untracked",
+        git2::Delta::Ignored => "ignored",
+        git2::Delta::Conflicted => "conflicted",
+        git2::Delta::Unreadable => "unreadable",
+        git2::Delta::Unmodified => "unmodified",
+    }
+}
@@ -1,9 +1,25 @@
 mod branch;
 mod commit;
+mod conflict;
+mod file_change;
+mod note;
+mod reference;
 mod repository;
+mod stash;
+mod status_entry;
+mod submodule;
 mod tag;
+mod worktree;
 
 pub use branch::*;
 pub use commit::*;
+pub use conflict::*;
+pub use file_change::*;
+pub use note::*;
+pub use reference::*;
 pub use repository::*;
+pub use stash::*;
+pub use status_entry::*;
+pub use submodule::*;
 pub use tag::*;
+pub use worktree::*;
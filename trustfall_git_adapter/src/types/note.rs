@@ -0,0 +1,36 @@
+#[derive(Debug, Clone)]
+pub struct Note {
+    notes_ref: String,
+    message: Option<String>,
+    author: Option<String>,
+    author_email: Option<String>,
+}
+
+impl Note {
+    pub fn new(notes_ref: String, note: &git2::Note<'_>) -> Self {
+        let author = note.author();
+        Self {
+            notes_ref,
+            message: note.message().ok().map(|s| s.to_string()),
+            author: author.name().ok().map(|s| s.to_string()),
+            author_email: author.email().ok().map(|s| s.to_string()),
+        }
+    }
+
+    /// The notes reference this note was read from, e.g. `refs/notes/commits`.
+    pub fn notes_ref(&self) -> &str {
+        &self.notes_ref
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn author_email(&self) -> Option<&str> {
+        self.author_email.as_deref()
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use git2::Oid;
+
+use crate::backend::{Backend, ReferenceInfo};
+
+#[derive(Clone)]
+pub struct Reference {
+    backend: Arc<dyn Backend>,
+    info: ReferenceInfo,
+}
+
+impl Reference {
+    pub fn new(backend: Arc<dyn Backend>, info: ReferenceInfo) -> Self {
+        Self { backend, info }
+    }
+
+    /// The repository this reference belongs to.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    pub fn shorthand(&self) -> &str {
+        &self.info.shorthand
+    }
+
+    /// One of `branch`, `remote`, `tag`, `note`, `head` or `other`.
+    pub fn kind(&self) -> &str {
+        &self.info.kind
+    }
+
+    /// The object the reference points to, after following symbolic references.
+    pub fn target(&self) -> Option<Oid> {
+        self.info.target
+    }
+}
+
+impl std::fmt::Debug for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reference")
+            .field("name", &self.info.name)
+            .field("target", &self.info.target)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    old_oid: Oid,
+    new_oid: Oid,
+    committer: Option<String>,
+    committer_email: Option<String>,
+    message: Option<String>,
+    timestamp: i64,
+}
+
+impl ReflogEntry {
+    pub fn new(entry: &git2::ReflogEntry<'_>) -> Self {
+        let committer = entry.committer();
+        Self {
+            old_oid: entry.id_old(),
+            new_oid: entry.id_new(),
+            committer: committer.name().ok().map(|s| s.to_string()),
+            committer_email: committer.email().ok().map(|s| s.to_string()),
+            message: entry.message().ok().flatten().map(|s| s.to_string()),
+            timestamp: committer.when().seconds(),
+        }
+    }
+
+    pub fn old_oid(&self) -> Oid {
+        self.old_oid
+    }
+
+    pub fn new_oid(&self) -> Oid {
+        self.new_oid
+    }
+
+    pub fn committer(&self) -> Option<&str> {
+        self.committer.as_deref()
+    }
+
+    pub fn committer_email(&self) -> Option<&str> {
+        self.committer_email.as_deref()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Seconds since the Unix epoch.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+}
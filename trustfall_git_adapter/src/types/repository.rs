@@ -1,14 +1,40 @@
-#[derive(Debug, Clone)]
+use std::sync::Arc;
+
+use crate::backend::Backend;
+
+#[derive(Clone)]
 pub struct Repository {
-    name: String,
+    backend: Arc<dyn Backend>,
 }
 
 impl Repository {
-    pub fn new(name: String) -> Self {
-        Repository { name }
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Repository { backend }
+    }
+
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     pub fn name(&self) -> &str {
-        &self.name
+        self.backend.name()
+    }
+
+    /// The work tree for regular repositories, or the git directory for bare ones.
+    pub fn path(&self) -> &str {
+        self.backend.path()
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.backend.is_bare()
+    }
+}
+
+impl std::fmt::Debug for Repository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Repository")
+            .field("name", &self.name())
+            .field("path", &self.path())
+            .finish()
     }
 }
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use git2::Oid;
+
+use crate::backend::{Backend, StashInfo};
+
+#[derive(Clone)]
+pub struct Stash {
+    backend: Arc<dyn Backend>,
+    info: StashInfo,
+}
+
+impl Stash {
+    pub fn new(backend: Arc<dyn Backend>, info: StashInfo) -> Self {
+        Self { backend, info }
+    }
+
+    /// The repository this stash belongs to.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    /// Position in the stash list, i.e. the `N` in `stash@{N}`.
+    pub fn index(&self) -> usize {
+        self.info.index
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.info.message.as_deref()
+    }
+
+    pub fn commit_oid(&self) -> Oid {
+        self.info.commit
+    }
+}
+
+impl std::fmt::Debug for Stash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stash")
+            .field("index", &self.info.index)
+            .field("commit_oid", &self.info.commit)
+            .finish()
+    }
+}
@@ -0,0 +1,76 @@
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+    path: String,
+    index_status: Option<&'static str>,
+    worktree_status: Option<&'static str>,
+    is_conflicted: bool,
+    is_ignored: bool,
+    is_untracked: bool,
+}
+
+impl StatusEntry {
+    pub fn new(path: String, status: git2::Status) -> Self {
+        let index_status = if status.is_index_new() {
+            Some("new")
+        } else if status.is_index_modified() {
+            Some("modified")
+        } else if status.is_index_deleted() {
+            Some("deleted")
+        } else if status.is_index_renamed() {
+            Some("renamed")
+        } else if status.is_index_typechange() {
+            Some("typechange")
+        } else {
+            None
+        };
+
+        let worktree_status = if status.is_wt_new() {
+            Some("new")
+        } else if status.is_wt_modified() {
+            Some("modified")
+        } else if status.is_wt_deleted() {
+            Some("deleted")
+        } else if status.is_wt_renamed() {
+            Some("renamed")
+        } else if status.is_wt_typechange() {
+            Some("typechange")
+        } else if status.contains(git2::Status::WT_UNREADABLE) {
+            Some("unreadable")
+        } else {
+            None
+        };
+
+        Self {
+            path,
+            index_status,
+            worktree_status,
+            is_conflicted: status.is_conflicted(),
+            is_ignored: status.is_ignored(),
+            is_untracked: status.is_wt_new(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn index_status(&self) -> Option<&str> {
+        self.index_status
+    }
+
+    pub fn worktree_status(&self) -> Option<&str> {
+        self.worktree_status
+    }
+
+    pub fn is_conflicted(&self) -> bool {
+        self.is_conflicted
+    }
+
+    pub fn is_ignored(&self) -> bool {
+        self.is_ignored
+    }
+
+    pub fn is_untracked(&self) -> bool {
+        self.is_untracked
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use git2::Oid;
+
+use crate::backend::{Backend, SubmoduleInfo};
+
+#[derive(Clone)]
+pub struct Submodule {
+    backend: Arc<dyn Backend>,
+    info: SubmoduleInfo,
+}
+
+impl Submodule {
+    pub fn new(backend: Arc<dyn Backend>, info: SubmoduleInfo) -> Self {
+        Self { backend, info }
+    }
+
+    /// The superproject this submodule is registered in.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    pub fn path(&self) -> &str {
+        &self.info.path
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.info.url.as_deref()
+    }
+
+    pub fn branch(&self) -> Option<&str> {
+        self.info.branch.as_deref()
+    }
+
+    /// The commit recorded for this submodule in the superproject's `HEAD`.
+    pub fn head_oid(&self) -> Option<Oid> {
+        self.info.head_oid
+    }
+}
+
+impl std::fmt::Debug for Submodule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Submodule")
+            .field("name", &self.info.name)
+            .field("path", &self.info.path)
+            .finish()
+    }
+}
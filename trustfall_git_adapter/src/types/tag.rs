@@ -1,48 +1,51 @@
+use std::sync::Arc;
+
 use git2::Oid;
 
-#[derive(Debug, Clone)]
+use crate::backend::{Backend, TagInfo};
+
+#[derive(Clone)]
 pub struct Tag {
-    name: String,
-    target_oid: Oid,
-    message: Option<String>,
-    tagger_name: Option<String>,
-    tagger_email: Option<String>,
+    backend: Arc<dyn Backend>,
+    info: TagInfo,
 }
 
 impl Tag {
-    pub fn new(
-        name: String,
-        target_oid: Oid,
-        message: Option<String>,
-        tagger_name: Option<String>,
-        tagger_email: Option<String>,
-    ) -> Self {
-        Self {
-            name,
-            target_oid,
-            message,
-            tagger_name,
-            tagger_email,
-        }
+    pub fn new(backend: Arc<dyn Backend>, info: TagInfo) -> Self {
+        Self { backend, info }
+    }
+
+    /// The repository this tag belongs to.
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     pub fn name(&self) -> &str {
-        &self.name
+        &self.info.name
     }
 
     pub fn target_oid(&self) -> Oid {
-        self.target_oid
+        self.info.target
     }
 
     pub fn message(&self) -> Option<&str> {
-        self.message.as_deref()
+        self.info.message.as_deref()
     }
 
     pub fn tagger_name(&self) -> Option<&str> {
-        self.tagger_name.as_deref()
+        self.info.tagger_name.as_deref()
     }
 
     pub fn tagger_email(&self) -> Option<&str> {
-        self.tagger_email.as_deref()
+        self.info.tagger_email.as_deref()
+    }
+}
+
+impl std::fmt::Debug for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tag")
+            .field("name", &self.info.name)
+            .field("target_oid", &self.info.target)
+            .finish()
     }
 }
@@ -0,0 +1,42 @@
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    name: String,
+    path: String,
+    is_locked: bool,
+    is_prunable: bool,
+}
+
+impl Worktree {
+    pub fn new(worktree: &git2::Worktree) -> Self {
+        Self {
+            name: worktree
+                .name()
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+                .to_string(),
+            path: worktree.path().to_string_lossy().into_owned(),
+            is_locked: matches!(
+                worktree.is_locked(),
+                Ok(git2::WorktreeLockStatus::Locked(_))
+            ),
+            is_prunable: worktree.is_prunable(None).unwrap_or(false),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    pub fn is_prunable(&self) -> bool {
+        self.is_prunable
+    }
+}
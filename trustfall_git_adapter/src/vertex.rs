@@ -3,9 +3,19 @@ use trustfall_derive::TrustfallEnumVertex;
 use crate::types;
 
 #[derive(Debug, Clone, TrustfallEnumVertex)]
-pub enum Vertex<'a> {
+pub enum Vertex {
     Repository(types::Repository),
-    Commit(types::Commit<'a>),
-    Branch(types::Branch<'a>),
+    Commit(types::Commit),
+    Branch(types::Branch),
     Tag(types::Tag),
+    StatusEntry(types::StatusEntry),
+    Conflict(types::Conflict),
+    IndexEntry(types::IndexEntry),
+    FileChange(types::FileChange),
+    Stash(types::Stash),
+    Reference(types::Reference),
+    ReflogEntry(types::ReflogEntry),
+    Note(types::Note),
+    Submodule(types::Submodule),
+    Worktree(types::Worktree),
 }
@@ -0,0 +1,185 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+};
+
+use std::sync::Arc;
+
+use git2::Oid;
+
+use crate::{
+    backend::{Backend, CommitInfo, GraphNode},
+    budget,
+    trace::{Span, trace_span},
+    types::Commit,
+};
+
+/// Parents and time of `oid`, from the backend's commit-graph when it has the commit.
+pub(crate) fn commit_node(backend: &dyn Backend, oid: Oid) -> Option<GraphNode> {
+    load_node(backend, oid).map(|(node, _)| node)
+}
+
+/// Like [`commit_node`], along with the commit itself when it had to be decoded because
+/// the commit-graph doesn't have it.
+fn load_node(backend: &dyn Backend, oid: Oid) -> Option<(GraphNode, Option<CommitInfo>)> {
+    if let Some(node) = backend.graph_node(oid) {
+        return Some((node, None));
+    }
+    let info = backend.find_commit(oid)?;
+    let node = GraphNode {
+        id: info.id,
+        parent_ids: info.parent_ids.clone(),
+        time: info.time,
+        generation: None,
+    };
+    Some((node, Some(info)))
+}
+
+/// Whether `ancestor` is `descendant` or one of its ancestors.
+///
+/// With generation numbers, the search skips every commit whose generation isn't above
+/// `ancestor`'s, since those can't reach it; without them it may walk all of history.
+pub(crate) fn is_ancestor(backend: &dyn Backend, ancestor: Oid, descendant: Oid) -> bool {
+    if ancestor == descendant {
+        return true;
+    }
+    let Some(target) = commit_node(backend, ancestor) else {
+        return false;
+    };
+
+    let mut pending = vec![descendant];
+    let mut seen = HashSet::from([descendant]);
+    while let Some(oid) = pending.pop() {
+        // Reading the commit-graph is quick, but the search may cover all of history.
+        if budget::spent() {
+            return false;
+        }
+        let Some(node) = commit_node(backend, oid) else {
+            continue;
+        };
+        if let (Some(generation), Some(target)) = (node.generation, target.generation)
+            && generation <= target
+        {
+            continue;
+        }
+        for parent in node.parent_ids {
+            if parent == ancestor {
+                return true;
+            }
+            if seen.insert(parent) {
+                pending.push(parent);
+            }
+        }
+    }
+    false
+}
+
+/// Walks history from a set of tips, newest commit first, like `git log` without options.
+///
+/// The walk follows parents with [`commit_node`], so it only decodes the commits it
+/// yields when the backend has a commit-graph, and works the same for every backend.
+/// Without one, each commit is decoded once, when it's queued.
+pub(crate) struct CommitWalk {
+    backend: Arc<dyn Backend>,
+    queue: BinaryHeap<Pending>,
+    seen: HashSet<Oid>,
+    next_seq: u64,
+    since: Option<i64>,
+    /// Entered whenever the walk reads commits, so their lookups nest under it.
+    span: Span,
+}
+
+struct Pending {
+    node: GraphNode,
+    /// The decoded commit, if reading `node` already needed it.
+    info: Option<CommitInfo>,
+    seq: u64,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    /// Newest first; commits with equal times come out in the order they were queued.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.node
+            .time
+            .cmp(&other.node.time)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl CommitWalk {
+    pub(crate) fn new(backend: Arc<dyn Backend>, tips: impl IntoIterator<Item = Oid>) -> Self {
+        let mut walk = Self {
+            backend,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            next_seq: 0,
+            since: None,
+            span: trace_span!("revwalk"),
+        };
+        let _entered = walk.span.clone().entered();
+        for tip in tips {
+            walk.enqueue(tip);
+        }
+        walk
+    }
+
+    /// Stop at the first commit older than `time`, in seconds since the Unix epoch.
+    pub(crate) fn since(mut self, time: Option<i64>) -> Self {
+        self.since = time;
+        self
+    }
+
+    fn enqueue(&mut self, oid: Oid) {
+        if !self.seen.insert(oid) {
+            return;
+        }
+        if let Some((node, info)) = load_node(self.backend.as_ref(), oid) {
+            self.queue.push(Pending {
+                node,
+                info,
+                seq: self.next_seq,
+            });
+            self.next_seq += 1;
+        }
+    }
+}
+
+impl Iterator for CommitWalk {
+    type Item = Commit;
+
+    fn next(&mut self) -> Option<Commit> {
+        let _entered = self.span.clone().entered();
+        loop {
+            if budget::spent() {
+                self.queue.clear();
+                return None;
+            }
+            let Pending { node, info, .. } = self.queue.pop()?;
+            // Everything still queued is older, so the walk is over.
+            if self.since.is_some_and(|since| node.time < since) {
+                self.queue.clear();
+                return None;
+            }
+            for &parent in &node.parent_ids {
+                self.enqueue(parent);
+            }
+            if let Some(info) = info.or_else(|| self.backend.find_commit(node.id)) {
+                return Some(Commit::new(self.backend.clone(), info));
+            }
+        }
+    }
+}
@@ -1,129 +1,48 @@
 use git2::Repository;
 use std::sync::Arc;
 use tempfile::TempDir;
-use trustfall_git_adapter::GitAdapter;
+use trustfall_git_adapter::{
+    GitAdapter, LimitExceeded, LimitedResults, Limits, Profile, ProfileEntry, Resolver,
+    backend::{
+        Backend, BranchInfo, CachedBackend, CommitInfo, FileChange, Git2Backend, MemoryBackend,
+        ReferenceInfo, TagInfo,
+    },
+    testing::{TestRepo, TestRepoBuilder, commit_file},
+};
 
 fn create_test_repo_with_tags() -> (TempDir, Repository) {
-    let (temp_dir, repo) = create_test_repo_with_multiple_commits();
-
-    let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
-
-    {
-        let head_commit = repo.head().unwrap().peel_to_commit().unwrap();
-
-        // Create a lightweight tag
-        repo.tag_lightweight("v0.1.0", head_commit.as_object(), false)
-            .unwrap();
-
-        // Create an annotated tag
-        repo.tag(
-            "v1.0.0",
-            head_commit.as_object(),
-            &signature,
-            "Release version 1.0.0",
-            false,
-        )
-        .unwrap();
-
-        // Create an annotated tag on the first commit
-        let first_commit_oid = head_commit.parent_id(0).unwrap();
-        let first_commit = repo.find_commit(first_commit_oid).unwrap();
-        repo.tag(
-            "v0.0.1",
-            first_commit.as_object(),
-            &signature,
-            "Initial release",
-            false,
-        )
-        .unwrap();
-    }
-
-    (temp_dir, repo)
+    multiple_commits()
+        .tag("v0.1.0", "second")
+        .annotated_tag("v1.0.0", "second", "Release version 1.0.0")
+        .annotated_tag("v0.0.1", "initial", "Initial release")
+        .build()
+        .into_parts()
 }
 
 fn create_test_repo() -> (TempDir, Repository) {
-    let temp_dir = TempDir::new().unwrap();
-    let repo = Repository::init(temp_dir.path()).unwrap();
-
-    {
-        let mut config = repo.config().unwrap();
-        config.set_str("user.name", "Test User").unwrap();
-        config.set_str("user.email", "test@example.com").unwrap();
-    }
-
-    let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
-    let tree_id = {
-        let mut index = repo.index().unwrap();
-        index.write_tree().unwrap()
-    };
-
-    {
-        let tree = repo.find_tree(tree_id).unwrap();
-        repo.commit(
-            Some("HEAD"),
-            &signature,
-            &signature,
-            "Initial commit",
-            &tree,
-            &[],
-        )
-        .unwrap();
-    }
-
-    (temp_dir, repo)
+    TestRepo::builder()
+        .commit("initial", |c| c.message("Initial commit"))
+        .build()
+        .into_parts()
 }
 
 fn create_test_repo_with_multiple_commits() -> (TempDir, Repository) {
-    let temp_dir = TempDir::new().unwrap();
-    let repo = Repository::init(temp_dir.path()).unwrap();
-
-    {
-        let mut config = repo.config().unwrap();
-        config.set_str("user.name", "Test User").unwrap();
-        config.set_str("user.email", "test@example.com").unwrap();
-    }
-
-    let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
-    let author_signature = git2::Signature::now("Author User", "author@example.com").unwrap();
-
-    // Create initial commit
-    let first_commit = {
-        let tree_id = {
-            let mut index = repo.index().unwrap();
-            index.write_tree().unwrap()
-        };
-        let tree = repo.find_tree(tree_id).unwrap();
-        repo.commit(
-            Some("HEAD"),
-            &author_signature,
-            &signature,
-            "Initial commit",
-            &tree,
-            &[],
-        )
-        .unwrap()
-    };
-
-    // Create a second commit
-    {
-        let tree_id = {
-            let mut index = repo.index().unwrap();
-            index.write_tree().unwrap()
-        };
-        let tree = repo.find_tree(tree_id).unwrap();
-        let first_commit_obj = repo.find_commit(first_commit).unwrap();
-        repo.commit(
-            Some("HEAD"),
-            &author_signature,
-            &signature,
-            "Second commit with more details",
-            &tree,
-            &[&first_commit_obj],
-        )
-        .unwrap();
-    }
+    multiple_commits().build().into_parts()
+}
 
-    (temp_dir, repo)
+/// Two commits authored by `Author User` and committed by `Test User`.
+fn multiple_commits() -> TestRepoBuilder {
+    TestRepo::builder()
+        .commit("initial", |c| {
+            c.message("Initial commit")
+                .author("Author User", "author@example.com")
+                .committer("Test User", "test@example.com")
+        })
+        .commit("second", |c| {
+            c.message("Second commit with more details")
+                .author("Author User", "author@example.com")
+                .committer("Test User", "test@example.com")
+        })
 }
 
 #[test]
@@ -1015,3 +934,2088 @@ fn test_filter_tags_by_name() {
         _ => panic!("Message field should be a non-null string for annotated tag"),
     }
 }
+
+fn run_query(
+    adapter: &GitAdapter,
+    query: &str,
+) -> Vec<std::collections::BTreeMap<Arc<str>, trustfall::FieldValue>> {
+    run_query_with_variables(adapter, query, &[])
+}
+
+fn run_query_with_variables(
+    adapter: &GitAdapter,
+    query: &str,
+    variables: &[(&str, &str)],
+) -> Vec<std::collections::BTreeMap<Arc<str>, trustfall::FieldValue>> {
+    let variables: std::collections::BTreeMap<&str, &str> = variables.iter().copied().collect();
+    trustfall::execute_query(adapter.schema(), Arc::new(adapter), query, variables)
+        .unwrap()
+        .collect()
+}
+
+fn string_field<'r>(
+    row: &'r std::collections::BTreeMap<Arc<str>, trustfall::FieldValue>,
+    name: &str,
+) -> Option<&'r str> {
+    match row.get(name) {
+        Some(trustfall::FieldValue::String(s)) => Some(s.as_ref()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_query_status_entries() {
+    let (temp_dir, repo) = create_test_repo();
+    commit_file(&repo, "tracked.txt", "one\n", "Add tracked file");
+
+    std::fs::write(temp_dir.path().join("tracked.txt"), "two\n").unwrap();
+    std::fs::write(temp_dir.path().join("staged.txt"), "new\n").unwrap();
+    std::fs::write(temp_dir.path().join("untracked.txt"), "?\n").unwrap();
+    {
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+    }
+
+    let adapter = GitAdapter::new(&repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            status {
+                path @output
+                index_status @output
+                worktree_status @output
+                is_untracked @output
+                is_conflicted @output
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 3);
+    let find = |path: &str| {
+        results
+            .iter()
+            .find(|row| string_field(row, "path") == Some(path))
+            .unwrap_or_else(|| panic!("Should find status entry for {path}"))
+    };
+
+    let staged = find("staged.txt");
+    assert_eq!(string_field(staged, "index_status"), Some("new"));
+    assert_eq!(
+        staged.get("worktree_status"),
+        Some(&trustfall::FieldValue::Null)
+    );
+
+    let modified = find("tracked.txt");
+    assert_eq!(string_field(modified, "worktree_status"), Some("modified"));
+    assert_eq!(
+        modified.get("index_status"),
+        Some(&trustfall::FieldValue::Null)
+    );
+
+    let untracked = find("untracked.txt");
+    assert_eq!(
+        untracked.get("is_untracked"),
+        Some(&trustfall::FieldValue::Boolean(true))
+    );
+    assert_eq!(
+        untracked.get("is_conflicted"),
+        Some(&trustfall::FieldValue::Boolean(false))
+    );
+}
+
+#[test]
+fn test_query_status_include_ignored() {
+    let (temp_dir, repo) = create_test_repo();
+    std::fs::write(temp_dir.path().join(".gitignore"), "*.log\n").unwrap();
+    std::fs::write(temp_dir.path().join("debug.log"), "noise\n").unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+    let query = |include_ignored: bool| {
+        format!(
+            r#"{{ repository {{ status(include_ignored: {include_ignored}) {{
+                path @output
+                is_ignored @output
+            }} }} }}"#
+        )
+    };
+
+    let without = run_query(&adapter, &query(false));
+    assert!(
+        without
+            .iter()
+            .all(|row| string_field(row, "path") != Some("debug.log"))
+    );
+
+    let with = run_query(&adapter, &query(true));
+    let ignored = with
+        .iter()
+        .find(|row| string_field(row, "path") == Some("debug.log"))
+        .expect("Ignored file should be listed when include_ignored is set");
+    assert_eq!(
+        ignored.get("is_ignored"),
+        Some(&trustfall::FieldValue::Boolean(true))
+    );
+}
+
+#[test]
+fn test_query_merge_conflicts() {
+    let (_temp_dir, repo) = create_test_repo();
+    let base = commit_file(&repo, "shared.txt", "base\n", "Add shared file");
+    let head_branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+    // Diverge: "feature" and the default branch both rewrite shared.txt.
+    repo.branch("feature", &repo.find_commit(base).unwrap(), false)
+        .unwrap();
+    let ours = commit_file(&repo, "shared.txt", "ours\n", "Our change");
+    repo.set_head("refs/heads/feature").unwrap();
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+        .unwrap();
+    let theirs = commit_file(&repo, "shared.txt", "theirs\n", "Their change");
+    repo.set_head(&format!("refs/heads/{head_branch}")).unwrap();
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+        .unwrap();
+    assert_eq!(repo.head().unwrap().target(), Some(ours));
+
+    let annotated = repo.find_annotated_commit(theirs).unwrap();
+    repo.merge(&[&annotated], None, None).unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            conflicts {
+                path @output
+                ancestor {
+                    ancestor_oid: oid @output
+                }
+                ours {
+                    our_oid: oid @output
+                }
+                theirs {
+                    their_oid: oid @output
+                    mode @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 1);
+    let row = &results[0];
+    assert_eq!(string_field(row, "path"), Some("shared.txt"));
+
+    let blob_oid = |commit: git2::Oid| {
+        repo.find_commit(commit)
+            .unwrap()
+            .tree()
+            .unwrap()
+            .get_name("shared.txt")
+            .unwrap()
+            .id()
+            .to_string()
+    };
+    assert_eq!(
+        string_field(row, "ancestor_oid"),
+        Some(blob_oid(base).as_str())
+    );
+    assert_eq!(string_field(row, "our_oid"), Some(blob_oid(ours).as_str()));
+    assert_eq!(
+        string_field(row, "their_oid"),
+        Some(blob_oid(theirs).as_str())
+    );
+    assert_eq!(
+        row.get("mode"),
+        Some(&trustfall::FieldValue::Int64(0o100644))
+    );
+
+    // The conflicted file also shows up in the status listing.
+    let status = run_query(
+        &adapter,
+        r#"{ repository { status { path @output is_conflicted @output } } }"#,
+    );
+    assert!(status.iter().any(|row| {
+        string_field(row, "path") == Some("shared.txt")
+            && row.get("is_conflicted") == Some(&trustfall::FieldValue::Boolean(true))
+    }));
+}
+
+#[test]
+fn test_query_staged_and_unstaged_changes() {
+    let (temp_dir, repo) = create_test_repo();
+    commit_file(&repo, "app.rs", "fn main() {}\n", "Add app");
+
+    std::fs::create_dir(temp_dir.path().join("migrations")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("migrations/001.sql"),
+        "CREATE TABLE a;\nCREATE TABLE b;\n",
+    )
+    .unwrap();
+    {
+        let mut index = repo.index().unwrap();
+        index
+            .add_path(std::path::Path::new("migrations/001.sql"))
+            .unwrap();
+        index.write().unwrap();
+    }
+    std::fs::write(temp_dir.path().join("app.rs"), "fn main() {\n}\n").unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+
+    let staged = run_query_with_variables(
+        &adapter,
+        r#"
+    {
+        repository {
+            staged_changes {
+                path @output @filter(op: "has_prefix", value: ["$prefix"])
+                status @output
+                additions @output
+                deletions @output
+            }
+        }
+    }
+    "#,
+        &[("prefix", "migrations/")],
+    );
+    assert_eq!(staged.len(), 1);
+    assert_eq!(string_field(&staged[0], "path"), Some("migrations/001.sql"));
+    assert_eq!(string_field(&staged[0], "status"), Some("added"));
+    assert_eq!(
+        staged[0].get("additions"),
+        Some(&trustfall::FieldValue::Int64(2))
+    );
+    assert_eq!(
+        staged[0].get("deletions"),
+        Some(&trustfall::FieldValue::Int64(0))
+    );
+
+    let unstaged = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            unstaged_changes {
+                path @output
+                status @output
+                additions @output
+                deletions @output
+            }
+        }
+    }
+    "#,
+    );
+    assert_eq!(unstaged.len(), 1);
+    assert_eq!(string_field(&unstaged[0], "path"), Some("app.rs"));
+    assert_eq!(string_field(&unstaged[0], "status"), Some("modified"));
+    assert_eq!(
+        unstaged[0].get("additions"),
+        Some(&trustfall::FieldValue::Int64(2))
+    );
+    assert_eq!(
+        unstaged[0].get("deletions"),
+        Some(&trustfall::FieldValue::Int64(1))
+    );
+}
+
+#[test]
+fn test_query_commit_changes() {
+    let (_temp_dir, repo) = create_test_repo();
+    commit_file(&repo, "notes.txt", "a\nb\n", "Add notes");
+    commit_file(&repo, "notes.txt", "a\nc\nd\n", "Edit notes");
+
+    let adapter = GitAdapter::new(&repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            commits(limit: 1) {
+                message @output
+                changes {
+                    path @output
+                    old_path @output
+                    status @output
+                    additions @output
+                    deletions @output
+                    is_binary @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 1);
+    let row = &results[0];
+    assert_eq!(string_field(row, "message"), Some("Edit notes"));
+    assert_eq!(string_field(row, "path"), Some("notes.txt"));
+    assert_eq!(row.get("old_path"), Some(&trustfall::FieldValue::Null));
+    assert_eq!(string_field(row, "status"), Some("modified"));
+    assert_eq!(row.get("additions"), Some(&trustfall::FieldValue::Int64(2)));
+    assert_eq!(row.get("deletions"), Some(&trustfall::FieldValue::Int64(1)));
+    assert_eq!(
+        row.get("is_binary"),
+        Some(&trustfall::FieldValue::Boolean(false))
+    );
+}
+
+#[test]
+fn test_query_stashes() {
+    let (temp_dir, mut repo) = create_test_repo();
+    commit_file(&repo, "work.txt", "v1\n", "Add work");
+
+    let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+    std::fs::write(temp_dir.path().join("work.txt"), "v2\n").unwrap();
+    repo.stash_save(&signature, "first stash", None).unwrap();
+    std::fs::write(temp_dir.path().join("work.txt"), "v3\n").unwrap();
+    let newest = repo.stash_save(&signature, "second stash", None).unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            stashes {
+                index @output
+                message @output
+                commit {
+                    hash @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 2);
+    let newest_row = results
+        .iter()
+        .find(|row| row.get("index") == Some(&trustfall::FieldValue::Int64(0)))
+        .expect("Should find stash@{0}");
+    assert!(
+        string_field(newest_row, "message")
+            .unwrap()
+            .contains("second stash")
+    );
+    assert_eq!(
+        string_field(newest_row, "hash"),
+        Some(newest.to_string().as_str())
+    );
+}
+
+#[test]
+fn test_query_reference_reflog() {
+    let (_temp_dir, repo) = create_test_repo_with_multiple_commits();
+    let adapter = GitAdapter::new(&repo);
+
+    let results = run_query_with_variables(
+        &adapter,
+        r#"
+    {
+        repository {
+            references {
+                name @output @filter(op: "=", value: ["$name"])
+                kind @output
+                reflog {
+                    old_oid @output
+                    new_oid @output
+                    committer @output
+                    message @output
+                    timestamp @output
+                }
+            }
+        }
+    }
+    "#,
+        &[("name", "HEAD")],
+    );
+
+    // One reflog entry per commit, newest first.
+    assert_eq!(results.len(), 2);
+    assert!(
+        results
+            .iter()
+            .all(|row| string_field(row, "kind") == Some("head"))
+    );
+    assert!(
+        results
+            .iter()
+            .all(|row| string_field(row, "committer") == Some("Test User"))
+    );
+
+    let head = repo.head().unwrap().target().unwrap().to_string();
+    assert_eq!(string_field(&results[0], "new_oid"), Some(head.as_str()));
+    assert_eq!(
+        string_field(&results[1], "old_oid"),
+        Some(git2::Oid::ZERO_SHA1.to_string().as_str())
+    );
+    assert!(string_field(&results[0], "timestamp").is_some());
+}
+
+#[test]
+fn test_query_references_kinds() {
+    let (_temp_dir, repo) = create_test_repo_with_tags();
+    let adapter = GitAdapter::new(&repo);
+
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            references {
+                shorthand @output
+                kind @output
+                commit {
+                    hash @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+
+    let kind_of = |shorthand: &str| {
+        results
+            .iter()
+            .find(|row| string_field(row, "shorthand") == Some(shorthand))
+            .and_then(|row| string_field(row, "kind"))
+    };
+    assert_eq!(kind_of("HEAD"), Some("head"));
+    assert_eq!(kind_of("v1.0.0"), Some("tag"));
+    assert!(kind_of("main") == Some("branch") || kind_of("master") == Some("branch"));
+
+    // Annotated tags are peeled through to their commit.
+    let head = repo.head().unwrap().target().unwrap().to_string();
+    let annotated = results
+        .iter()
+        .find(|row| string_field(row, "shorthand") == Some("v1.0.0"))
+        .unwrap();
+    assert_eq!(string_field(annotated, "hash"), Some(head.as_str()));
+}
+
+#[test]
+fn test_query_commit_notes() {
+    let (_temp_dir, repo) = create_test_repo_with_multiple_commits();
+    let signature = git2::Signature::now("CI Bot", "ci@example.com").unwrap();
+    let head = repo.head().unwrap().target().unwrap();
+    repo.note(
+        &signature,
+        &signature,
+        Some("refs/notes/ci"),
+        head,
+        "tests: passed",
+        false,
+    )
+    .unwrap();
+    repo.note(&signature, &signature, None, head, "reviewed", false)
+        .unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+
+    let ci_notes = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            commits {
+                hash @output
+                notes(ref: "ci") {
+                    ref @output
+                    message @output
+                    author @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+    assert_eq!(ci_notes.len(), 1);
+    assert_eq!(
+        string_field(&ci_notes[0], "hash"),
+        Some(head.to_string().as_str())
+    );
+    assert_eq!(string_field(&ci_notes[0], "ref"), Some("refs/notes/ci"));
+    assert_eq!(string_field(&ci_notes[0], "message"), Some("tests: passed"));
+    assert_eq!(string_field(&ci_notes[0], "author"), Some("CI Bot"));
+
+    let all_notes = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            commits {
+                notes {
+                    ref @output
+                }
+            }
+        }
+    }
+    "#,
+    );
+    let mut refs: Vec<_> = all_notes
+        .iter()
+        .filter_map(|row| string_field(row, "ref"))
+        .collect();
+    refs.sort();
+    assert_eq!(refs, vec!["refs/notes/ci", "refs/notes/commits"]);
+}
+
+#[test]
+fn test_query_submodules_and_open() {
+    let (lib_dir, lib_repo) = create_test_repo_with_multiple_commits();
+    let lib_head = lib_repo.head().unwrap().target().unwrap();
+    let (_super_dir, super_repo) = create_test_repo();
+
+    {
+        let url = lib_dir.path().to_str().unwrap();
+        let mut submodule = super_repo
+            .submodule(url, std::path::Path::new("vendor/lib"), true)
+            .unwrap();
+        submodule.clone(None).unwrap();
+        submodule.add_finalize().unwrap();
+
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let tree = super_repo
+            .find_tree(super_repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let parent = super_repo.head().unwrap().peel_to_commit().unwrap();
+        super_repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Add lib submodule",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+    }
+
+    let adapter = GitAdapter::new(&super_repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            submodules {
+                name @output
+                path @output
+                url @output
+                branch @output
+                head_oid @output
+                open {
+                    commits(limit: 1) {
+                        message @output
+                        hash @output
+                    }
+                }
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 1);
+    let row = &results[0];
+    assert_eq!(string_field(row, "name"), Some("vendor/lib"));
+    assert_eq!(string_field(row, "path"), Some("vendor/lib"));
+    assert_eq!(string_field(row, "url"), lib_dir.path().to_str());
+    assert_eq!(row.get("branch"), Some(&trustfall::FieldValue::Null));
+    assert_eq!(
+        string_field(row, "head_oid"),
+        Some(lib_head.to_string().as_str())
+    );
+
+    // `open` walks the submodule's own history, not the superproject's.
+    assert_eq!(
+        string_field(row, "message"),
+        Some("Second commit with more details")
+    );
+    assert_eq!(
+        string_field(row, "hash"),
+        Some(lib_head.to_string().as_str())
+    );
+}
+
+#[test]
+fn test_query_worktrees() {
+    let (_temp_dir, repo) = create_test_repo();
+    let worktree_dir = TempDir::new().unwrap();
+    let worktree_path = worktree_dir.path().join("feature-wt");
+
+    let worktree = repo.worktree("feature-wt", &worktree_path, None).unwrap();
+    worktree.lock(Some("on a USB drive")).unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            worktrees {
+                name @output
+                path @output
+                is_locked @output
+                is_prunable @output
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 1);
+    let row = &results[0];
+    assert_eq!(string_field(row, "name"), Some("feature-wt"));
+    assert!(string_field(row, "path").unwrap().contains("feature-wt"));
+    assert_eq!(
+        row.get("is_locked"),
+        Some(&trustfall::FieldValue::Boolean(true))
+    );
+    assert_eq!(
+        row.get("is_prunable"),
+        Some(&trustfall::FieldValue::Boolean(false))
+    );
+}
+
+#[test]
+fn test_query_repository_path_and_is_bare() {
+    let (temp_dir, repo) = create_test_repo();
+    let adapter = GitAdapter::new(&repo);
+
+    let results = run_query(
+        &adapter,
+        r#"{ repository { name @output path @output is_bare @output } }"#,
+    );
+
+    assert_eq!(results.len(), 1);
+    let expected_path = temp_dir.path().canonicalize().unwrap();
+    let path = std::path::Path::new(string_field(&results[0], "path").unwrap());
+    assert_eq!(path.canonicalize().unwrap(), expected_path);
+    assert!(!string_field(&results[0], "path").unwrap().ends_with('/'));
+    assert_eq!(
+        results[0].get("is_bare"),
+        Some(&trustfall::FieldValue::Boolean(false))
+    );
+}
+
+#[test]
+fn test_query_repositories_registered_with_adapter() {
+    let (_first_dir, first) = create_test_repo();
+    let (_second_dir, second) = create_test_repo_with_multiple_commits();
+    let mut adapter = GitAdapter::new(&first);
+    adapter.add_repository(&second);
+
+    let results = run_query(
+        &adapter,
+        r#"
+    {
+        repositories {
+            path @output
+            commits @fold {
+                count: hash @output
+            }
+        }
+    }
+    "#,
+    );
+
+    assert_eq!(results.len(), 2);
+    let counts: Vec<_> = results
+        .iter()
+        .map(|row| match row.get("count") {
+            Some(trustfall::FieldValue::List(hashes)) => hashes.len(),
+            _ => panic!("count should be a list"),
+        })
+        .collect();
+    assert_eq!(counts, vec![1, 2]);
+
+    // `repository` still refers to the first repository only.
+    assert_eq!(
+        run_query(&adapter, "{ repository { name @output } }").len(),
+        1
+    );
+}
+
+#[test]
+fn test_query_repositories_by_path_and_discovery() {
+    let root = TempDir::new().unwrap();
+    for name in ["service-a", "nested/service-b"] {
+        let path = root.path().join(name);
+        std::fs::create_dir_all(&path).unwrap();
+        Repository::init(&path).unwrap();
+    }
+    Repository::init_bare(root.path().join("mirror.git")).unwrap();
+    std::fs::create_dir(root.path().join("not-a-repo")).unwrap();
+
+    let (_temp_dir, repo) = create_test_repo();
+    let adapter = GitAdapter::new(&repo);
+
+    let query = format!(
+        r#"{{ repositories(under: "{}") {{ path @output is_bare @output }} }}"#,
+        root.path().display()
+    );
+    let results = run_query(&adapter, &query);
+    let mut found: Vec<_> = results
+        .iter()
+        .map(|row| {
+            let path = std::path::Path::new(string_field(row, "path").unwrap());
+            let relative = path.strip_prefix(root.path()).unwrap();
+            (
+                relative.to_string_lossy().into_owned(),
+                row.get("is_bare") == Some(&trustfall::FieldValue::Boolean(true)),
+            )
+        })
+        .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        vec![
+            ("mirror.git".to_string(), true),
+            ("nested/service-b".to_string(), false),
+            ("service-a".to_string(), false),
+        ]
+    );
+
+    let query = format!(
+        r#"{{ repositories(paths: ["{}", "{}"]) {{ path @output }} }}"#,
+        root.path().join("service-a").display(),
+        root.path().join("not-a-repo").display()
+    );
+    assert_eq!(run_query(&adapter, &query).len(), 1);
+}
+
+/// A service-style struct that owns its adapter.
+struct RepoService {
+    adapter: GitAdapter,
+}
+
+fn open_service(path: &std::path::Path) -> RepoService {
+    RepoService {
+        adapter: GitAdapter::open(path).unwrap(),
+    }
+}
+
+#[test]
+fn test_owned_adapter_open_and_execute() {
+    let (temp_dir, _repo) = create_test_repo_with_multiple_commits();
+    let service = open_service(temp_dir.path());
+
+    let rows = service
+        .adapter
+        .execute(
+            r#"
+        {
+            repository {
+                commits {
+                    message @output @filter(op: "regex", value: ["$pattern"])
+                }
+            }
+        }
+        "#,
+            std::collections::BTreeMap::from([("pattern", "^Second")]),
+        )
+        .unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        string_field(&rows[0], "message"),
+        Some("Second commit with more details")
+    );
+}
+
+#[test]
+fn test_owned_adapter_discover_from_subdirectory() {
+    let (temp_dir, _repo) = create_test_repo();
+    let subdir = temp_dir.path().join("nested/dir");
+    std::fs::create_dir_all(&subdir).unwrap();
+
+    assert!(GitAdapter::open(&subdir).is_err());
+    let adapter = GitAdapter::discover(&subdir).unwrap();
+
+    let rows = adapter
+        .execute(
+            "{ repository { path @output } }",
+            std::collections::BTreeMap::<&str, &str>::new(),
+        )
+        .unwrap();
+    let path = std::path::Path::new(string_field(&rows[0], "path").unwrap());
+    assert_eq!(
+        path.canonicalize().unwrap(),
+        temp_dir.path().canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_execute_reports_invalid_query() {
+    let (temp_dir, _repo) = create_test_repo();
+    let adapter = GitAdapter::open(temp_dir.path()).unwrap();
+
+    let result = adapter.execute(
+        "{ repository { no_such_field @output } }",
+        std::collections::BTreeMap::<&str, &str>::new(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_adapter_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<GitAdapter>();
+}
+
+#[test]
+fn test_adapter_shared_across_threads() {
+    let (temp_dir, repo) = create_test_repo();
+    commit_file(&repo, "a.txt", "a\n", "Add a");
+    commit_file(&repo, "b.txt", "b\n", "Add b");
+    let adapter = Arc::new(GitAdapter::open(temp_dir.path()).unwrap());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let adapter = Arc::clone(&adapter);
+            std::thread::spawn(move || {
+                adapter
+                    .execute(
+                        "{ repository { commits { hash @output changes @fold { path @output } } } }",
+                        std::collections::BTreeMap::<&str, &str>::new(),
+                    )
+                    .unwrap()
+                    .len()
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), 3);
+    }
+}
+
+#[test]
+fn test_adapter_outlives_repository_handle() {
+    let (temp_dir, repo) = create_test_repo();
+    let adapter = GitAdapter::new(&repo);
+    drop(repo);
+
+    let rows = run_query(&adapter, "{ repository { commits { hash @output } } }");
+    assert_eq!(rows.len(), 1);
+    drop(temp_dir);
+}
+
+#[test]
+fn test_query_commits_merge_history_newest_first() {
+    let (_temp_dir, repo) = create_test_repo();
+    let signature = |seconds| {
+        git2::Signature::new(
+            "Test User",
+            "test@example.com",
+            &git2::Time::new(seconds, 0),
+        )
+        .unwrap()
+    };
+    let commit_at = |seconds: i64, message: &str, parents: &[&git2::Commit]| {
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        let sig = signature(seconds);
+        repo.commit(None, &sig, &sig, message, &tree, parents)
+            .unwrap()
+    };
+
+    let root = repo.head().unwrap().peel_to_commit().unwrap();
+    let side = commit_at(2_000_000_000, "side", &[&root]);
+    let main = commit_at(2_000_000_100, "main", &[&root]);
+    let (side, main) = (
+        repo.find_commit(side).unwrap(),
+        repo.find_commit(main).unwrap(),
+    );
+    let merge = commit_at(2_000_000_200, "merge", &[&main, &side]);
+    repo.reference("refs/heads/master", merge, true, "merge")
+        .unwrap();
+    repo.set_head("refs/heads/master").unwrap();
+
+    let adapter = GitAdapter::new(&repo);
+    let rows = run_query(&adapter, "{ repository { commits { message @output } } }");
+    let messages: Vec<_> = rows
+        .iter()
+        .map(|row| string_field(row, "message").unwrap())
+        .collect();
+    assert_eq!(messages, vec!["merge", "main", "side", "Initial commit"]);
+}
+
+fn memory_fixture() -> MemoryBackend {
+    MemoryBackend::builder("fixture")
+        .commit("init", |c| {
+            c.message("Initial commit")
+                .file("README.md", "hello\n")
+                .file("old.txt", "bye\n")
+        })
+        .commit("feature", |c| {
+            c.message("Add feature")
+                .author("Alice", "alice@example.com")
+                .file("README.md", "hello\nworld\n")
+                .file("src/lib.rs", "pub fn feature() {}\n")
+                .remove("old.txt")
+        })
+        .commit("fix", |c| {
+            c.message("Fix on main")
+                .parents(["init"])
+                .file("fix.txt", "fixed\n")
+        })
+        .commit("merge", |c| {
+            c.message("Merge feature")
+                .parents(["fix", "feature"])
+                .file("src/lib.rs", "pub fn feature() {}\n")
+        })
+        .branch("main", "merge")
+        .branch("feature", "feature")
+        .tag("v0.1.0", "init")
+        .annotated_tag("v1.0.0", "merge", "Release 1.0.0")
+        .head("main")
+        .build()
+}
+
+#[test]
+fn test_memory_backend_history() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+
+    let rows = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            name @output
+            path @output
+            commits {
+                message @output
+                author @output
+            }
+        }
+    }
+    "#,
+    );
+
+    let messages: Vec<_> = rows
+        .iter()
+        .map(|row| string_field(row, "message").unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Merge feature",
+            "Fix on main",
+            "Add feature",
+            "Initial commit"
+        ]
+    );
+    assert_eq!(string_field(&rows[0], "name"), Some("fixture"));
+    assert_eq!(string_field(&rows[0], "path"), Some("memory:fixture"));
+    assert_eq!(string_field(&rows[2], "author"), Some("Alice"));
+    assert_eq!(string_field(&rows[3], "author"), Some("Test User"));
+}
+
+#[test]
+fn test_memory_backend_commit_changes() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+
+    let rows = run_query_with_variables(
+        &adapter,
+        r#"
+    {
+        repository {
+            commits {
+                message @filter(op: "=", value: ["$message"])
+                changes {
+                    path @output
+                    status @output
+                    additions @output
+                    deletions @output
+                }
+            }
+        }
+    }
+    "#,
+        &[("message", "Add feature")],
+    );
+
+    let changes: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            (
+                string_field(row, "path").unwrap(),
+                string_field(row, "status").unwrap(),
+                row["additions"].clone(),
+                row["deletions"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            ("README.md", "modified", 1.into(), 0.into()),
+            ("old.txt", "deleted", 0.into(), 1.into()),
+            ("src/lib.rs", "added", 1.into(), 0.into()),
+        ]
+    );
+}
+
+#[test]
+fn test_memory_backend_branches_tags_and_references() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+
+    let rows = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            tags {
+                name @output
+                message @output
+                tagger_name @output
+                commit {
+                    message @output(name: "commit_message")
+                }
+            }
+        }
+    }
+    "#,
+    );
+    assert_eq!(rows.len(), 2);
+    assert_eq!(string_field(&rows[0], "name"), Some("v0.1.0"));
+    assert_eq!(string_field(&rows[0], "message"), None);
+    assert_eq!(string_field(&rows[1], "message"), Some("Release 1.0.0"));
+    assert_eq!(string_field(&rows[1], "tagger_name"), Some("Test User"));
+    assert_eq!(
+        string_field(&rows[1], "commit_message"),
+        Some("Merge feature")
+    );
+
+    let rows = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            references {
+                name @output
+                kind @output
+                commit {
+                    message @output(name: "commit_message")
+                }
+            }
+        }
+    }
+    "#,
+    );
+    let references: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            (
+                string_field(row, "name").unwrap(),
+                string_field(row, "kind").unwrap(),
+                string_field(row, "commit_message").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        references,
+        vec![
+            ("HEAD", "head", "Merge feature"),
+            ("refs/heads/feature", "branch", "Add feature"),
+            ("refs/heads/main", "branch", "Merge feature"),
+            ("refs/tags/v0.1.0", "tag", "Initial commit"),
+            ("refs/tags/v1.0.0", "tag", "Merge feature"),
+        ]
+    );
+
+    // Working-tree state doesn't exist in memory.
+    assert!(run_query(&adapter, "{ repository { status { path @output } } }").is_empty());
+}
+
+#[test]
+fn test_memory_backend_ids_are_deterministic() {
+    let hashes = || {
+        let adapter = GitAdapter::with_backend(memory_fixture());
+        run_query(&adapter, "{ repository { commits { hash @output } } }")
+    };
+    assert_eq!(hashes(), hashes());
+}
+
+#[test]
+fn test_memory_backend_alongside_git2_repository() {
+    let (_temp_dir, repo) = create_test_repo();
+    let mut adapter = GitAdapter::new(&repo);
+    adapter.add_backend(memory_fixture());
+
+    let rows = run_query(
+        &adapter,
+        "{ repositories { name @output is_bare @output } }",
+    );
+    assert_eq!(rows.len(), 2);
+    assert_eq!(string_field(&rows[1], "name"), Some("fixture"));
+    assert_eq!(
+        rows[0].get("is_bare"),
+        Some(&trustfall::FieldValue::Boolean(false))
+    );
+}
+
+#[test]
+fn test_test_repo_builder_history() {
+    let repo = TestRepo::builder()
+        .commit("init", |c| {
+            c.message("Initial commit").file("README.md", "hello\n")
+        })
+        .commit("feature", |c| {
+            c.message("Add feature")
+                .author("Alice", "alice@example.com")
+                .time(1_800_000_000)
+                .file("src/lib.rs", "pub fn feature() {}\n")
+        })
+        .commit("fix", |c| {
+            c.message("Fix on main")
+                .parents(["init"])
+                .file("README.md", "hello\nworld\n")
+        })
+        .commit("merge", |c| {
+            c.message("Merge feature")
+                .parents(["fix", "feature"])
+                .file("src/lib.rs", "pub fn feature() {}\n")
+        })
+        .branch("main", "merge")
+        .branch("feature", "feature")
+        .tag("v0.1.0", "init")
+        .annotated_tag("v1.0.0", "merge", "Release 1.0.0")
+        .head("main")
+        .build();
+    let adapter = repo.adapter();
+
+    let rows = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            commits {
+                hash @output
+                message @output
+                author @output
+                date @output
+            }
+        }
+    }
+    "#,
+    );
+    let messages: Vec<_> = rows
+        .iter()
+        .map(|row| string_field(row, "message").unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Merge feature",
+            "Fix on main",
+            "Add feature",
+            "Initial commit"
+        ]
+    );
+    assert_eq!(
+        string_field(&rows[2], "hash"),
+        Some(repo.oid("feature").to_string().as_str())
+    );
+    assert_eq!(string_field(&rows[2], "author"), Some("Alice"));
+    assert!(
+        string_field(&rows[2], "date")
+            .unwrap()
+            .starts_with("2027-01-15")
+    );
+
+    let merge = repo.repo().find_commit(repo.oid("merge")).unwrap();
+    assert_eq!(
+        merge.parent_ids().collect::<Vec<_>>(),
+        vec![repo.oid("fix"), repo.oid("feature")]
+    );
+
+    let rows = run_query(
+        &adapter,
+        r#"
+    {
+        repository {
+            tags {
+                name @output
+                message @output
+                tagger_name @output
+            }
+        }
+    }
+    "#,
+    );
+    assert_eq!(rows.len(), 2);
+    assert_eq!(string_field(&rows[0], "name"), Some("v0.1.0"));
+    assert_eq!(string_field(&rows[0], "message"), None);
+    assert_eq!(string_field(&rows[1], "message"), Some("Release 1.0.0"));
+    assert_eq!(string_field(&rows[1], "tagger_name"), Some("Test User"));
+
+    // The work tree is checked out at HEAD.
+    assert_eq!(
+        std::fs::read_to_string(repo.path().join("README.md")).unwrap(),
+        "hello\nworld\n"
+    );
+    assert!(run_query(&adapter, "{ repository { status { path @output } } }").is_empty());
+    assert_eq!(repo.repo().head().unwrap().shorthand().unwrap(), "main");
+}
+
+#[test]
+fn test_test_repo_builder_remote_and_mailmap() {
+    let repo = TestRepo::builder()
+        .commit("init", |c| {
+            c.message("Initial commit")
+                .author("jdoe", "jdoe@old.example.com")
+        })
+        .remote("origin", "https://example.com/team/project.git")
+        .mailmap("Jane Doe <jane@example.com> <jdoe@old.example.com>")
+        .build();
+
+    let rows = run_query(&repo.adapter(), "{ repository { name @output } }");
+    assert_eq!(string_field(&rows[0], "name"), Some("project"));
+
+    let commit = repo.repo().find_commit(repo.oid("init")).unwrap();
+    let author = repo
+        .repo()
+        .mailmap()
+        .unwrap()
+        .resolve_signature(&commit.author())
+        .unwrap();
+    assert_eq!(author.name(), Ok("Jane Doe"));
+    assert_eq!(author.email(), Ok("jane@example.com"));
+
+    // The mailmap lives in the git directory, so the work tree stays clean.
+    assert!(repo.repo().statuses(None).unwrap().is_empty());
+}
+
+fn cache_fixture() -> TestRepo {
+    TestRepo::builder()
+        .commit("init", |c| {
+            c.message("Initial commit")
+                .file("README.md", "hello\n")
+                .file("old.txt", "bye\n")
+        })
+        .commit("feature", |c| {
+            c.message("Add feature")
+                .file("README.md", "hello\nworld\n")
+                .remove("old.txt")
+        })
+        .build()
+}
+
+const CACHE_QUERY: &str = r#"
+{
+    repository {
+        commits {
+            hash @output
+            message @output
+            author @output
+            date @output
+            changes @fold {
+                path @output
+                status @output
+                additions @output
+                deletions @output
+            }
+        }
+    }
+}
+"#;
+
+#[test]
+fn test_cached_backend_matches_uncached() {
+    let repo = cache_fixture();
+    let cached = GitAdapter::with_backend(Git2Backend::new(repo.repo()).with_cache());
+
+    let expected = run_query(&repo.adapter(), CACHE_QUERY);
+    assert_eq!(run_query(&cached, CACHE_QUERY), expected);
+    // Answered from the cache the second time.
+    assert_eq!(run_query(&cached, CACHE_QUERY), expected);
+
+    let cache_dir = repo.repo().path().join("git-seek");
+    let lines = |name: &str| {
+        std::fs::read_to_string(cache_dir.join(name))
+            .unwrap()
+            .lines()
+            .count()
+    };
+    assert_eq!(lines("commits-v2.jsonl"), 2);
+    assert_eq!(lines("changes-v2.jsonl"), 2);
+}
+
+#[test]
+fn test_cached_backend_persists_and_grows() {
+    let repo = cache_fixture();
+    let cached = || GitAdapter::with_backend(Git2Backend::new(repo.repo()).with_cache());
+    run_query(&cached(), CACHE_QUERY);
+
+    // A fresh backend reads what the first one wrote instead of decoding the commit.
+    let commits_file = repo.repo().path().join("git-seek/commits-v2.jsonl");
+    let contents = std::fs::read_to_string(&commits_file).unwrap();
+    std::fs::write(
+        &commits_file,
+        contents.replace("Initial commit", "Remembered commit"),
+    )
+    .unwrap();
+    let rows = run_query(&cached(), CACHE_QUERY);
+    assert_eq!(string_field(&rows[1], "message"), Some("Remembered commit"));
+
+    // New commits are appended as queries reach them.
+    commit_file(repo.repo(), "new.txt", "new\n", "Third commit");
+    let rows = run_query(&cached(), CACHE_QUERY);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(string_field(&rows[0], "message"), Some("Third commit"));
+    let contents = std::fs::read_to_string(&commits_file).unwrap();
+    assert_eq!(contents.lines().count(), 3);
+}
+
+#[test]
+fn test_cached_backend_drops_duplicate_and_broken_lines() {
+    let repo = cache_fixture();
+    let cached = || GitAdapter::with_backend(Git2Backend::new(repo.repo()).with_cache());
+    let expected = run_query(&cached(), CACHE_QUERY);
+
+    // Lines appended twice, say by processes racing on the same commits, a line that
+    // doesn't parse and one cut short by an interrupted write.
+    let commits_file = repo.repo().path().join("git-seek/commits-v2.jsonl");
+    let contents = std::fs::read_to_string(&commits_file).unwrap();
+    let head = repo.repo().head().unwrap().target().unwrap();
+    std::fs::write(
+        &commits_file,
+        format!("{contents}{contents}not a line\n{head}\t{{\"id\":"),
+    )
+    .unwrap();
+
+    assert_eq!(run_query(&cached(), CACHE_QUERY), expected);
+    assert_eq!(std::fs::read_to_string(&commits_file).unwrap(), contents);
+
+    // A line that's found but doesn't read back is computed again, and the new line
+    // replaces it the next time the file is loaded.
+    std::fs::write(&commits_file, format!("{contents}{head}\tnot json\n")).unwrap();
+    assert_eq!(run_query(&cached(), CACHE_QUERY), expected);
+    assert_eq!(run_query(&cached(), CACHE_QUERY), expected);
+    let contents = std::fs::read_to_string(&commits_file).unwrap();
+    assert_eq!(contents.lines().count(), 2);
+    assert!(!contents.contains("not json"));
+}
+
+/// Counts the commits it decodes, and fails every diff until `fail_diffs` runs out.
+#[derive(Debug)]
+struct ProbedBackend {
+    inner: MemoryBackend,
+    fail_diffs: std::sync::atomic::AtomicUsize,
+    commits_decoded: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl ProbedBackend {
+    fn new(inner: MemoryBackend, fail_diffs: usize) -> Self {
+        Self {
+            inner,
+            fail_diffs: fail_diffs.into(),
+            commits_decoded: Arc::default(),
+        }
+    }
+}
+
+impl Backend for ProbedBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn path(&self) -> &str {
+        self.inner.path()
+    }
+
+    fn is_bare(&self) -> bool {
+        self.inner.is_bare()
+    }
+
+    fn head(&self) -> Option<git2::Oid> {
+        self.inner.head()
+    }
+
+    fn find_commit(&self, oid: git2::Oid) -> Option<CommitInfo> {
+        self.commits_decoded
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.inner.find_commit(oid)
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.inner.branches()
+    }
+
+    fn tags(&self) -> Vec<TagInfo> {
+        self.inner.tags()
+    }
+
+    fn references(&self) -> Vec<ReferenceInfo> {
+        self.inner.references()
+    }
+
+    fn commit_changes(&self, oid: git2::Oid) -> Option<Vec<FileChange>> {
+        use std::sync::atomic::Ordering;
+        let failing = self
+            .fail_diffs
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            None
+        } else {
+            self.inner.commit_changes(oid)
+        }
+    }
+}
+
+#[test]
+fn test_cached_backend_skips_failed_diffs() {
+    let dir = TempDir::new().unwrap();
+    let flaky = |fail_diffs| {
+        let inner = MemoryBackend::builder("fixture")
+            .commit("init", |c| c.message("Initial commit").file("a.txt", "a\n"))
+            .branch("main", "init")
+            .head("main")
+            .build();
+        ProbedBackend::new(inner, fail_diffs)
+    };
+    let cached = CachedBackend::new(flaky(1), dir.path());
+    let head = cached.head().unwrap();
+
+    // The failure isn't remembered, so the next call diffs again.
+    assert!(cached.commit_changes(head).is_none());
+    let changes = cached.commit_changes(head).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].path(), "a.txt");
+    let lines = std::fs::read_to_string(dir.path().join("changes-v2.jsonl")).unwrap();
+    assert_eq!(lines.lines().count(), 1);
+
+    // Only the successful diff was written, so a later process reads it back even if
+    // diffing fails there.
+    let cached = CachedBackend::new(flaky(usize::MAX), dir.path());
+    assert_eq!(cached.commit_changes(head).unwrap().len(), 1);
+}
+
+fn octopus_fixture() -> TestRepo {
+    TestRepo::builder()
+        .commit("root", |c| c.message("Root").file("a.txt", "a\n"))
+        .commit("left", |c| c.message("Left").file("left.txt", "l\n"))
+        .commit("middle", |c| {
+            c.message("Middle")
+                .parents(["root"])
+                .file("middle.txt", "m\n")
+        })
+        .commit("right", |c| {
+            c.message("Right")
+                .parents(["middle"])
+                .file("right.txt", "r\n")
+        })
+        .commit("merge", |c| {
+            c.message("Octopus").parents(["left", "middle", "right"])
+        })
+        .branch("main", "merge")
+        .branch("topic", "right")
+        .build()
+}
+
+const GRAPH_QUERY: &str = r#"
+{
+    repository {
+        commits {
+            message @output
+            generation @output
+            parents @fold {
+                parents: message @output
+            }
+        }
+    }
+}
+"#;
+
+#[test]
+fn test_commit_graph_write_and_read() {
+    let repo = octopus_fixture();
+    let without_graph = run_query(&repo.adapter(), GRAPH_QUERY);
+    assert!(
+        without_graph
+            .iter()
+            .all(|row| row["generation"] == trustfall::FieldValue::Null)
+    );
+
+    let written = trustfall_git_adapter::commit_graph::write(repo.repo()).unwrap();
+    assert_eq!(written, 5);
+    assert!(repo.path().join(".git/objects/info/commit-graph").is_file());
+
+    let with_graph = run_query(&repo.adapter(), GRAPH_QUERY);
+    let summary: Vec<(String, i64, Vec<String>)> = with_graph
+        .iter()
+        .map(|row| {
+            let trustfall::FieldValue::Int64(generation) = row["generation"] else {
+                panic!("generation should be set: {row:?}");
+            };
+            let trustfall::FieldValue::List(parents) = &row["parents"] else {
+                panic!("parents should be a list");
+            };
+            let parents = parents
+                .iter()
+                .map(|parent| parent.as_str().unwrap().to_string())
+                .collect();
+            (
+                string_field(row, "message").unwrap().to_string(),
+                generation,
+                parents,
+            )
+        })
+        .collect();
+    let owned = |parents: &[&str]| parents.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "Octopus".to_string(),
+                4,
+                owned(&["Left", "Middle", "Right"])
+            ),
+            ("Right".to_string(), 3, owned(&["Middle"])),
+            ("Middle".to_string(), 2, owned(&["Root"])),
+            ("Left".to_string(), 2, owned(&["Root"])),
+            ("Root".to_string(), 1, owned(&[])),
+        ]
+    );
+
+    // The graph only changes how history is read, not what queries return.
+    let strip_generation = |rows: Vec<trustfall_git_adapter::Row>| {
+        rows.into_iter()
+            .map(|mut row| {
+                row.remove("generation");
+                row
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        strip_generation(with_graph),
+        strip_generation(without_graph)
+    );
+}
+
+#[test]
+fn test_commit_graph_misses_fall_back_to_objects() {
+    let repo = octopus_fixture();
+    trustfall_git_adapter::commit_graph::write(repo.repo()).unwrap();
+    commit_file(repo.repo(), "new.txt", "new\n", "After the graph");
+
+    let rows = run_query(&repo.adapter(), GRAPH_QUERY);
+    assert_eq!(rows.len(), 6);
+    assert_eq!(string_field(&rows[0], "message"), Some("After the graph"));
+    assert_eq!(rows[0]["generation"], trustfall::FieldValue::Null);
+    assert_eq!(rows[1]["generation"], trustfall::FieldValue::Int64(4));
+}
+
+#[test]
+fn test_commits_since() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let messages = |since: &str| {
+        run_query(
+            &adapter,
+            &format!(r#"{{ repository {{ commits(since: "{since}") {{ message @output }} }} }}"#),
+        )
+        .iter()
+        .map(|row| string_field(row, "message").unwrap().to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // The fixture's commits are a minute apart, starting at 2023-11-14T22:13:20Z.
+    assert_eq!(
+        messages("2023-11-14T22:14:20Z"),
+        vec!["Merge feature", "Fix on main", "Add feature"]
+    );
+    assert_eq!(messages("2023-11-14T23:14:20+01:00").len(), 3);
+    assert_eq!(messages("2000-01-01").len(), 4);
+    assert!(messages("2030-01-01").is_empty());
+}
+
+#[test]
+fn test_walk_decodes_each_commit_once() {
+    let backend = ProbedBackend::new(memory_fixture(), 0);
+    let decoded = backend.commits_decoded.clone();
+    let adapter = GitAdapter::with_backend(backend);
+
+    let rows = run_query(&adapter, "{ repository { commits { message @output } } }");
+    assert_eq!(rows.len(), 4);
+    assert_eq!(decoded.load(std::sync::atomic::Ordering::Relaxed), 4);
+}
+
+#[test]
+fn test_commits_since_invalid_date() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    for query in [
+        r#"{ repository { commits(since: "last tuesday") { message @output } } }"#,
+        r#"{ repository { name @output commits(since: "last tuesday") @fold { message @output } } }"#,
+    ] {
+        let error = adapter
+            .execute(query, std::collections::BTreeMap::<&str, &str>::new())
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("'last tuesday'"),
+            "unexpected error: {error}"
+        );
+        let error = adapter
+            .execute_with_limits(
+                query,
+                std::collections::BTreeMap::<&str, &str>::new(),
+                &Limits::default(),
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("'last tuesday'"));
+        let error = adapter
+            .execute_streaming(
+                query,
+                std::collections::BTreeMap::<&str, &str>::new(),
+                &Limits::default(),
+                |_| Ok(()),
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("'last tuesday'"));
+    }
+
+    // Run through the `Adapter` impl directly there's no check, so no commits match.
+    let rows: Vec<_> = trustfall::execute_query(
+        adapter.schema(),
+        Arc::new(&adapter),
+        r#"{ repository { commits(since: "last tuesday") { message @output } } }"#,
+        std::collections::BTreeMap::<&str, &str>::new(),
+    )
+    .unwrap()
+    .collect();
+    assert!(rows.is_empty());
+}
+
+#[test]
+fn test_commit_by_hash() {
+    let repo = TestRepo::builder()
+        .commit("init", |c| c.message("Initial commit"))
+        .commit("main", |c| c.message("On main"))
+        .commit("dangling", |c| c.message("On no branch").parents(["init"]))
+        .branch("main", "main")
+        .build();
+    let adapter = repo.adapter();
+    let lookup = |hash: &str| {
+        run_query(
+            &adapter,
+            &format!(
+                r#"{{ repository {{ commit(hash: "{hash}") {{
+                    message @output
+                    parents @fold {{ parent: hash @output }}
+                }} }} }}"#
+            ),
+        )
+    };
+
+    // Found without walking history, even when nothing refers to it.
+    let rows = lookup(&repo.oid("dangling").to_string());
+    assert_eq!(rows.len(), 1);
+    assert_eq!(string_field(&rows[0], "message"), Some("On no branch"));
+    assert_eq!(
+        rows[0]["parent"],
+        trustfall::FieldValue::List(vec![repo.oid("init").to_string().into()].into())
+    );
+
+    assert!(lookup(&"0".repeat(40)).is_empty());
+    assert!(lookup("not a hash").is_empty());
+}
+
+#[test]
+fn test_commits_all() {
+    let backend = MemoryBackend::builder("fixture")
+        .commit("init", |c| c.message("Initial commit"))
+        .commit("main", |c| c.message("On main"))
+        .commit("side", |c| c.message("On side").parents(["init"]))
+        .commit("tagged", |c| c.message("Tagged").parents(["init"]))
+        .branch("main", "main")
+        .branch("side", "side")
+        .tag("detached", "tagged")
+        .head("main")
+        .build();
+    let adapter = GitAdapter::with_backend(backend);
+    let messages = |arguments: &str| {
+        run_query(
+            &adapter,
+            &format!(r#"{{ repository {{ commits{arguments} {{ message @output }} }} }}"#),
+        )
+        .iter()
+        .map(|row| string_field(row, "message").unwrap().to_string())
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(messages(""), vec!["On main", "Initial commit"]);
+    assert_eq!(messages("(all: false)"), messages(""));
+    let mut all = messages("(all: true)");
+    assert_eq!(all.len(), 4);
+    assert_eq!(all.pop().as_deref(), Some("Initial commit"));
+    assert_eq!(messages("(all: true, limit: 1)").len(), 1);
+}
+
+#[test]
+fn test_commit_containing_branches() {
+    let check = |adapter: &GitAdapter| {
+        let rows = run_query(
+            adapter,
+            r#"
+        {
+            repository {
+                commits {
+                    message @output
+                    containing_branches @fold {
+                        branches: name @output
+                    }
+                }
+            }
+        }
+        "#,
+        );
+        rows.iter()
+            .map(|row| {
+                let trustfall::FieldValue::List(branches) = &row["branches"] else {
+                    panic!("branches should be a list");
+                };
+                let branches: Vec<&str> = branches.iter().map(|b| b.as_str().unwrap()).collect();
+                format!(
+                    "{}: {}",
+                    string_field(row, "message").unwrap(),
+                    branches.join(",")
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let expected = vec![
+        "Octopus: main",
+        "Right: main,topic",
+        "Middle: main,topic",
+        "Left: main",
+        "Root: main,topic",
+    ];
+    let repo = octopus_fixture();
+    assert_eq!(check(&repo.adapter()), expected);
+    // Same answers when the search is pruned by generation numbers.
+    trustfall_git_adapter::commit_graph::write(repo.repo()).unwrap();
+    assert_eq!(check(&repo.adapter()), expected);
+}
+
+#[test]
+fn test_memory_backend_generations() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let rows = run_query(
+        &adapter,
+        "{ repository { commits { message @output generation @output } } }",
+    );
+    let generations: Vec<_> = rows.iter().map(|row| row["generation"].clone()).collect();
+    assert_eq!(
+        generations,
+        [3, 2, 2, 1].map(trustfall::FieldValue::Int64).to_vec()
+    );
+}
+
+const CHANGES_QUERY: &str = r#"
+{
+    repository {
+        commits {
+            hash @output
+            changes {
+                path @output
+            }
+        }
+    }
+}
+"#;
+
+fn execute_limited(adapter: &GitAdapter, query: &str, limits: Limits) -> LimitedResults {
+    adapter
+        .execute_with_limits(
+            query,
+            std::collections::BTreeMap::<&str, &str>::new(),
+            &limits,
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_execute_with_limits_unlimited() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let limited = execute_limited(&adapter, CHANGES_QUERY, Limits::default());
+    assert_eq!(limited.exceeded, None);
+    assert_eq!(limited.rows, run_query(&adapter, CHANGES_QUERY));
+    assert!(limited.objects_read > 0);
+}
+
+#[test]
+fn test_execute_with_limits_max_rows() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let all = run_query(&adapter, CHANGES_QUERY);
+
+    let limited = execute_limited(
+        &adapter,
+        CHANGES_QUERY,
+        Limits {
+            max_rows: Some(2),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(limited.exceeded, Some(LimitExceeded::MaxRows(2)));
+    assert_eq!(limited.rows, all[..2]);
+
+    // Exactly enough rows isn't a violation.
+    let limited = execute_limited(
+        &adapter,
+        CHANGES_QUERY,
+        Limits {
+            max_rows: Some(all.len()),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(limited.exceeded, None);
+    assert_eq!(limited.rows.len(), all.len());
+}
+
+#[test]
+fn test_execute_with_limits_max_objects() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let all = run_query(&adapter, CHANGES_QUERY);
+
+    let limited = execute_limited(
+        &adapter,
+        CHANGES_QUERY,
+        Limits {
+            max_objects: Some(10),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(limited.exceeded, Some(LimitExceeded::MaxObjects(10)));
+    assert!(limited.rows.len() < all.len());
+    // The rows that were returned are complete.
+    assert_eq!(limited.rows, all[..limited.rows.len()]);
+    assert_eq!(
+        LimitExceeded::MaxObjects(10).to_string(),
+        "query read more than 10 git objects"
+    );
+}
+
+#[test]
+fn test_execute_with_limits_timeout() {
+    let repo = octopus_fixture();
+    let limited = execute_limited(
+        &repo.adapter(),
+        CHANGES_QUERY,
+        Limits {
+            timeout: Some(std::time::Duration::ZERO),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(
+        limited.exceeded,
+        Some(LimitExceeded::Timeout(std::time::Duration::ZERO))
+    );
+    assert!(limited.rows.is_empty());
+}
+
+#[test]
+fn test_execute_with_limits_counts_discovered_repositories() {
+    let (first, _first_repo) = create_test_repo_with_multiple_commits();
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let query = format!(
+        r#"{{ repositories(paths: ["{}"]) {{ commits {{ hash @output }} }} }}"#,
+        first.path().display()
+    );
+
+    let limited = execute_limited(&adapter, &query, Limits::default());
+    assert_eq!(limited.rows.len(), 2);
+    assert!(limited.objects_read >= 2);
+
+    let limited = execute_limited(
+        &adapter,
+        &query,
+        Limits {
+            max_objects: Some(1),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(limited.exceeded, Some(LimitExceeded::MaxObjects(1)));
+}
+
+#[test]
+fn test_execute_with_limits_counts_reads_below_the_cache() {
+    let repo = cache_fixture();
+    let cached = GitAdapter::with_backend(Git2Backend::new(repo.repo()).with_cache());
+
+    // Both commits; for the second commit's changes, it and its parent again, their
+    // trees and three blobs; for the root commit's, it, its tree and two blobs.
+    let limited = execute_limited(&cached, CACHE_QUERY, Limits::default());
+    assert_eq!(limited.rows.len(), 2);
+    assert_eq!(limited.objects_read, 13);
+
+    // Answered from the cache, which reads nothing.
+    let limited = execute_limited(&cached, CACHE_QUERY, Limits::default());
+    assert_eq!(limited.rows.len(), 2);
+    assert_eq!(limited.objects_read, 0);
+}
+
+fn profile_entry<'a>(profile: &'a Profile, type_name: &str, name: &str) -> &'a ProfileEntry {
+    profile
+        .entries
+        .iter()
+        .find(|entry| entry.type_name == type_name && entry.name == name)
+        .unwrap_or_else(|| panic!("no profile entry for {type_name}.{name}"))
+}
+
+#[test]
+fn test_execute_profiled_counts_resolvers() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let query = r#"
+    {
+        repository {
+            commits {
+                message @filter(op: "=", value: ["$message"])
+                hash @output
+            }
+        }
+    }
+    "#;
+    let variables = std::collections::BTreeMap::from([("message", "Fix on main")]);
+
+    let (results, profile) = adapter
+        .execute_profiled(query, variables, &Limits::default())
+        .unwrap();
+    assert_eq!(results.rows.len(), 1);
+    assert_eq!(profile.objects_read, results.objects_read);
+
+    let root = profile_entry(&profile, "RootSchemaQuery", "repository");
+    assert_eq!(root.resolver, Resolver::StartingVertices);
+    assert_eq!((root.calls, root.vertices), (1, 1));
+
+    let commits = profile_entry(&profile, "Repository", "commits");
+    assert_eq!(commits.resolver, Resolver::Neighbors);
+    assert_eq!((commits.calls, commits.vertices), (1, 1));
+
+    // Every commit is filtered on its message, but only the match has its hash resolved.
+    let message = profile_entry(&profile, "Commit", "message");
+    assert_eq!(message.resolver, Resolver::Property);
+    assert_eq!(message.vertices, 4);
+    assert_eq!(profile_entry(&profile, "Commit", "hash").vertices, 1);
+}
+
+#[test]
+fn test_execute_profiled_matches_unprofiled_results() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let (results, profile) = adapter
+        .execute_profiled(
+            CHANGES_QUERY,
+            std::collections::BTreeMap::<&str, &str>::new(),
+            &Limits {
+                max_rows: Some(2),
+                ..Limits::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(results.exceeded, Some(LimitExceeded::MaxRows(2)));
+    assert_eq!(results.rows, run_query(&adapter, CHANGES_QUERY)[..2]);
+    assert!(profile.entries.iter().any(|entry| entry.name == "changes"));
+    assert!(profile.objects_read > 0);
+}
+
+/// Collects formatted log lines, for checking which spans a query opened.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(feature = "tracing")]
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_spans() {
+    use tracing_subscriber::{fmt::format::FmtSpan, util::SubscriberInitExt};
+
+    let repo = octopus_fixture();
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing_subscriber::filter::LevelFilter::TRACE)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    {
+        let _guard = subscriber.set_default();
+        execute_limited(&repo.adapter(), CHANGES_QUERY, Limits::default());
+    }
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    for expected in [
+        "query{",
+        "resolve_starting_vertices{edge=repository",
+        "resolve_neighbors{type_name=Repository edge=commits",
+        "resolve_property{type_name=FileChange property=path}",
+        "revwalk",
+        "find_commit{oid=",
+        "diff{commit=",
+    ] {
+        assert!(logs.contains(expected), "missing {expected:?} in:\n{logs}");
+    }
+}
+
+#[test]
+fn test_execute_streaming_passes_rows_in_order() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let mut rows = Vec::new();
+    let streamed = adapter
+        .execute_streaming(
+            CHANGES_QUERY,
+            std::collections::BTreeMap::<&str, &str>::new(),
+            &Limits {
+                max_rows: Some(3),
+                ..Limits::default()
+            },
+            |row| {
+                rows.push(row);
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert_eq!(streamed.rows, 3);
+    assert_eq!(streamed.exceeded, Some(LimitExceeded::MaxRows(3)));
+    assert_eq!(rows, run_query(&adapter, CHANGES_QUERY)[..3]);
+}
+
+#[test]
+fn test_execute_streaming_stops_on_row_error() {
+    let adapter = GitAdapter::with_backend(memory_fixture());
+    let mut seen = 0;
+    let error = adapter
+        .execute_streaming(
+            CHANGES_QUERY,
+            std::collections::BTreeMap::<&str, &str>::new(),
+            &Limits::default(),
+            |_| {
+                seen += 1;
+                anyhow::bail!("consumer went away")
+            },
+        )
+        .unwrap_err();
+    assert_eq!(error.to_string(), "consumer went away");
+    assert_eq!(seen, 1);
+}